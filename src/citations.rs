@@ -0,0 +1,233 @@
+//! Inline source citations for generated answers
+//!
+//! Prompt context is numbered (`[1] ...`, `[2] ...`) and the model is asked to
+//! cite with the same markers. Markers are parsed back out of the answer and
+//! mapped to the numbered sources, with character spans for both the marker
+//! and the claim it supports. Markers that refer to a source that was not in
+//! the prompt are kept but flagged as invalid.
+
+use nodespace_core_types::NodeId;
+use serde::{Deserialize, Serialize};
+
+/// Instruction appended to RAG prompts that carry numbered sources
+pub const CITATION_INSTRUCTION: &str = "Cite the numbered sources that support each statement with markers like [1] or [2][3]. Only cite sources listed above.";
+
+/// Maximum digits accepted in a citation marker
+const MAX_MARKER_DIGITS: usize = 3;
+
+/// A citation marker found in a generated answer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnswerCitation {
+    /// Source number as written in the answer (1-based)
+    pub marker: usize,
+    /// Character span of the bracketed marker, e.g. `[2]` or `[1, 3]`
+    pub marker_start: usize,
+    pub marker_end: usize,
+    /// Character span of the claim the marker supports
+    pub claim_start: usize,
+    pub claim_end: usize,
+    /// Index into the numbered sources, `None` for invalid markers
+    pub source_index: Option<usize>,
+    /// Cited node, `None` when the marker refers to a nonexistent source
+    pub node_id: Option<NodeId>,
+    pub valid: bool,
+}
+
+/// Prefix placed before the `number`th (1-based) source in a numbered prompt
+pub fn source_marker(number: usize) -> String {
+    format!("[{}] ", number)
+}
+
+/// Render context documents as a numbered list for citation-aware prompts
+pub fn number_sources(texts: &[String]) -> String {
+    texts
+        .iter()
+        .enumerate()
+        .map(|(index, text)| format!("{}{}", source_marker(index + 1), text))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Extract citation markers from an answer and map them to numbered sources
+///
+/// `sources` must be in the same order as they were numbered in the prompt.
+/// All offsets are character (not byte) offsets into `answer`.
+pub fn extract_citations(answer: &str, sources: &[NodeId]) -> Vec<AnswerCitation> {
+    let chars: Vec<char> = answer.chars().collect();
+    let mut citations = Vec::new();
+    // (marker_end, claim_start, claim_end) of the previous marker group
+    let mut previous_group: Option<(usize, usize, usize)> = None;
+    let mut position = 0;
+
+    while position < chars.len() {
+        if chars[position] != '[' {
+            position += 1;
+            continue;
+        }
+
+        let (markers, group_end) = match parse_marker_group(&chars, position) {
+            Some(parsed) => parsed,
+            None => {
+                position += 1;
+                continue;
+            }
+        };
+
+        // Adjacent groups like "[1][2]" support the same claim
+        let (claim_start, claim_end) = match previous_group {
+            Some((previous_end, start, end))
+                if chars[previous_end..position]
+                    .iter()
+                    .all(|c| c.is_whitespace()) =>
+            {
+                (start, end)
+            }
+            _ => claim_span(&chars, position),
+        };
+
+        for marker in markers {
+            let source_index = marker.checked_sub(1).filter(|index| *index < sources.len());
+            let node_id = source_index.map(|index| sources[index].clone());
+            citations.push(AnswerCitation {
+                marker,
+                marker_start: position,
+                marker_end: group_end,
+                claim_start,
+                claim_end,
+                source_index,
+                valid: node_id.is_some(),
+                node_id,
+            });
+        }
+
+        previous_group = Some((group_end, claim_start, claim_end));
+        position = group_end;
+    }
+
+    citations
+}
+
+/// Markers that do not correspond to any numbered source
+pub fn invalid_citations(citations: &[AnswerCitation]) -> Vec<&AnswerCitation> {
+    citations.iter().filter(|c| !c.valid).collect()
+}
+
+/// Parse `[n]` or `[n, m, ...]` starting at `start`, returning the numbers and the end offset
+fn parse_marker_group(chars: &[char], start: usize) -> Option<(Vec<usize>, usize)> {
+    let mut markers = Vec::new();
+    let mut digits = String::new();
+    let mut position = start + 1;
+
+    while position < chars.len() {
+        let c = chars[position];
+        if c.is_ascii_digit() {
+            digits.push(c);
+            if digits.len() > MAX_MARKER_DIGITS {
+                return None;
+            }
+        } else if c == ',' || c == ']' {
+            markers.push(digits.parse::<usize>().ok()?);
+            digits.clear();
+            if c == ']' {
+                return Some((markers, position + 1));
+            }
+        } else if c != ' ' {
+            return None;
+        }
+        position += 1;
+    }
+
+    None
+}
+
+/// Span of the sentence preceding a marker at `marker_start`
+fn claim_span(chars: &[char], marker_start: usize) -> (usize, usize) {
+    let mut end = marker_start;
+    while end > 0 && chars[end - 1].is_whitespace() {
+        end -= 1;
+    }
+
+    // A marker placed after the full stop cites the sentence that just ended
+    let mut scan = end;
+    if scan > 0 && is_sentence_terminator(chars[scan - 1]) {
+        scan -= 1;
+    }
+
+    let mut start = scan;
+    while start > 0 && !is_sentence_terminator(chars[start - 1]) && chars[start - 1] != ']' {
+        start -= 1;
+    }
+    while start < end && chars[start].is_whitespace() {
+        start += 1;
+    }
+
+    (start, end)
+}
+
+fn is_sentence_terminator(c: char) -> bool {
+    matches!(c, '.' | '!' | '?' | '\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(count: usize) -> Vec<NodeId> {
+        (1..=count)
+            .map(|n| NodeId::from_string(format!("node-{}", n)))
+            .collect()
+    }
+
+    fn span(answer: &str, start: usize, end: usize) -> String {
+        answer.chars().skip(start).take(end - start).collect()
+    }
+
+    #[test]
+    fn test_number_sources() {
+        let numbered = number_sources(&["Alpha".to_string(), "Beta".to_string()]);
+        assert_eq!(numbered, "[1] Alpha\n\n[2] Beta");
+    }
+
+    #[test]
+    fn test_citations_map_to_sources_with_claim_spans() {
+        let answer = "The budget was approved [2]. Claire leads the launch.[1]";
+        let citations = extract_citations(answer, &ids(2));
+
+        assert_eq!(citations.len(), 2);
+        assert_eq!(citations[0].node_id.as_ref().unwrap().as_str(), "node-2");
+        assert_eq!(
+            span(answer, citations[0].claim_start, citations[0].claim_end),
+            "The budget was approved"
+        );
+        assert_eq!(
+            span(answer, citations[0].marker_start, citations[0].marker_end),
+            "[2]"
+        );
+        assert_eq!(
+            span(answer, citations[1].claim_start, citations[1].claim_end),
+            "Claire leads the launch."
+        );
+    }
+
+    #[test]
+    fn test_grouped_and_adjacent_markers_share_claim() {
+        let answer = "Équipe validée [1, 2][3].";
+        let citations = extract_citations(answer, &ids(3));
+
+        let markers: Vec<usize> = citations.iter().map(|c| c.marker).collect();
+        assert_eq!(markers, vec![1, 2, 3]);
+        assert!(citations
+            .iter()
+            .all(|c| span(answer, c.claim_start, c.claim_end) == "Équipe validée"));
+    }
+
+    #[test]
+    fn test_nonexistent_sources_are_flagged() {
+        let answer = "Approved [4]. See [0] and [note].";
+        let citations = extract_citations(answer, &ids(2));
+
+        assert_eq!(citations.len(), 2);
+        assert_eq!(invalid_citations(&citations).len(), 2);
+        assert!(citations.iter().all(|c| c.node_id.is_none()));
+    }
+}
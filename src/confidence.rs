@@ -0,0 +1,377 @@
+//! Grounded confidence scoring for generated answers
+//!
+//! Confidence is derived from evidence rather than constants:
+//! - retrieval: how strong the best matching sources are
+//! - grounding: how much of the answer is backed by the sources, by lexical
+//!   overlap and optionally an entailment grade from the NLP engine
+//! - citation coverage: how many answer sentences cite a valid source
+//!
+//! The breakdown keeps every component plus human-readable reasons so the UI
+//! can explain why an answer scored low.
+
+use crate::citations::AnswerCitation;
+use crate::constants;
+use crate::generation::TextGenerator;
+use crate::prompts::{names, PromptRegistry};
+use crate::reranker::{lexical_overlap_score, parse_relevance_score};
+use nodespace_core_types::NodeSpaceResult;
+use serde::{Deserialize, Serialize};
+
+/// Share of a sentence's terms that must appear in one source for it to count as supported
+pub(crate) const SUPPORTED_SENTENCE_OVERLAP: f32 = 0.5;
+
+/// Components below this value are explained in `reasons`
+const LOW_COMPONENT_THRESHOLD: f32 = 0.5;
+
+/// Number of top retrieval scores averaged into the retrieval component
+const RETRIEVAL_TOP_K: usize = 3;
+
+/// Maximum characters of source context sent to the entailment check
+pub(crate) const MAX_ENTAILMENT_CONTEXT_CHARS: usize = 4000;
+
+/// Answer phrases that mark a canned fallback rather than a generated answer
+const FALLBACK_MARKERS: [&str; 2] = ["currently unable", "cannot generate"];
+
+/// Explainable confidence for a generated answer (all scores 0.0-1.0)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConfidenceBreakdown {
+    pub overall: f32,
+    /// Strength of the best retrieval matches
+    pub retrieval: f32,
+    /// Support of the answer by its sources
+    pub grounding: f32,
+    /// Entailment grade from the NLP engine, when the check ran
+    pub entailment: Option<f32>,
+    /// Share of answer sentences that cite a valid source
+    pub citation_coverage: f32,
+    pub supported_sentences: usize,
+    pub total_sentences: usize,
+    /// Explanations for low-scoring components
+    pub reasons: Vec<String>,
+}
+
+/// Score an answer against the context it was generated from
+///
+/// `context` and `scores` are the included sources and their retrieval
+/// scores. When `entailment` is given the NLP engine also grades how well the
+/// sources support the answer; failures fall back to lexical grounding only.
+pub async fn assess_confidence(
+    entailment: Option<&dyn TextGenerator>,
+    prompts: &PromptRegistry,
+    answer: &str,
+    context: &[String],
+    scores: &[f32],
+    citations: &[AnswerCitation],
+) -> ConfidenceBreakdown {
+    let mut breakdown = lexical_confidence(answer, context, scores, citations);
+    if context.is_empty() || is_fallback_answer(answer) {
+        return breakdown;
+    }
+
+    if let Some(generator) = entailment {
+        let graded = match entailment_prompt(prompts, answer, context) {
+            Ok(prompt) => generator.generate(&prompt).await,
+            Err(e) => Err(e),
+        };
+        match graded.map(|response| parse_relevance_score(&response)) {
+            Ok(Some(grade)) => {
+                breakdown.entailment = Some(grade);
+                breakdown.grounding = (breakdown.grounding + grade) / 2.0;
+                breakdown.overall = weighted_overall(&breakdown);
+                if grade < LOW_COMPONENT_THRESHOLD {
+                    breakdown.reasons.push(format!(
+                        "The NLP engine rated the answer as weakly supported by its sources ({:.0}/10)",
+                        grade * 10.0
+                    ));
+                }
+            }
+            Ok(None) => log::warn!("⚠️ Could not parse entailment grade"),
+            Err(e) => log::warn!("⚠️ Entailment check failed: {}", e),
+        }
+    }
+
+    breakdown
+}
+
+/// Confidence from retrieval scores, lexical grounding and citations only
+pub fn lexical_confidence(
+    answer: &str,
+    context: &[String],
+    scores: &[f32],
+    citations: &[AnswerCitation],
+) -> ConfidenceBreakdown {
+    let mut breakdown = ConfidenceBreakdown::default();
+
+    if context.is_empty() {
+        breakdown.overall = constants::BASE_CONFIDENCE_NO_CONTEXT;
+        breakdown
+            .reasons
+            .push("No matching notes were found; the answer relies on general knowledge".into());
+        return breakdown;
+    }
+
+    breakdown.retrieval = retrieval_confidence(scores);
+    if breakdown.retrieval < LOW_COMPONENT_THRESHOLD {
+        breakdown.reasons.push(format!(
+            "The best matching notes are weak matches (retrieval {:.2})",
+            breakdown.retrieval
+        ));
+    }
+
+    let sentences = sentence_spans(answer);
+    let chars: Vec<char> = answer.chars().collect();
+    breakdown.total_sentences = sentences.len();
+    breakdown.supported_sentences = sentences
+        .iter()
+        .filter(|(start, end)| {
+            let sentence: String = chars[*start..*end].iter().collect();
+            context.iter().any(|source| {
+                lexical_overlap_score(&sentence, source) >= SUPPORTED_SENTENCE_OVERLAP
+            })
+        })
+        .count();
+    let cited_sentences = sentences
+        .iter()
+        .filter(|(start, end)| {
+            citations.iter().any(|c| {
+                c.valid
+                    && ((c.claim_start >= *start && c.claim_start < *end)
+                        || (c.marker_start >= *start && c.marker_start < *end))
+            })
+        })
+        .count();
+
+    if !sentences.is_empty() {
+        breakdown.grounding = breakdown.supported_sentences as f32 / sentences.len() as f32;
+        breakdown.citation_coverage = cited_sentences as f32 / sentences.len() as f32;
+    }
+    if breakdown.grounding < LOW_COMPONENT_THRESHOLD {
+        breakdown.reasons.push(format!(
+            "Only {} of {} answer sentences overlap with the source notes",
+            breakdown.supported_sentences, breakdown.total_sentences
+        ));
+    }
+    if breakdown.citation_coverage < LOW_COMPONENT_THRESHOLD {
+        breakdown.reasons.push(format!(
+            "Only {} of {} answer sentences cite a source",
+            cited_sentences, breakdown.total_sentences
+        ));
+    }
+
+    let invalid = citations.iter().filter(|c| !c.valid).count();
+    if invalid > 0 {
+        breakdown.reasons.push(format!(
+            "{} citation(s) refer to sources that were not provided",
+            invalid
+        ));
+    }
+
+    breakdown.overall = weighted_overall(&breakdown);
+    if is_fallback_answer(answer) {
+        breakdown.overall *= constants::FALLBACK_CONFIDENCE_FACTOR;
+        breakdown
+            .reasons
+            .push("The answer is a fallback message, not a generated answer".into());
+    }
+
+    breakdown
+}
+
+/// Blend of the best score and the mean of the top scores, clamped to 0.0-1.0
+pub fn retrieval_confidence(scores: &[f32]) -> f32 {
+    let mut sorted: Vec<f32> = scores.iter().map(|s| s.clamp(0.0, 1.0)).collect();
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    sorted.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+
+    let top = sorted[0];
+    let top_k = &sorted[..sorted.len().min(RETRIEVAL_TOP_K)];
+    let mean = top_k.iter().sum::<f32>() / top_k.len() as f32;
+    (top + mean) / 2.0
+}
+
+fn weighted_overall(breakdown: &ConfidenceBreakdown) -> f32 {
+    (constants::CONFIDENCE_RETRIEVAL_WEIGHT * breakdown.retrieval
+        + constants::CONFIDENCE_GROUNDING_WEIGHT * breakdown.grounding
+        + constants::CONFIDENCE_CITATION_WEIGHT * breakdown.citation_coverage)
+        .clamp(0.0, 1.0)
+}
+
+fn is_fallback_answer(answer: &str) -> bool {
+    FALLBACK_MARKERS
+        .iter()
+        .any(|marker| answer.contains(marker))
+}
+
+fn entailment_prompt(
+    prompts: &PromptRegistry,
+    answer: &str,
+    context: &[String],
+) -> NodeSpaceResult<String> {
+    let sources: String = context
+        .join("\n\n")
+        .chars()
+        .take(MAX_ENTAILMENT_CONTEXT_CHARS)
+        .collect();
+    prompts
+        .render(
+            names::GRADE_ENTAILMENT,
+            &[("sources", &sources), ("answer", answer)],
+        )
+        .map(|prompt| prompt.text)
+}
+
+/// Character spans of answer sentences, ignoring fragments that are only citation markers
+///
+/// Markers placed after a full stop (`... done.[1] Next`) belong to the
+/// previous sentence, so spans start after them.
+pub(crate) fn sentence_spans(answer: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = answer.chars().collect();
+    let mut spans = Vec::new();
+    let mut start = 0;
+
+    for end in 0..=chars.len() {
+        let at_boundary = end == chars.len() || matches!(chars[end], '.' | '!' | '?' | '\n');
+        if !at_boundary {
+            continue;
+        }
+        if has_words(&chars[start..end]) {
+            spans.push((skip_leading_markers(&chars, start, end), end));
+        }
+        start = end + 1;
+    }
+
+    spans
+}
+
+/// First position in `start..end` after whitespace and `[n]` marker groups
+fn skip_leading_markers(chars: &[char], mut start: usize, end: usize) -> usize {
+    loop {
+        while start < end && chars[start].is_whitespace() {
+            start += 1;
+        }
+        if start >= end || chars[start] != '[' {
+            return start;
+        }
+        let close = match chars[start..end].iter().position(|c| *c == ']') {
+            Some(offset) => start + offset,
+            None => return start,
+        };
+        let is_marker = chars[start + 1..close]
+            .iter()
+            .all(|c| c.is_ascii_digit() || *c == ',' || *c == ' ');
+        if !is_marker {
+            return start;
+        }
+        start = close + 1;
+    }
+}
+
+fn has_words(chars: &[char]) -> bool {
+    let mut in_marker = false;
+    for c in chars {
+        match c {
+            '[' => in_marker = true,
+            ']' => in_marker = false,
+            c if c.is_alphabetic() && !in_marker => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::citations::extract_citations;
+    use async_trait::async_trait;
+    use nodespace_core_types::NodeId;
+
+    struct FixedGrade(&'static str);
+
+    #[async_trait]
+    impl TextGenerator for FixedGrade {
+        async fn generate(&self, _prompt: &str) -> NodeSpaceResult<String> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    fn sources() -> (Vec<String>, Vec<NodeId>) {
+        (
+            vec![
+                "The Q3 marketing budget of $50k was approved by Claire".to_string(),
+                "The product launch is planned for March".to_string(),
+            ],
+            vec![
+                NodeId::from_string("budget".to_string()),
+                NodeId::from_string("launch".to_string()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_grounded_cited_answer_scores_high() {
+        let (context, ids) = sources();
+        let answer =
+            "Claire approved the marketing budget [1]. The launch is planned for March [2].";
+        let citations = extract_citations(answer, &ids);
+
+        let breakdown = lexical_confidence(answer, &context, &[0.9, 0.8], &citations);
+
+        assert_eq!(breakdown.total_sentences, 2);
+        assert_eq!(breakdown.supported_sentences, 2);
+        assert_eq!(breakdown.citation_coverage, 1.0);
+        assert!(breakdown.overall > 0.8);
+        assert!(breakdown.reasons.is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_uncited_answer_is_explained() {
+        let (context, ids) = sources();
+        let answer = "The team moved offices in July. Lunch was catered by a new vendor [5].";
+        let citations = extract_citations(answer, &ids);
+
+        let breakdown = lexical_confidence(answer, &context, &[0.3, 0.1], &citations);
+
+        assert_eq!(breakdown.grounding, 0.0);
+        assert_eq!(breakdown.citation_coverage, 0.0);
+        assert!(breakdown.overall < 0.3);
+        assert_eq!(breakdown.reasons.len(), 4);
+
+        let no_context = lexical_confidence(answer, &[], &[], &[]);
+        assert_eq!(no_context.overall, constants::BASE_CONFIDENCE_NO_CONTEXT);
+    }
+
+    #[tokio::test]
+    async fn test_entailment_grade_blends_into_grounding() {
+        let (context, ids) = sources();
+        let answer = "Claire approved the marketing budget [1].";
+        let citations = extract_citations(answer, &ids);
+
+        let lexical = assess_confidence(
+            None,
+            &PromptRegistry::default(),
+            answer,
+            &context,
+            &[0.9],
+            &citations,
+        )
+        .await;
+        let graded = assess_confidence(
+            Some(&FixedGrade("2")),
+            &PromptRegistry::default(),
+            answer,
+            &context,
+            &[0.9],
+            &citations,
+        )
+        .await;
+
+        assert_eq!(lexical.entailment, None);
+        assert_eq!(graded.entailment, Some(0.2));
+        assert!((graded.grounding - 0.6).abs() < 1e-6);
+        assert!(graded.overall < lexical.overall);
+        assert_eq!(graded.reasons.len(), 1);
+    }
+}
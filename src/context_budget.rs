@@ -0,0 +1,277 @@
+//! Token budgeting for RAG prompt context
+//!
+//! Packs whole retrieved documents into the prompt by score until the token
+//! budget is spent. The budget is the model context window minus the tokens
+//! reserved for the answer and the tokens used by the prompt scaffold, so
+//! documents are never cut mid-text and the answer always has room. Each
+//! source is charged for its citation marker as well as its text, since the
+//! prompt numbers the packed sources.
+
+use crate::citations::source_marker;
+use nodespace_core_types::NodeId;
+use serde::{Deserialize, Serialize};
+
+/// Separator placed between packed context documents
+pub const CONTEXT_SEPARATOR: &str = "\n\n";
+
+/// Counts tokens for budgeting purposes
+///
+/// Implement this with the generation model's tokenizer for exact counts;
+/// `HeuristicTokenCounter` is used when no tokenizer is available.
+pub trait TokenCounter: Send + Sync {
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+/// Conservative tokenizer-free estimate
+///
+/// ASCII text is counted at ~4 characters per token per word, and every
+/// non-ASCII character as its own token, which over- rather than
+/// under-estimates for most BPE tokenizers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenCounter;
+
+impl TokenCounter for HeuristicTokenCounter {
+    fn count_tokens(&self, text: &str) -> usize {
+        text.split_whitespace()
+            .map(|word| {
+                let total_chars = word.chars().count();
+                let ascii_chars = word.chars().filter(|c| c.is_ascii()).count();
+                let non_ascii_chars = total_chars - ascii_chars;
+                (ascii_chars.div_ceil(4) + non_ascii_chars).max(1)
+            })
+            .sum()
+    }
+}
+
+/// Token budget for a single generation request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenBudget {
+    /// Total model context window in tokens
+    pub context_window: usize,
+    /// Tokens reserved for the generated answer
+    pub reserved_output_tokens: usize,
+    /// Tokens used by the prompt scaffold (instructions + question)
+    pub prompt_tokens: usize,
+}
+
+impl TokenBudget {
+    /// Tokens left for retrieved context
+    pub fn available_context_tokens(&self) -> usize {
+        self.context_window
+            .saturating_sub(self.reserved_output_tokens)
+            .saturating_sub(self.prompt_tokens)
+    }
+}
+
+/// Retrieved document competing for a place in the prompt
+#[derive(Debug, Clone)]
+pub struct ContextCandidate {
+    pub node_id: NodeId,
+    pub content: String,
+    pub score: f32,
+}
+
+/// Token accounting for one source document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetedSource {
+    pub node_id: NodeId,
+    pub tokens: usize,
+    pub score: f32,
+}
+
+/// Which sources made it into the prompt and which were dropped for budget
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContextBudgetReport {
+    pub context_window: usize,
+    pub reserved_output_tokens: usize,
+    /// Tokens available for context after reservations
+    pub budget_tokens: usize,
+    /// Tokens consumed by included sources (including markers and separators)
+    pub used_tokens: usize,
+    pub included: Vec<BudgetedSource>,
+    pub dropped: Vec<BudgetedSource>,
+    /// Hits already shown inside an included source, at no extra cost
+    #[serde(default)]
+    pub deduplicated: Vec<BudgetedSource>,
+}
+
+/// Result of packing candidates into a token budget
+#[derive(Debug, Clone, Default)]
+pub struct ContextPlan {
+    /// Included candidates, highest score first
+    pub included: Vec<ContextCandidate>,
+    pub report: ContextBudgetReport,
+}
+
+impl ContextPlan {
+    /// Contents of the included sources in prompt order
+    pub fn context_texts(&self) -> Vec<String> {
+        self.included.iter().map(|c| c.content.clone()).collect()
+    }
+
+    /// Node IDs of the included sources in prompt order
+    pub fn source_ids(&self) -> Vec<NodeId> {
+        self.included.iter().map(|c| c.node_id.clone()).collect()
+    }
+
+    /// Included contents joined with `CONTEXT_SEPARATOR`
+    pub fn joined_context(&self) -> String {
+        self.context_texts().join(CONTEXT_SEPARATOR)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.included.is_empty()
+    }
+}
+
+/// Pack whole candidates by descending score until the budget is spent
+///
+/// A candidate that does not fit is dropped, but packing continues so that
+/// smaller lower-scored documents can still use the remaining budget.
+pub fn plan_context(
+    counter: &dyn TokenCounter,
+    mut candidates: Vec<ContextCandidate>,
+    budget: TokenBudget,
+) -> ContextPlan {
+    candidates.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let budget_tokens = budget.available_context_tokens();
+    let separator_tokens = counter.count_tokens(CONTEXT_SEPARATOR);
+    let mut used_tokens = 0;
+    let mut included = Vec::new();
+    let mut report = ContextBudgetReport {
+        context_window: budget.context_window,
+        reserved_output_tokens: budget.reserved_output_tokens,
+        budget_tokens,
+        ..Default::default()
+    };
+
+    for candidate in candidates {
+        let tokens = counter.count_tokens(&candidate.content);
+        let cost = packing_cost(counter, tokens, included.len(), separator_tokens);
+        let entry = BudgetedSource {
+            node_id: candidate.node_id.clone(),
+            tokens,
+            score: candidate.score,
+        };
+
+        if tokens > 0 && used_tokens + cost <= budget_tokens {
+            used_tokens += cost;
+            report.included.push(entry);
+            included.push(candidate);
+        } else {
+            report.dropped.push(entry);
+        }
+    }
+
+    report.used_tokens = used_tokens;
+    ContextPlan { included, report }
+}
+
+/// Tokens a source adds when packed after `packed` others: its marker, text
+/// and, after the first, a separator
+pub(crate) fn packing_cost(
+    counter: &dyn TokenCounter,
+    tokens: usize,
+    packed: usize,
+    separator_tokens: usize,
+) -> usize {
+    let marker_tokens = counter.count_tokens(&source_marker(packed + 1));
+    let separator_tokens = if packed == 0 { 0 } else { separator_tokens };
+    tokens + marker_tokens + separator_tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::citations::number_sources;
+
+    /// One token per whitespace-separated word
+    struct WordCounter;
+
+    impl TokenCounter for WordCounter {
+        fn count_tokens(&self, text: &str) -> usize {
+            text.split_whitespace().count()
+        }
+    }
+
+    fn candidate(id: &str, content: &str, score: f32) -> ContextCandidate {
+        ContextCandidate {
+            node_id: NodeId::from_string(id.to_string()),
+            content: content.to_string(),
+            score,
+        }
+    }
+
+    #[test]
+    fn test_budget_reserves_output_and_prompt_tokens() {
+        let budget = TokenBudget {
+            context_window: 100,
+            reserved_output_tokens: 60,
+            prompt_tokens: 30,
+        };
+        assert_eq!(budget.available_context_tokens(), 10);
+
+        let overcommitted = TokenBudget {
+            context_window: 50,
+            reserved_output_tokens: 60,
+            prompt_tokens: 30,
+        };
+        assert_eq!(overcommitted.available_context_tokens(), 0);
+    }
+
+    #[test]
+    fn test_plan_packs_whole_documents_by_score() {
+        let budget = TokenBudget {
+            context_window: 20,
+            reserved_output_tokens: 10,
+            prompt_tokens: 0,
+        };
+        let plan = plan_context(
+            &WordCounter,
+            vec![
+                candidate("low", "one two", 0.2),
+                candidate("big", "a b c d e f g h i", 0.8),
+                candidate("best", "alpha beta gamma", 0.9),
+            ],
+            budget,
+        );
+
+        let included: Vec<&str> = plan.included.iter().map(|c| c.node_id.as_str()).collect();
+        assert_eq!(included, vec!["best", "low"]);
+        assert_eq!(plan.report.dropped.len(), 1);
+        assert_eq!(plan.report.dropped[0].node_id.as_str(), "big");
+        // Each source is charged for its "[n] " marker as well as its text
+        assert_eq!(plan.report.used_tokens, 7);
+        assert_eq!(
+            WordCounter.count_tokens(&number_sources(&plan.context_texts())),
+            plan.report.used_tokens
+        );
+        assert!(plan.report.used_tokens <= plan.report.budget_tokens);
+    }
+
+    #[test]
+    fn test_heuristic_counter_handles_multibyte_text() {
+        let counter = HeuristicTokenCounter;
+        assert_eq!(counter.count_tokens(""), 0);
+        assert_eq!(counter.count_tokens("hello world"), 4);
+        assert_eq!(counter.count_tokens("会議は承認"), 5);
+
+        // Multibyte content is packed or dropped whole, never sliced
+        let plan = plan_context(
+            &counter,
+            vec![candidate("jp", "予算は承認されました ✅", 1.0)],
+            TokenBudget {
+                context_window: 4,
+                reserved_output_tokens: 0,
+                prompt_tokens: 0,
+            },
+        );
+        assert!(plan.is_empty());
+        assert_eq!(plan.report.dropped.len(), 1);
+    }
+}
@@ -0,0 +1,306 @@
+//! Hierarchy-aware expansion of retrieved RAG context
+//!
+//! A retrieved bullet like "Approved" means little without its parent. When
+//! enabled, each hit is expanded with its ancestry path and nearby siblings and
+//! children, rendered as an indented outline. Nodes already shown for a
+//! higher-scored hit are not repeated, and blocks are packed within the same
+//! token budget as plain context.
+
+use crate::context_budget::{
+    packing_cost, BudgetedSource, ContextBudgetReport, ContextCandidate, ContextPlan, TokenBudget,
+    TokenCounter, CONTEXT_SEPARATOR,
+};
+use nodespace_core_types::{Node, NodeId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Maximum characters shown for an ancestor that was already rendered in full
+const ANCESTOR_BREADCRUMB_CHARS: usize = 40;
+
+/// Options for expanding retrieved nodes with their surrounding hierarchy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HierarchyContextOptions {
+    /// Expand hits at all (plain content is used when false)
+    pub enabled: bool,
+    /// Maximum number of ancestors shown above each hit (nearest first)
+    pub max_ancestors: usize,
+    /// Maximum number of siblings shown next to each hit
+    pub max_siblings: usize,
+    /// Maximum number of children shown below each hit
+    pub max_children: usize,
+}
+
+impl Default for HierarchyContextOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_ancestors: 5,
+            max_siblings: 3,
+            max_children: 5,
+        }
+    }
+}
+
+impl HierarchyContextOptions {
+    /// Expansion enabled with default limits
+    pub fn enabled() -> Self {
+        Self {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// A retrieved node together with the hierarchy around it
+#[derive(Debug, Clone)]
+pub struct ExpandedHit {
+    pub node: Node,
+    pub score: f32,
+    /// Ancestors ordered from the root down to the direct parent
+    pub ancestors: Vec<Node>,
+    pub siblings: Vec<Node>,
+    pub children: Vec<Node>,
+}
+
+/// Pack expanded hits by descending score, rendering each as an outline block
+///
+/// Blocks that do not fit are retried without siblings and children before
+/// being dropped. A hit already rendered inside a previous block is reported
+/// as deduplicated rather than included, so it is not a source of its own.
+pub fn plan_expanded_context(
+    counter: &dyn TokenCounter,
+    mut hits: Vec<ExpandedHit>,
+    budget: TokenBudget,
+) -> ContextPlan {
+    hits.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let budget_tokens = budget.available_context_tokens();
+    let separator_tokens = counter.count_tokens(CONTEXT_SEPARATOR);
+    let mut seen: HashSet<NodeId> = HashSet::new();
+    let mut used_tokens = 0;
+    let mut included = Vec::new();
+    let mut report = ContextBudgetReport {
+        context_window: budget.context_window,
+        reserved_output_tokens: budget.reserved_output_tokens,
+        budget_tokens,
+        ..Default::default()
+    };
+
+    for hit in hits {
+        if seen.contains(&hit.node.id) {
+            report.deduplicated.push(BudgetedSource {
+                node_id: hit.node.id.clone(),
+                tokens: 0,
+                score: hit.score,
+            });
+            continue;
+        }
+
+        let full = render_outline(&hit, &seen, true);
+        let minimal = render_outline(&hit, &seen, false);
+        let mut accepted = None;
+        let mut tokens = 0;
+
+        for (text, shown) in [full, minimal] {
+            tokens = counter.count_tokens(&text);
+            let cost = packing_cost(counter, tokens, included.len(), separator_tokens);
+            if tokens > 0 && used_tokens + cost <= budget_tokens {
+                used_tokens += cost;
+                accepted = Some((text, shown));
+                break;
+            }
+        }
+
+        let entry = BudgetedSource {
+            node_id: hit.node.id.clone(),
+            tokens,
+            score: hit.score,
+        };
+
+        match accepted {
+            Some((text, shown)) => {
+                seen.extend(shown);
+                report.included.push(entry);
+                included.push(ContextCandidate {
+                    node_id: hit.node.id.clone(),
+                    content: text,
+                    score: hit.score,
+                });
+            }
+            None => report.dropped.push(entry),
+        }
+    }
+
+    report.used_tokens = used_tokens;
+    ContextPlan { included, report }
+}
+
+/// Render one hit as an indented outline, returning the text and the node IDs shown in full
+fn render_outline(
+    hit: &ExpandedHit,
+    seen: &HashSet<NodeId>,
+    include_neighbours: bool,
+) -> (String, Vec<NodeId>) {
+    let mut lines = Vec::new();
+    let mut shown = Vec::new();
+    let mut depth = 0;
+
+    for ancestor in &hit.ancestors {
+        if let Some(label) = node_label(ancestor) {
+            if seen.contains(&ancestor.id) {
+                lines.push(outline_line(depth, &breadcrumb(&label)));
+            } else {
+                lines.push(outline_line(depth, &label));
+                shown.push(ancestor.id.clone());
+            }
+            depth += 1;
+        }
+    }
+
+    if let Some(label) = node_label(&hit.node) {
+        lines.push(outline_line(depth, &label));
+        shown.push(hit.node.id.clone());
+    }
+
+    if include_neighbours {
+        for child in &hit.children {
+            if let Some(label) = node_label(child).filter(|_| !seen.contains(&child.id)) {
+                lines.push(outline_line(depth + 1, &label));
+                shown.push(child.id.clone());
+            }
+        }
+        for sibling in &hit.siblings {
+            if let Some(label) = node_label(sibling).filter(|_| !seen.contains(&sibling.id)) {
+                lines.push(outline_line(depth, &label));
+                shown.push(sibling.id.clone());
+            }
+        }
+    }
+
+    (lines.join("\n"), shown)
+}
+
+/// Human-readable label for a node, using the date for content-less date nodes
+fn node_label(node: &Node) -> Option<String> {
+    match node.content.as_str() {
+        Some(content) if !content.trim().is_empty() => Some(content.trim().to_string()),
+        _ if node.r#type == "date" => Some(format!("Date: {}", node.id)),
+        _ => None,
+    }
+}
+
+fn breadcrumb(label: &str) -> String {
+    if label.chars().count() > ANCESTOR_BREADCRUMB_CHARS {
+        let short: String = label.chars().take(ANCESTOR_BREADCRUMB_CHARS).collect();
+        format!("{}…", short)
+    } else {
+        label.to_string()
+    }
+}
+
+fn outline_line(depth: usize, label: &str) -> String {
+    // Keep multi-line content inside its bullet
+    let indent = "  ".repeat(depth);
+    let continuation = format!("\n{}  ", indent);
+    format!("{}- {}", indent, label.replace('\n', &continuation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_budget::HeuristicTokenCounter;
+    use serde_json::json;
+
+    fn node(id: &str, content: &str) -> Node {
+        let mut node = Node::new("text".to_string(), json!(content));
+        node.id = NodeId::from_string(id.to_string());
+        node
+    }
+
+    fn roomy_budget() -> TokenBudget {
+        TokenBudget {
+            context_window: 1000,
+            reserved_output_tokens: 0,
+            prompt_tokens: 0,
+        }
+    }
+
+    #[test]
+    fn test_hit_rendered_under_its_ancestry() {
+        let mut date = Node::new("date".to_string(), serde_json::Value::Null);
+        date.id = NodeId::from_string("2026-10-17".to_string());
+        let hit = ExpandedHit {
+            node: node("approved", "Approved"),
+            score: 0.9,
+            ancestors: vec![date, node("budget", "Q3 marketing budget")],
+            siblings: vec![node("pending", "Pending legal review")],
+            children: vec![node("amount", "$50k total")],
+        };
+
+        let plan = plan_expanded_context(&HeuristicTokenCounter, vec![hit], roomy_budget());
+
+        assert_eq!(
+            plan.included[0].content,
+            "- Date: 2026-10-17\n  - Q3 marketing budget\n    - Approved\n      - $50k total\n    - Pending legal review"
+        );
+    }
+
+    #[test]
+    fn test_shared_nodes_deduplicated_across_hits() {
+        let parent = node("parent", "Launch checklist");
+        let first = ExpandedHit {
+            node: node("a", "Book venue"),
+            score: 0.9,
+            ancestors: vec![parent.clone()],
+            siblings: vec![node("b", "Send invites")],
+            children: vec![],
+        };
+        let second = ExpandedHit {
+            node: node("b", "Send invites"),
+            score: 0.5,
+            ancestors: vec![parent],
+            siblings: vec![node("a", "Book venue")],
+            children: vec![],
+        };
+
+        let plan =
+            plan_expanded_context(&HeuristicTokenCounter, vec![second, first], roomy_budget());
+
+        // The second hit is already covered as a sibling of the first
+        assert_eq!(
+            plan.source_ids(),
+            vec![NodeId::from_string("a".to_string())]
+        );
+        assert_eq!(plan.report.included.len(), 1);
+        assert_eq!(plan.report.deduplicated.len(), 1);
+        assert_eq!(plan.report.deduplicated[0].node_id.as_str(), "b");
+        assert_eq!(plan.joined_context().matches("Send invites").count(), 1);
+    }
+
+    #[test]
+    fn test_block_falls_back_to_hit_only_when_budget_is_tight() {
+        let hit = ExpandedHit {
+            node: node("hit", "Approved"),
+            score: 1.0,
+            ancestors: vec![],
+            siblings: vec![node(
+                "s",
+                "A long sibling note that does not fit the budget",
+            )],
+            children: vec![],
+        };
+        let budget = TokenBudget {
+            context_window: 4,
+            reserved_output_tokens: 0,
+            prompt_tokens: 0,
+        };
+
+        let plan = plan_expanded_context(&HeuristicTokenCounter, vec![hit], budget);
+
+        assert_eq!(plan.included[0].content, "- Approved");
+    }
+}
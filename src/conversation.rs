@@ -0,0 +1,432 @@
+//! Multi-turn conversational RAG
+//!
+//! A `ConversationSession` keeps the turns of a chat. Follow-up questions such
+//! as "what about the budget?" are rewritten into standalone retrieval queries
+//! using the prior turns, and the answer prompt carries a bounded window of
+//! recent history. Sessions can optionally be persisted as a single `ai-chat`
+//! node through `upsert_node`; only the chat title is embedded, the turns are
+//! kept in metadata.
+
+use crate::generation::TextGenerator;
+use crate::prompts::{names, PromptRegistry};
+use crate::{constants, DataStore, NLPEngine, NodeSpaceService, PromptStyle, QueryResponse};
+use chrono::{DateTime, NaiveDate, Utc};
+use nodespace_core_types::{NodeId, NodeSpaceError, NodeSpaceResult};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Maximum characters of a single prior turn rendered into prompts
+const MAX_HISTORY_TURN_CHARS: usize = 500;
+
+/// Rewrites longer than this are treated as the model rambling and discarded
+const MAX_REWRITE_CHARS: usize = 300;
+
+/// Maximum characters of the first question used as the chat title
+const MAX_TITLE_CHARS: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    User,
+    Assistant,
+}
+
+impl ChatRole {
+    fn label(&self) -> &'static str {
+        match self {
+            ChatRole::User => "User",
+            ChatRole::Assistant => "Assistant",
+        }
+    }
+}
+
+/// One message in a conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationTurn {
+    pub role: ChatRole,
+    pub content: String,
+    /// Standalone query used for retrieval (user turns only)
+    #[serde(default)]
+    pub retrieval_query: Option<String>,
+    /// Sources the answer was grounded on (assistant turns only)
+    #[serde(default)]
+    pub sources: Vec<NodeId>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Where a session is stored as an `ai-chat` node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatPersistence {
+    pub date: NaiveDate,
+    pub parent_id: Option<NodeId>,
+}
+
+/// State of a multi-turn conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSession {
+    /// Session identifier, also the `ai-chat` node ID when persisted
+    pub id: NodeId,
+    pub turns: Vec<ConversationTurn>,
+    /// Maximum number of prior turns included in prompts
+    pub history_window: usize,
+    /// Persist the session as an `ai-chat` node after every exchange
+    #[serde(default)]
+    pub persistence: Option<ChatPersistence>,
+}
+
+impl Default for ConversationSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConversationSession {
+    pub fn new() -> Self {
+        Self {
+            id: NodeId::new(),
+            turns: Vec::new(),
+            history_window: constants::DEFAULT_CONVERSATION_HISTORY_TURNS,
+            persistence: None,
+        }
+    }
+
+    /// Number of prior turns included in prompts
+    pub fn with_history_window(mut self, turns: usize) -> Self {
+        self.history_window = turns;
+        self
+    }
+
+    /// Store the session as an `ai-chat` node under the given date
+    pub fn persist_as_node(mut self, date: NaiveDate, parent_id: Option<NodeId>) -> Self {
+        self.persistence = Some(ChatPersistence { date, parent_id });
+        self
+    }
+
+    /// The most recent turns, bounded by `history_window`
+    pub fn recent_turns(&self) -> &[ConversationTurn] {
+        let start = self.turns.len().saturating_sub(self.history_window);
+        &self.turns[start..]
+    }
+
+    /// Title of the chat: the first user question
+    pub fn title(&self) -> String {
+        self.turns
+            .iter()
+            .find(|turn| turn.role == ChatRole::User)
+            .map(|turn| truncate_chars(&turn.content, MAX_TITLE_CHARS))
+            .unwrap_or_default()
+    }
+
+    pub fn push_user_turn(&mut self, content: &str, retrieval_query: Option<String>) {
+        self.turns.push(ConversationTurn {
+            role: ChatRole::User,
+            content: content.to_string(),
+            retrieval_query,
+            sources: Vec::new(),
+            timestamp: Utc::now(),
+        });
+    }
+
+    pub fn push_assistant_turn(&mut self, content: &str, sources: Vec<NodeId>) {
+        self.turns.push(ConversationTurn {
+            role: ChatRole::Assistant,
+            content: content.to_string(),
+            retrieval_query: None,
+            sources,
+            timestamp: Utc::now(),
+        });
+    }
+}
+
+/// Render turns as a `User: ...` / `Assistant: ...` transcript
+pub fn render_history(turns: &[ConversationTurn]) -> String {
+    turns
+        .iter()
+        .map(|turn| {
+            format!(
+                "{}: {}",
+                turn.role.label(),
+                truncate_chars(turn.content.trim(), MAX_HISTORY_TURN_CHARS)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Rewrite a follow-up question into a standalone retrieval query
+///
+/// Returns the question unchanged when there is no history. Falls back to
+/// `fallback_rewrite` when generation fails or the rewrite is unusable.
+pub async fn rewrite_follow_up(
+    generator: &dyn TextGenerator,
+    prompts: &PromptRegistry,
+    history: &[ConversationTurn],
+    question: &str,
+) -> String {
+    if history.is_empty() {
+        return question.to_string();
+    }
+
+    let history_text = render_history(history);
+    let generated = match prompts.render(
+        names::REWRITE_FOLLOW_UP,
+        &[("history", &history_text), ("question", question)],
+    ) {
+        Ok(prompt) => generator.generate(&prompt.text).await,
+        Err(e) => Err(e),
+    };
+
+    match generated {
+        Ok(response) => match clean_rewrite(&response) {
+            Some(rewritten) => rewritten,
+            None => {
+                log::warn!("⚠️ Unusable follow-up rewrite, using fallback");
+                fallback_rewrite(history, question)
+            }
+        },
+        Err(e) => {
+            log::warn!("⚠️ Follow-up rewrite failed, using fallback: {}", e);
+            fallback_rewrite(history, question)
+        }
+    }
+}
+
+/// Deterministic rewrite: prefix the follow-up with the previous user question
+pub fn fallback_rewrite(history: &[ConversationTurn], question: &str) -> String {
+    match history
+        .iter()
+        .rev()
+        .find(|turn| turn.role == ChatRole::User)
+    {
+        Some(previous) => format!("{} {}", previous.content.trim(), question.trim()),
+        None => question.to_string(),
+    }
+}
+
+/// First line of the response without labels or quotes, if usable
+fn clean_rewrite(response: &str) -> Option<String> {
+    let line = response.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = line
+        .strip_prefix("Standalone query:")
+        .unwrap_or(line)
+        .trim();
+    let line = line
+        .trim_matches(|c| c == '"' || c == '\'' || c == '`')
+        .trim();
+
+    if line.is_empty() || line.chars().count() > MAX_REWRITE_CHARS {
+        None
+    } else {
+        Some(line.to_string())
+    }
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    if text.chars().count() > max_chars {
+        let short: String = text.chars().take(max_chars).collect();
+        format!("{}…", short)
+    } else {
+        text.to_string()
+    }
+}
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Answer the next question in a conversation
+    ///
+    /// Follow-ups are rewritten into standalone queries for retrieval, the
+    /// prompt carries the session's recent history, and the request is sent
+    /// with `conversation_mode: true`. Both turns are appended to the session,
+    /// which is persisted as an `ai-chat` node when configured.
+    pub async fn process_conversational_query(
+        &self,
+        session: &mut ConversationSession,
+        question: &str,
+    ) -> NodeSpaceResult<QueryResponse> {
+        log::info!(
+            "💬 Conversational query in session {} ({} prior turns): '{}'",
+            session.id,
+            session.turns.len(),
+            question
+        );
+
+        if !self.is_ready().await {
+            return Err(NodeSpaceError::InternalError {
+                message: "Service not ready for conversational query".to_string(),
+                service: "core-logic".to_string(),
+            });
+        }
+
+        let history = session.recent_turns().to_vec();
+        let retrieval_query =
+            rewrite_follow_up(&self.nlp_engine, &self.prompts, &history, question).await;
+        log::info!("   Standalone retrieval query: '{}'", retrieval_query);
+
+        // History is part of the scaffold, so it is charged against the token budget
+        let output = self
+            .rag_pipeline()
+            .with_retrieval_query(retrieval_query.clone())
+            .with_prompt(PromptStyle::Conversation {
+                history: render_history(&history),
+            })
+            .with_conversation_mode(true)
+            .with_related_queries(true)
+            .run(question)
+            .await?;
+
+        session.push_user_turn(question, Some(retrieval_query));
+        session.push_assistant_turn(&output.answer, output.sources.clone());
+
+        if let Err(e) = self.persist_conversation(session).await {
+            log::warn!("⚠️ Failed to persist conversation {}: {}", session.id, e);
+        }
+
+        Ok(QueryResponse::from(output))
+    }
+
+    /// Store the session as an `ai-chat` node if persistence is configured
+    async fn persist_conversation(&self, session: &ConversationSession) -> NodeSpaceResult<()> {
+        let persistence = match &session.persistence {
+            Some(persistence) => persistence,
+            None => return Ok(()),
+        };
+
+        let metadata = json!({
+            "session_id": session.id.as_str(),
+            "history_window": session.history_window,
+            "turns": session.turns,
+        });
+
+        self.upsert_node(
+            session.id.clone(),
+            persistence.date,
+            session.title(),
+            persistence.parent_id.clone(),
+            None,
+            "ai-chat".to_string(),
+            Some(metadata),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PromptTemplate;
+    use async_trait::async_trait;
+
+    /// Generator double returning a canned response, or failing when `None`
+    struct CannedGenerator(Option<&'static str>);
+
+    #[async_trait]
+    impl TextGenerator for CannedGenerator {
+        async fn generate(&self, _prompt: &str) -> NodeSpaceResult<String> {
+            match self.0 {
+                Some(response) => Ok(response.to_string()),
+                None => Err(NodeSpaceError::InternalError {
+                    message: "model offline".to_string(),
+                    service: "test".to_string(),
+                }),
+            }
+        }
+    }
+
+    /// Generator double answering with the prompt it was sent
+    struct EchoGenerator;
+
+    #[async_trait]
+    impl TextGenerator for EchoGenerator {
+        async fn generate(&self, prompt: &str) -> NodeSpaceResult<String> {
+            Ok(prompt.to_string())
+        }
+    }
+
+    fn session_with_exchange() -> ConversationSession {
+        let mut session = ConversationSession::new();
+        session.push_user_turn("When is the product launch?", None);
+        session.push_assistant_turn("The launch is on March 3rd [1].", vec![]);
+        session
+    }
+
+    #[tokio::test]
+    async fn test_follow_up_rewritten_by_generator() {
+        let session = session_with_exchange();
+        let generator = CannedGenerator(Some(
+            "Standalone query: \"What is the budget for the product launch?\"\nExtra text",
+        ));
+
+        let rewritten = rewrite_follow_up(
+            &generator,
+            &PromptRegistry::default(),
+            session.recent_turns(),
+            "what about the budget?",
+        )
+        .await;
+
+        assert_eq!(rewritten, "What is the budget for the product launch?");
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_prompt_can_be_overridden() {
+        let session = session_with_exchange();
+        let prompts = PromptRegistry::default()
+            .with_template(PromptTemplate::new(
+                names::REWRITE_FOLLOW_UP,
+                "2",
+                "Search for {{question}}\n\n{{history}}",
+            ))
+            .unwrap();
+
+        let rewritten = rewrite_follow_up(
+            &EchoGenerator,
+            &prompts,
+            session.recent_turns(),
+            "the launch budget",
+        )
+        .await;
+
+        assert_eq!(rewritten, "Search for the launch budget");
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_falls_back_without_generator() {
+        let session = session_with_exchange();
+
+        let rewritten = rewrite_follow_up(
+            &CannedGenerator(None),
+            &PromptRegistry::default(),
+            session.recent_turns(),
+            "what about the budget?",
+        )
+        .await;
+        assert_eq!(
+            rewritten,
+            "When is the product launch? what about the budget?"
+        );
+
+        // First questions are used as-is
+        let first = rewrite_follow_up(
+            &CannedGenerator(None),
+            &PromptRegistry::default(),
+            &[],
+            "Who owns the launch?",
+        )
+        .await;
+        assert_eq!(first, "Who owns the launch?");
+    }
+
+    #[test]
+    fn test_history_window_is_bounded() {
+        let mut session = ConversationSession::new().with_history_window(2);
+        for n in 0..3 {
+            session.push_user_turn(&format!("question {}", n), None);
+            session.push_assistant_turn(&format!("answer {}", n), vec![]);
+        }
+
+        assert_eq!(
+            render_history(session.recent_turns()),
+            "User: question 2\nAssistant: answer 2"
+        );
+        assert_eq!(session.title(), "question 0");
+    }
+}
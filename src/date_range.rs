@@ -0,0 +1,425 @@
+//! Date range access over date nodes
+//!
+//! Date nodes have predictable IDs (`YYYY-MM-DD`) and every node under a date
+//! carries the date node as its `root_id`, so a day's whole tree is one
+//! indexed `get_nodes_by_root` lookup. Range queries run that lookup for
+//! several days at once, instead of the per-day parent resolution done by the
+//! single-day methods.
+//!
+//! Calendar views need a `NodeCountSource` to stay cheap: the data store has no
+//! count query, so without one every node in the range is loaded just to be
+//! counted.
+
+use crate::{
+    constants, DataStore, HierarchicalNode, HierarchicalNodes, NLPEngine, NodeSpaceService,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use nodespace_core_types::{Node, NodeId, NodeSpaceError, NodeSpaceResult, ValidationError};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+/// Days looked up at once by range queries
+const MAX_CONCURRENT_DAYS: usize = 8;
+
+/// Node counts without loading the nodes, as offered by indexed stores
+#[async_trait]
+pub trait NodeCountSource: Send + Sync {
+    /// Number of nodes whose `root_id` is `root_id`
+    async fn count_nodes_by_root(&self, root_id: &NodeId) -> NodeSpaceResult<usize>;
+}
+
+/// Node count of one day in a calendar view
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalendarDay {
+    pub date: NaiveDate,
+    /// Nodes under the date node, at any depth
+    pub node_count: usize,
+    pub has_content: bool,
+}
+
+/// Per-day node counts for rendering a calendar, ordered by date
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalendarSummary {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub days: Vec<CalendarDay>,
+    pub total_count: usize,
+    pub days_with_content: usize,
+}
+
+impl CalendarSummary {
+    pub fn new(start: NaiveDate, end: NaiveDate, days: Vec<CalendarDay>) -> Self {
+        let total_count = days.iter().map(|day| day.node_count).sum();
+        let days_with_content = days.iter().filter(|day| day.has_content).count();
+        Self {
+            start,
+            end,
+            days,
+            total_count,
+            days_with_content,
+        }
+    }
+}
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Date node and every node under it, or `None` when the date has no date node
+    pub(crate) async fn date_tree_nodes(
+        &self,
+        date: NaiveDate,
+    ) -> NodeSpaceResult<Option<(Node, Vec<Node>)>> {
+        let date_node_id = date_node_id(date);
+        let Some(date_node) = self
+            .data_store
+            .get_node(&date_node_id)
+            .await?
+            .filter(|node| node.r#type == "date")
+        else {
+            return Ok(None);
+        };
+        let tree_nodes = self.data_store.get_nodes_by_root(&date_node_id).await?;
+        Ok(Some((date_node, tree_nodes)))
+    }
+
+    pub(crate) async fn nodes_for_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> NodeSpaceResult<Vec<(NaiveDate, Vec<Node>)>> {
+        validate_date_range(start, end, constants::MAX_DATE_RANGE_DAYS)?;
+
+        for_each_day(start, end, |date| async move {
+            let nodes = match self.date_tree_nodes(date).await? {
+                Some((date_node, tree_nodes)) => {
+                    self.build_logical_hierarchy_for_root(tree_nodes, &date_node.id)?
+                }
+                None => vec![],
+            };
+            Ok((date, nodes))
+        })
+        .await
+    }
+
+    pub(crate) async fn hierarchical_nodes_for_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> NodeSpaceResult<Vec<HierarchicalNodes>> {
+        validate_date_range(start, end, constants::MAX_DATE_RANGE_DAYS)?;
+
+        for_each_day(start, end, |date| async move {
+            let hierarchy = match self.date_tree_nodes(date).await? {
+                Some((date_node, tree_nodes)) => {
+                    let children =
+                        self.build_hierarchical_tree_from_flat_list(tree_nodes, &date_node.id, 0)?;
+                    HierarchicalNodes {
+                        total_count: crate::count_hierarchical_nodes(&children),
+                        has_content: !children.is_empty(),
+                        date_node,
+                        children,
+                    }
+                }
+                None => HierarchicalNodes {
+                    date_node: Node::new_date_node(date),
+                    children: vec![],
+                    total_count: 0,
+                    has_content: false,
+                },
+            };
+            Ok(hierarchy)
+        })
+        .await
+    }
+
+    pub(crate) async fn calendar_summary_for_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> NodeSpaceResult<CalendarSummary> {
+        validate_date_range(start, end, constants::MAX_DATE_RANGE_DAYS)?;
+
+        // Nodes under a date carry its date node as root, so no date node lookup is needed
+        let days = match &self.node_count_source {
+            Some(counts) => count_days(start, end, counts.as_ref()).await?,
+            None => {
+                log::debug!(
+                    "No node count source configured, loading nodes from {} to {} to count them",
+                    start,
+                    end
+                );
+                for_each_day(start, end, |date| async move {
+                    let nodes = self
+                        .data_store
+                        .get_nodes_by_root(&date_node_id(date))
+                        .await?;
+                    Ok(calendar_day(date, nodes.len()))
+                })
+                .await?
+            }
+        };
+        Ok(CalendarSummary::new(start, end, days))
+    }
+
+    /// Each date in the range that has notes, with its hierarchy under the date node
+    pub(crate) async fn date_trees(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> NodeSpaceResult<Vec<(NaiveDate, HierarchicalNode)>> {
+        let days = self.hierarchical_nodes_for_range(start, end).await?;
+        Ok(dates_in_range(start, end)
+            .zip(days)
+            .filter(|(_, day)| day.has_content)
+            .enumerate()
+            .map(|(index, (date, day))| {
+                (
+                    date,
+                    HierarchicalNode {
+                        node: day.date_node,
+                        children: day.children,
+                        depth: 0,
+                        sibling_index: index as u32,
+                        parent_id: None,
+                    },
+                )
+            })
+            .collect())
+    }
+}
+
+/// Predictable ID of the date node for `date`
+pub fn date_node_id(date: NaiveDate) -> NodeId {
+    NodeId::from_string(date.format("%Y-%m-%d").to_string())
+}
+
+/// First and last day of a month, or `None` for an invalid month
+pub fn month_bounds(year: i32, month: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let next_month = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    };
+    Some((start, next_month.pred_opt()?))
+}
+
+fn dates_in_range(start: NaiveDate, end: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+    start.iter_days().take_while(move |date| *date <= end)
+}
+
+/// Per-day counts from a count source, without loading any nodes
+async fn count_days(
+    start: NaiveDate,
+    end: NaiveDate,
+    counts: &dyn NodeCountSource,
+) -> NodeSpaceResult<Vec<CalendarDay>> {
+    for_each_day(start, end, |date| async move {
+        let node_count = counts.count_nodes_by_root(&date_node_id(date)).await?;
+        Ok(calendar_day(date, node_count))
+    })
+    .await
+}
+
+fn calendar_day(date: NaiveDate, node_count: usize) -> CalendarDay {
+    CalendarDay {
+        date,
+        node_count,
+        has_content: node_count > 0,
+    }
+}
+
+/// Run `lookup` for each date in the range, several at a time, in date order
+async fn for_each_day<T, F, Fut>(
+    start: NaiveDate,
+    end: NaiveDate,
+    lookup: F,
+) -> NodeSpaceResult<Vec<T>>
+where
+    F: FnMut(NaiveDate) -> Fut,
+    Fut: Future<Output = NodeSpaceResult<T>>,
+{
+    lookup_days(dates_in_range(start, end), lookup)
+        .try_collect()
+        .await
+}
+
+/// Run `lookup` for each day, several at a time, yielding results in the given order
+///
+/// Days are only looked up as the stream is polled, so a caller can stop early.
+pub(crate) fn lookup_days<I, T, F, Fut>(
+    days: I,
+    lookup: F,
+) -> impl Stream<Item = NodeSpaceResult<T>>
+where
+    I: IntoIterator,
+    F: FnMut(I::Item) -> Fut,
+    Fut: Future<Output = NodeSpaceResult<T>>,
+{
+    stream::iter(days).map(lookup).buffered(MAX_CONCURRENT_DAYS)
+}
+
+/// Reject ranges that end before they start or span more than `max_days` days
+pub(crate) fn validate_date_range(
+    start: NaiveDate,
+    end: NaiveDate,
+    max_days: i64,
+) -> NodeSpaceResult<()> {
+    let days = (end - start).num_days() + 1;
+    if !(1..=max_days).contains(&days) {
+        return Err(NodeSpaceError::Validation(ValidationError::InvalidFormat {
+            field: "date range".to_string(),
+            expected: format!(
+                "a start date on or before the end date, spanning at most {} days",
+                max_days
+            ),
+            actual: format!("{} to {}", start, end),
+            examples: vec!["2024-06-01 to 2024-06-07".to_string()],
+        }));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Datelike;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_month_bounds_handle_leap_years_and_december() {
+        assert_eq!(
+            month_bounds(2024, 2),
+            Some((date(2024, 2, 1), date(2024, 2, 29)))
+        );
+        assert_eq!(
+            month_bounds(2023, 2),
+            Some((date(2023, 2, 1), date(2023, 2, 28)))
+        );
+        assert_eq!(
+            month_bounds(2024, 12),
+            Some((date(2024, 12, 1), date(2024, 12, 31)))
+        );
+        assert_eq!(month_bounds(2024, 13), None);
+        assert_eq!(month_bounds(2024, 0), None);
+    }
+
+    #[test]
+    fn test_ranges_are_validated_and_iterated_in_date_order() {
+        assert!(validate_date_range(date(2024, 6, 1), date(2024, 6, 1), 31).is_ok());
+        assert!(validate_date_range(date(2024, 6, 2), date(2024, 6, 1), 31).is_err());
+        assert!(validate_date_range(date(2024, 6, 1), date(2024, 7, 2), 31).is_err());
+
+        let dates: Vec<_> = dates_in_range(date(2024, 2, 28), date(2024, 3, 1)).collect();
+        assert_eq!(
+            dates,
+            vec![date(2024, 2, 28), date(2024, 2, 29), date(2024, 3, 1)]
+        );
+        assert_eq!(date_node_id(date(2024, 3, 1)).to_string(), "2024-03-01");
+    }
+
+    #[tokio::test]
+    async fn test_days_are_looked_up_concurrently_and_returned_in_order() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let running = AtomicUsize::new(0);
+        let most_running = AtomicUsize::new(0);
+        let days = for_each_day(date(2024, 6, 1), date(2024, 6, 30), |day| {
+            let (running, most_running) = (&running, &most_running);
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most_running.fetch_max(now, Ordering::SeqCst);
+                // Later days finish first
+                let delay = 31 - day.day() as u64;
+                tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(day.day())
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(days, (1..=30).collect::<Vec<_>>());
+        assert_eq!(most_running.load(Ordering::SeqCst), MAX_CONCURRENT_DAYS);
+
+        let failed: NodeSpaceResult<Vec<u32>> =
+            for_each_day(date(2024, 6, 1), date(2024, 6, 3), |day| async move {
+                match day.day() {
+                    2 => Err(NodeSpaceError::InternalError {
+                        message: "store unavailable".to_string(),
+                        service: "core-logic".to_string(),
+                    }),
+                    n => Ok(n),
+                }
+            })
+            .await;
+        assert!(failed.is_err());
+    }
+
+    #[test]
+    fn test_calendar_summary_totals() {
+        let days = vec![
+            CalendarDay {
+                date: date(2024, 6, 1),
+                node_count: 3,
+                has_content: true,
+            },
+            CalendarDay {
+                date: date(2024, 6, 2),
+                node_count: 0,
+                has_content: false,
+            },
+            CalendarDay {
+                date: date(2024, 6, 3),
+                node_count: 5,
+                has_content: true,
+            },
+        ];
+
+        let summary = CalendarSummary::new(date(2024, 6, 1), date(2024, 6, 3), days);
+
+        assert_eq!(summary.total_count, 8);
+        assert_eq!(summary.days_with_content, 2);
+        assert_eq!(summary.days[1].date.day(), 2);
+    }
+
+    /// Counts from a fixed table, recording which roots were asked for
+    struct FixedCounts {
+        counts: std::collections::HashMap<String, usize>,
+        asked: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl NodeCountSource for FixedCounts {
+        async fn count_nodes_by_root(&self, root_id: &NodeId) -> NodeSpaceResult<usize> {
+            self.asked.lock().unwrap().push(root_id.to_string());
+            Ok(self.counts.get(root_id.as_str()).copied().unwrap_or(0))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_calendar_days_are_counted_by_date_node_root() {
+        let counts = FixedCounts {
+            counts: [("2024-06-01".to_string(), 4), ("2024-06-03".to_string(), 1)]
+                .into_iter()
+                .collect(),
+            asked: std::sync::Mutex::new(Vec::new()),
+        };
+
+        let days = count_days(date(2024, 6, 1), date(2024, 6, 3), &counts)
+            .await
+            .unwrap();
+        let summary = CalendarSummary::new(date(2024, 6, 1), date(2024, 6, 3), days);
+
+        assert_eq!(
+            *counts.asked.lock().unwrap(),
+            vec!["2024-06-01", "2024-06-02", "2024-06-03"]
+        );
+        assert_eq!(summary.total_count, 5);
+        assert_eq!(summary.days_with_content, 2);
+        assert!(!summary.days[1].has_content);
+    }
+}
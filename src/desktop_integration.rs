@@ -5,12 +5,14 @@
 //! - Enhanced query responses with rich metadata
 //! - AIChat node type support with vector embedding control
 
-use crate::{CoreLogic, DataStore, HierarchyComputation, NLPEngine, NodeSpaceService};
+use crate::{
+    AnswerCitation, ConfidenceBreakdown, CoreLogic, DataStore, HierarchyComputation, NLPEngine,
+    NodeSpaceService, PromptVersion, ResolvedGeneration, SearchResult, StageTiming,
+};
 use chrono::{DateTime, NaiveDate, Utc};
 use nodespace_core_types::{NodeId, NodeSpaceError, NodeSpaceResult};
 use nodespace_data_store::NodeType;
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
 
 /// Enhanced query response for desktop app AIChatNode integration
 /// Provides rich metadata and full source content for sophisticated UI
@@ -25,6 +27,26 @@ pub struct EnhancedQueryResponse {
 
     // Rich source information with full content
     pub sources: Vec<NodeSource>,
+
+    // Inline citation markers in the answer; `source_index` points into `sources`
+    #[serde(default)]
+    pub citations: Vec<AnswerCitation>,
+
+    // Components behind `overall_confidence`, for explaining low scores
+    #[serde(default)]
+    pub confidence_breakdown: ConfidenceBreakdown,
+
+    // Template the answer prompt was rendered from, for tracing prompt changes
+    #[serde(default)]
+    pub prompt_template: Option<PromptVersion>,
+
+    // Generation parameters used for the answer
+    #[serde(default)]
+    pub generation: Option<ResolvedGeneration>,
+
+    // Time spent in each pipeline stage; `generation_time_ms` is their total
+    #[serde(default)]
+    pub stage_timings: Vec<StageTiming>,
 }
 
 /// Rich source information for AIChatNode metadata
@@ -112,8 +134,13 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
                 node.parent_id = Some(parent.clone());
             }
 
+            let entities = self.attach_entities(&mut node).await;
+
             // Update the node in the data store
             self.data_store.update_node(node).await?;
+            if let Some(entities) = entities {
+                self.index_entities(&node_id, &entities).await;
+            }
 
             // Handle sibling ordering if specified
             if let Some(ref before_sibling) = before_sibling_id {
@@ -160,90 +187,17 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
         query: String,
     ) -> NodeSpaceResult<EnhancedQueryResponse> {
         log::info!("🔍 Processing enhanced query: '{}'", query);
-        let start_time = Instant::now();
 
-        // Check if service is ready
-        if !self.is_ready().await {
-            return Ok(EnhancedQueryResponse {
-                answer: "I encountered an error processing your question.".to_string(),
-                confidence: 0.0,
-                generation_time_ms: 0,
-                overall_confidence: 0.0,
-                sources: vec![],
-            });
-        }
-
-        // Perform semantic search to get relevant sources
-        let search_results = match self.semantic_search(&query, 5).await {
-            Ok(results) => results,
-            Err(e) => {
-                log::error!("   ❌ Semantic search failed: {}", e);
-                return Ok(EnhancedQueryResponse {
-                    answer: "I encountered an error processing your question.".to_string(),
-                    confidence: 0.0,
-                    generation_time_ms: start_time.elapsed().as_millis() as u64,
-                    overall_confidence: 0.0,
-                    sources: vec![],
-                });
-            }
-        };
+        let output = self.grounded_pipeline(&[]).run(&query).await?;
 
-        // Build enhanced sources with full content and metadata
-        let mut enhanced_sources = Vec::new();
-        for result in &search_results {
-            let content_str = result.node.content.as_str().unwrap_or("");
-            let node_type_str = result.node.r#type.as_str();
-
-            // Calculate token count (rough approximation)
-            let token_count = content_str.split_whitespace().count();
-
-            enhanced_sources.push(NodeSource {
-                node_id: result.node.id.as_str().to_string(),
-                content: content_str.to_string(),
-                retrieval_score: result.score as f64,
-                context_tokens: token_count,
-                node_type: node_type_str.to_string(),
-                last_modified: chrono::DateTime::parse_from_rfc3339(&result.node.updated_at)
-                    .unwrap_or_else(|_| {
-                        chrono::DateTime::parse_from_rfc3339("1970-01-01T00:00:00Z").unwrap()
-                    })
-                    .with_timezone(&chrono::Utc),
-            });
-        }
+        // Build enhanced sources in the order they were numbered in the prompt
+        let enhanced_sources = build_node_sources(&output.sources, &output.retrieved);
+        let overall_confidence = output.confidence.overall as f64;
 
-        // Generate AI response using enhanced text generation
-        let ai_response = match self
-            .generate_ai_response(
-                &query,
-                &search_results
-                    .iter()
-                    .map(|r| r.node.id.clone())
-                    .collect::<Vec<_>>(),
-            )
-            .await
-        {
-            Ok(response) => response,
-            Err(e) => {
-                log::error!("   ❌ AI response generation failed: {}", e);
-                "I encountered an error generating a response. Please try again.".to_string()
-            }
-        };
-
-        let generation_time = start_time.elapsed().as_millis() as u64;
-
-        // Calculate overall confidence based on source quality and generation success
-        let overall_confidence = if enhanced_sources.is_empty() {
-            0.3 // Low confidence with no sources
-        } else {
-            let avg_source_score = enhanced_sources
-                .iter()
-                .map(|s| s.retrieval_score)
-                .sum::<f64>()
-                / enhanced_sources.len() as f64;
-            (avg_source_score * 0.8) + 0.2 // Weighted by source quality
-        };
-
-        log::info!("   ✅ Enhanced query processed in {}ms", generation_time);
+        log::info!(
+            "   ✅ Enhanced query processed in {}ms",
+            output.total_time_ms
+        );
         log::info!(
             "   📊 Sources: {}, Confidence: {:.2}",
             enhanced_sources.len(),
@@ -251,11 +205,52 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
         );
 
         Ok(EnhancedQueryResponse {
-            answer: ai_response,
+            answer: output.answer,
             confidence: overall_confidence,
-            generation_time_ms: generation_time,
+            generation_time_ms: output.total_time_ms,
             overall_confidence,
             sources: enhanced_sources,
+            citations: output.citations,
+            confidence_breakdown: output.confidence,
+            prompt_template: Some(output.prompt_template),
+            generation: Some(output.generation),
+            stage_timings: output.stage_timings,
         })
     }
 }
+
+/// Build rich source entries for `prompt_sources`, in that order
+///
+/// Keeping the prompt's numbering order makes citation markers line up with
+/// the returned sources. IDs without a matching search result are skipped.
+pub(crate) fn build_node_sources(
+    prompt_sources: &[NodeId],
+    search_results: &[SearchResult],
+) -> Vec<NodeSource> {
+    let mut enhanced_sources = Vec::new();
+    for source_id in prompt_sources {
+        let result = match search_results.iter().find(|r| &r.node.id == source_id) {
+            Some(result) => result,
+            None => continue,
+        };
+        let content_str = result.node.content.as_str().unwrap_or("");
+        let node_type_str = result.node.r#type.as_str();
+
+        // Calculate token count (rough approximation)
+        let token_count = content_str.split_whitespace().count();
+
+        enhanced_sources.push(NodeSource {
+            node_id: result.node.id.as_str().to_string(),
+            content: content_str.to_string(),
+            retrieval_score: result.score as f64,
+            context_tokens: token_count,
+            node_type: node_type_str.to_string(),
+            last_modified: chrono::DateTime::parse_from_rfc3339(&result.node.updated_at)
+                .unwrap_or_else(|_| {
+                    chrono::DateTime::parse_from_rfc3339("1970-01-01T00:00:00Z").unwrap()
+                })
+                .with_timezone(&chrono::Utc),
+        });
+    }
+    enhanced_sources
+}
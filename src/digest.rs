@@ -0,0 +1,536 @@
+//! Daily and weekly digests of journal dates
+//!
+//! `generate_digest` walks the hierarchy under every date node in a range and
+//! collects open tasks, decisions and key topics, each attributed to the node
+//! and date it came from. Extraction is rule-based, so the same notes always
+//! produce the same digest. In `DigestMode::Model` an overview written by the
+//! map-reduce summarizer is added; `DigestMode::Deterministic` makes no model
+//! calls.
+//!
+//! Digests can be stored as a node under the last date of the range. Stored
+//! digests are skipped when gathering notes, so a digest never repeats an
+//! earlier one.
+
+use crate::date_range::validate_date_range;
+use crate::reranker::tokenize;
+use crate::scoped_query::flatten_hierarchy;
+use crate::summarization::SummarizationOptions;
+use crate::{constants, CoreLogic, DataStore, HierarchicalNode, NLPEngine, NodeSpaceService};
+use chrono::{Datelike, Duration, NaiveDate};
+use nodespace_core_types::{Node, NodeId, NodeSpaceResult};
+use nodespace_data_store::NodeType;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// Node metadata key marking stored digests
+pub const DIGEST_METADATA_KEY: &str = "digest";
+
+/// Line prefixes of unfinished tasks, lowercased
+const OPEN_TASK_PREFIXES: &[&str] = &["- [ ]", "* [ ]", "[ ]", "todo:", "action item:"];
+
+/// Line prefixes of decisions, lowercased
+const DECISION_PREFIXES: &[&str] = &["decision:", "decided:", "agreed:"];
+
+/// Phrases marking a decision anywhere in a line, lowercased
+const DECISION_PHRASES: &[&str] = &[" decided to ", " agreed to ", " agreed on "];
+
+/// Task statuses that count as finished
+const DONE_STATUSES: &[&str] = &["done", "completed", "cancelled"];
+
+/// Shortest word considered as a topic
+const MIN_TOPIC_CHARS: usize = 4;
+
+/// Words too common to be topics
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "again", "also", "been", "before", "being", "could", "does", "done", "from",
+    "have", "into", "just", "last", "like", "make", "more", "need", "next", "only", "over",
+    "should", "some", "still", "that", "their", "them", "then", "there", "these", "they", "this",
+    "todo", "were", "what", "when", "which", "will", "with", "would", "your",
+];
+
+/// How the digest is produced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DigestMode {
+    /// Rule-based extraction plus a model-written overview
+    #[default]
+    Model,
+    /// Rule-based extraction only; no model calls
+    Deterministic,
+}
+
+/// Settings for `generate_digest_with_options`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DigestOptions {
+    pub mode: DigestMode,
+    /// Store the digest as a node under the last date of the range
+    pub store: bool,
+    /// Number of key topics to report
+    pub max_topics: usize,
+}
+
+impl Default for DigestOptions {
+    fn default() -> Self {
+        Self {
+            mode: DigestMode::default(),
+            store: false,
+            max_topics: constants::DEFAULT_DIGEST_TOPICS,
+        }
+    }
+}
+
+impl DigestOptions {
+    pub fn with_mode(mut self, mode: DigestMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_store(mut self, store: bool) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub fn with_max_topics(mut self, max_topics: usize) -> Self {
+        self.max_topics = max_topics;
+        self
+    }
+}
+
+/// Task or decision found in a note
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DigestItem {
+    pub node_id: NodeId,
+    pub date: NaiveDate,
+    pub text: String,
+}
+
+/// Topic and the number of notes mentioning it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DigestTopic {
+    pub topic: String,
+    pub mentions: usize,
+}
+
+/// Open tasks, decisions and key topics of a date range
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Digest {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub mode: DigestMode,
+    pub open_tasks: Vec<DigestItem>,
+    pub decisions: Vec<DigestItem>,
+    pub key_topics: Vec<DigestTopic>,
+    /// Dates in the range that have notes
+    pub dates: Vec<NaiveDate>,
+    /// Notes read, excluding date nodes and stored digests
+    pub node_count: usize,
+    /// Model-written overview, in `DigestMode::Model` only
+    #[serde(default)]
+    pub overview: Option<String>,
+    /// ID of the stored digest node when `DigestOptions::store` is set
+    #[serde(default)]
+    pub stored_node_id: Option<NodeId>,
+}
+
+impl Digest {
+    /// Digest as note text
+    pub fn to_markdown(&self) -> String {
+        let mut text = if self.start == self.end {
+            format!("Digest for {}", self.start.format("%A, %B %-d, %Y"))
+        } else {
+            format!(
+                "Digest for {} – {}",
+                self.start.format("%B %-d, %Y"),
+                self.end.format("%B %-d, %Y")
+            )
+        };
+
+        if let Some(overview) = &self.overview {
+            text.push_str(&format!("\n\n{}", overview));
+        }
+
+        for (heading, items) in [
+            ("Open tasks", &self.open_tasks),
+            ("Decisions", &self.decisions),
+        ] {
+            if items.is_empty() {
+                continue;
+            }
+            text.push_str(&format!("\n\n{}:", heading));
+            for item in items {
+                text.push_str(&format!(
+                    "\n- {} ({})",
+                    item.text,
+                    item.date.format("%b %-d")
+                ));
+            }
+        }
+
+        if !self.key_topics.is_empty() {
+            let topics: Vec<String> = self
+                .key_topics
+                .iter()
+                .map(|topic| format!("{} ({})", topic.topic, topic.mentions))
+                .collect();
+            text.push_str(&format!("\n\nKey topics: {}", topics.join(", ")));
+        }
+
+        if self.node_count == 0 {
+            text.push_str("\n\nNo notes in this period.");
+        }
+        text
+    }
+}
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Digest of the notes from `start` to `end`, inclusive
+    pub async fn generate_digest(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> NodeSpaceResult<Digest> {
+        self.generate_digest_with_options(start, end, &DigestOptions::default())
+            .await
+    }
+
+    /// Digest of a single day
+    pub async fn generate_daily_digest(
+        &self,
+        date: NaiveDate,
+        options: &DigestOptions,
+    ) -> NodeSpaceResult<Digest> {
+        self.generate_digest_with_options(date, date, options).await
+    }
+
+    /// Digest of the Monday-to-Sunday week containing `date`
+    pub async fn generate_weekly_digest(
+        &self,
+        date: NaiveDate,
+        options: &DigestOptions,
+    ) -> NodeSpaceResult<Digest> {
+        let (start, end) = week_bounds(date);
+        self.generate_digest_with_options(start, end, options).await
+    }
+
+    /// Digest with explicit mode, storage and topic settings
+    pub async fn generate_digest_with_options(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+        options: &DigestOptions,
+    ) -> NodeSpaceResult<Digest> {
+        validate_date_range(start, end, constants::MAX_DIGEST_RANGE_DAYS)?;
+
+        let days: Vec<(NaiveDate, HierarchicalNode)> = self
+            .date_trees(start, end)
+            .await?
+            .into_iter()
+            .map(|(date, tree)| (date, without_digests(tree)))
+            .collect();
+        let mut digest = build_digest(start, end, &days, options);
+
+        if options.mode == DigestMode::Model && digest.node_count > 0 {
+            match self
+                .summarize_days(&days, &SummarizationOptions::default())
+                .await
+            {
+                Ok(summary) => digest.overview = Some(summary.summary),
+                Err(e) => log::warn!("⚠️ Digest overview unavailable: {}", e),
+            }
+        }
+
+        if options.store {
+            let metadata = serde_json::json!({
+                DIGEST_METADATA_KEY: &digest,
+            });
+            let node_id = self
+                .create_node_for_date(end, &digest.to_markdown(), NodeType::Text, Some(metadata))
+                .await?;
+            log::info!("📰 Stored digest for {} to {} as {}", start, end, node_id);
+            digest.stored_node_id = Some(node_id);
+        }
+
+        Ok(digest)
+    }
+}
+
+/// Monday and Sunday of the week containing `date`
+pub fn week_bounds(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let monday = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+    (monday, monday + Duration::days(6))
+}
+
+/// Rule-based digest of the given days' notes
+pub fn build_digest(
+    start: NaiveDate,
+    end: NaiveDate,
+    days: &[(NaiveDate, HierarchicalNode)],
+    options: &DigestOptions,
+) -> Digest {
+    let mut open_tasks = Vec::new();
+    let mut decisions = Vec::new();
+    let mut topic_mentions: BTreeMap<String, usize> = BTreeMap::new();
+    let mut node_count = 0;
+
+    for (date, tree) in days {
+        for node in flatten_hierarchy(&tree.children) {
+            let Some(content) = node.content.as_str() else {
+                continue;
+            };
+            node_count += 1;
+
+            let item = |text: String| DigestItem {
+                node_id: node.id.clone(),
+                date: *date,
+                text,
+            };
+            // A task node is one task, whatever markers its text carries
+            let task_node = node.r#type == "task";
+            if is_open_task_node(&node) {
+                if let Some(first_line) = content.lines().map(str::trim).find(|l| !l.is_empty()) {
+                    let task = open_task_text(first_line).unwrap_or_else(|| first_line.to_string());
+                    open_tasks.push(item(task));
+                }
+            }
+            for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                match open_task_text(line) {
+                    Some(task) if !task_node => open_tasks.push(item(task)),
+                    Some(_) => {}
+                    None => {
+                        if let Some(decision) = decision_text(line) {
+                            decisions.push(item(decision));
+                        }
+                    }
+                }
+            }
+            for topic in topics(content) {
+                *topic_mentions.entry(topic).or_default() += 1;
+            }
+        }
+    }
+
+    // BTreeMap iteration is alphabetical, so equal counts keep a stable order
+    let mut key_topics: Vec<DigestTopic> = topic_mentions
+        .into_iter()
+        .map(|(topic, mentions)| DigestTopic { topic, mentions })
+        .collect();
+    key_topics.sort_by_key(|topic| std::cmp::Reverse(topic.mentions));
+    key_topics.truncate(options.max_topics);
+
+    Digest {
+        start,
+        end,
+        mode: options.mode,
+        open_tasks,
+        decisions,
+        key_topics,
+        dates: days.iter().map(|(date, _)| *date).collect(),
+        node_count,
+        overview: None,
+        stored_node_id: None,
+    }
+}
+
+/// Tree without stored digest nodes and their children
+fn without_digests(mut tree: HierarchicalNode) -> HierarchicalNode {
+    tree.children = tree
+        .children
+        .into_iter()
+        .filter(|child| !is_digest_node(&child.node))
+        .map(without_digests)
+        .collect();
+    tree
+}
+
+fn is_digest_node(node: &Node) -> bool {
+    node.metadata
+        .as_ref()
+        .and_then(|metadata| metadata.get(DIGEST_METADATA_KEY))
+        .is_some()
+}
+
+/// Task-typed node whose metadata does not mark it finished
+fn is_open_task_node(node: &Node) -> bool {
+    if node.r#type != "task" {
+        return false;
+    }
+    let status = node
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.get("status"))
+        .and_then(|status| status.as_str())
+        .map(|status| status.to_lowercase());
+    !matches!(status, Some(status) if DONE_STATUSES.contains(&status.as_str()))
+}
+
+fn open_task_text(line: &str) -> Option<String> {
+    strip_marker(line, OPEN_TASK_PREFIXES)
+}
+
+fn decision_text(line: &str) -> Option<String> {
+    if let Some(decision) = strip_marker(line, DECISION_PREFIXES) {
+        return Some(decision);
+    }
+    let padded = format!(" {} ", line.to_lowercase());
+    DECISION_PHRASES
+        .iter()
+        .any(|phrase| padded.contains(phrase))
+        .then(|| line.trim_start_matches(['-', '*', ' ']).to_string())
+}
+
+/// Line text after the first matching marker, compared case-insensitively
+fn strip_marker(line: &str, markers: &[&str]) -> Option<String> {
+    let lower = line.to_lowercase();
+    markers
+        .iter()
+        // Markers are ASCII, so the byte length matches in the original line
+        .find(|marker| lower.starts_with(*marker))
+        .map(|marker| {
+            line[marker.len()..]
+                .trim_start_matches([':', ' '])
+                .trim()
+                .to_string()
+        })
+        .filter(|text| !text.is_empty())
+}
+
+/// Distinct topic words and hashtags of one note
+fn topics(content: &str) -> HashSet<String> {
+    let mut topics: HashSet<String> = content
+        .split_whitespace()
+        .filter_map(|word| word.strip_prefix('#'))
+        .flat_map(tokenize)
+        .collect();
+    topics.extend(tokenize(content).into_iter().filter(|word| {
+        word.chars().count() >= MIN_TOPIC_CHARS
+            && !word.chars().all(|c| c.is_ascii_digit())
+            && !STOP_WORDS.contains(&word.as_str())
+    }));
+    topics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn hierarchical(node: Node, children: Vec<HierarchicalNode>) -> HierarchicalNode {
+        HierarchicalNode {
+            node,
+            children,
+            depth: 0,
+            sibling_index: 0,
+            parent_id: None,
+        }
+    }
+
+    fn note(content: &str) -> HierarchicalNode {
+        hierarchical(Node::new("text".to_string(), json!(content)), vec![])
+    }
+
+    fn day(date: NaiveDate, children: Vec<HierarchicalNode>) -> (NaiveDate, HierarchicalNode) {
+        (date, hierarchical(Node::new_date_node(date), children))
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 6, day).unwrap()
+    }
+
+    #[test]
+    fn test_tasks_and_decisions_are_extracted_with_their_source() {
+        let mut done_task = Node::new("task".to_string(), json!("Book venue"));
+        done_task.metadata = Some(json!({ "status": "done" }));
+        let open_task = Node::new("task".to_string(), json!("- [ ] Order badges"));
+        let mut finished_todo = Node::new("task".to_string(), json!("TODO: email Sam"));
+        finished_todo.metadata = Some(json!({ "status": "completed" }));
+        let standup = note("Standup\n- [ ] Send budget to finance\n- [x] Review launch plan\nTODO: email Priya\ntodo lists are overrated\nDecision: ship the beta on Friday\nWe decided to drop the legacy importer");
+        let standup_id = standup.node.id.clone();
+
+        let days = vec![day(
+            date(3),
+            vec![hierarchical(
+                Node::new("text".to_string(), json!("Planning")),
+                vec![
+                    standup,
+                    hierarchical(done_task, vec![]),
+                    hierarchical(open_task, vec![]),
+                    hierarchical(finished_todo, vec![]),
+                ],
+            )],
+        )];
+        let digest = build_digest(date(3), date(3), &days, &DigestOptions::default());
+
+        // Task nodes are listed once, without their markers, and only while open
+        let tasks: Vec<&str> = digest.open_tasks.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(
+            tasks,
+            vec!["Send budget to finance", "email Priya", "Order badges"]
+        );
+        let decisions: Vec<&str> = digest.decisions.iter().map(|d| d.text.as_str()).collect();
+        assert_eq!(
+            decisions,
+            vec![
+                "ship the beta on Friday",
+                "We decided to drop the legacy importer"
+            ]
+        );
+        assert_eq!(digest.open_tasks[0].node_id, standup_id);
+        assert_eq!(digest.open_tasks[0].date, date(3));
+        assert_eq!(digest.node_count, 5);
+    }
+
+    #[test]
+    fn test_topics_are_ranked_deterministically_and_stored_digests_are_skipped() {
+        let mut old_digest = Node::new("text".to_string(), json!("Digest: budget budget budget"));
+        old_digest.metadata = Some(json!({ DIGEST_METADATA_KEY: {} }));
+        let days = vec![
+            day(
+                date(3),
+                vec![
+                    note("Budget review with #finance"),
+                    note("Launch checklist and budget"),
+                    hierarchical(old_digest, vec![note("Budget copy")]),
+                ],
+            ),
+            day(date(4), vec![note("Launch retro, #finance follow-up")]),
+        ];
+        let days: Vec<_> = days
+            .into_iter()
+            .map(|(date, tree)| (date, without_digests(tree)))
+            .collect();
+        let options = DigestOptions::default().with_max_topics(3);
+
+        let digest = build_digest(date(3), date(4), &days, &options);
+
+        assert_eq!(digest.node_count, 3);
+        let topics: Vec<(&str, usize)> = digest
+            .key_topics
+            .iter()
+            .map(|t| (t.topic.as_str(), t.mentions))
+            .collect();
+        assert_eq!(topics, vec![("budget", 2), ("finance", 2), ("launch", 2)]);
+        assert_eq!(digest, build_digest(date(3), date(4), &days, &options));
+    }
+
+    #[test]
+    fn test_weekly_bounds_and_markdown_rendering() {
+        assert_eq!(week_bounds(date(5)), (date(3), date(9)));
+        assert_eq!(week_bounds(date(9)), (date(3), date(9)));
+        assert_eq!(week_bounds(date(3)), (date(3), date(9)));
+
+        let days = vec![day(date(4), vec![note("- [ ] Call the printer")])];
+        let digest = build_digest(
+            date(3),
+            date(9),
+            &days,
+            &DigestOptions::default().with_mode(DigestMode::Deterministic),
+        );
+        let text = digest.to_markdown();
+
+        assert!(text.starts_with("Digest for June 3, 2024 – June 9, 2024"));
+        assert!(text.contains("Open tasks:\n- Call the printer (Jun 4)"));
+        assert!(!text.contains("Decisions:"));
+        assert_eq!(digest.dates, vec![date(4)]);
+        assert_eq!(digest.overview, None);
+    }
+}
@@ -0,0 +1,726 @@
+//! Named-entity extraction at write time and the workspace entity index
+//!
+//! Node content is run through a rule-based extractor when it is written, or
+//! through the entity prompt when `EntityExtraction::Model` is chosen. The
+//! rules' gazetteer is the workspace's own vocabulary: entities already in the
+//! index are recognized wherever they reappear, in notes and in lowercase
+//! queries alike. Extracted entities are kept in node metadata under
+//! `entities`; the index itself is saved through the service's `IndexStore`
+//! and only rebuilt from node metadata when no usable saved copy exists.
+
+use crate::images::gps_location;
+use crate::index_store::SavedIndex;
+use crate::{prompts, DataStore, ExtractedEntities, NLPEngine, NodeSpaceService};
+use nodespace_core_types::{Node, NodeId, NodeSpaceResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+/// Node metadata key holding the entities extracted from the node's content
+pub const ENTITIES_METADATA_KEY: &str = "entities";
+
+/// Name of the entity index in the index store
+pub const ENTITY_INDEX_NAME: &str = "entity_index";
+
+/// Occasions recognized as events by the rule-based extractor
+const EVENT_WORDS: &[&str] = &[
+    "birthday",
+    "anniversary",
+    "wedding",
+    "meeting",
+    "party",
+    "conference",
+    "dinner",
+    "lunch",
+    "interview",
+    "standup",
+    "vacation",
+    "trip",
+];
+
+/// Things recognized as objects by the rule-based extractor
+const OBJECT_WORDS: &[&str] = &[
+    "shirt",
+    "document",
+    "photo",
+    "screenshot",
+    "diagram",
+    "chart",
+    "invoice",
+    "contract",
+    "slides",
+    "receipt",
+];
+
+/// Words before a capitalized name that make it a place rather than a person
+///
+/// "to", "from" and "via" are left out: they precede people as often as
+/// places ("sent the notes to Claire").
+const LOCATION_CUES: &[&str] = &["in", "at", "near", "visited"];
+
+/// Capitalized words that are not names
+const NOT_NAMES: &[&str] = &[
+    "i",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+    "today",
+    "tomorrow",
+    "yesterday",
+    "todo",
+    "done",
+    "note",
+    "notes",
+    "ok",
+    "the",
+    "a",
+    "an",
+];
+
+/// Longest run of capitalized words taken as one name
+const MAX_NAME_WORDS: usize = 3;
+
+/// Category of an extracted entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Person,
+    Event,
+    Object,
+    Location,
+}
+
+/// How entities are extracted from node content when nodes are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityExtraction {
+    /// Entity prompt, falling back to rules when the response is unusable;
+    /// adds a model call to every write
+    Model,
+    /// Rule-based extraction only; no model call per write
+    #[default]
+    Rules,
+    Disabled,
+}
+
+/// Entity with the nodes that mention it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedEntity {
+    /// Name as first written
+    pub name: String,
+    pub kind: EntityKind,
+    pub node_ids: Vec<NodeId>,
+}
+
+/// Mapping from entity names to the nodes that mention them
+#[derive(Debug, Default)]
+pub struct EntityIndex {
+    /// Keyed by normalized name
+    entities: HashMap<String, IndexedEntity>,
+    /// Normalized names indexed for each node, for updates and removal
+    node_entities: HashMap<NodeId, Vec<String>>,
+    /// Most words in any name indexed so far, bounding the phrases matched in text
+    longest_name_words: usize,
+}
+
+impl EntityIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index holding `entities`, as returned by `entities`
+    pub fn from_entities(entities: Vec<IndexedEntity>) -> Self {
+        let mut index = Self::new();
+        for entity in entities {
+            let key = normalize(&entity.name);
+            if key.is_empty() || index.entities.contains_key(&key) {
+                continue;
+            }
+            for node_id in &entity.node_ids {
+                index
+                    .node_entities
+                    .entry(node_id.clone())
+                    .or_default()
+                    .push(key.clone());
+            }
+            index.note_name_length(&key);
+            index.entities.insert(key, entity);
+        }
+        index
+    }
+
+    /// Index a node's entities, replacing what was indexed for it before
+    pub fn index_node(&mut self, node_id: &NodeId, extracted: &ExtractedEntities) {
+        self.remove_node(node_id);
+
+        let mut keys = Vec::new();
+        for (name, kind) in entity_list(extracted) {
+            let key = normalize(name);
+            if key.is_empty() || keys.contains(&key) {
+                continue;
+            }
+            let entity = self
+                .entities
+                .entry(key.clone())
+                .or_insert_with(|| IndexedEntity {
+                    name: name.trim().to_string(),
+                    kind,
+                    node_ids: Vec::new(),
+                });
+            entity.node_ids.push(node_id.clone());
+            self.note_name_length(&key);
+            keys.push(key);
+        }
+        if !keys.is_empty() {
+            self.node_entities.insert(node_id.clone(), keys);
+        }
+    }
+
+    pub fn remove_node(&mut self, node_id: &NodeId) {
+        for key in self.node_entities.remove(node_id).unwrap_or_default() {
+            if let Some(entity) = self.entities.get_mut(&key) {
+                entity.node_ids.retain(|id| id != node_id);
+                if entity.node_ids.is_empty() {
+                    self.entities.remove(&key);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&IndexedEntity> {
+        self.entities.get(&normalize(name))
+    }
+
+    /// Nodes mentioning an entity; a single word also matches longer names
+    /// containing it, so "claire" finds "Claire Dubois"
+    pub fn lookup(&self, name: &str) -> Vec<NodeId> {
+        if let Some(entity) = self.get(name) {
+            return entity.node_ids.clone();
+        }
+        let key = normalize(name);
+        if key.is_empty() || key.contains(' ') {
+            return vec![];
+        }
+        let mut matches: Vec<&IndexedEntity> = self
+            .entities
+            .iter()
+            .filter(|(name, _)| name.split(' ').any(|word| word == key))
+            .map(|(_, entity)| entity)
+            .collect();
+        matches.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut node_ids = Vec::new();
+        for entity in matches {
+            for node_id in &entity.node_ids {
+                if !node_ids.contains(node_id) {
+                    node_ids.push(node_id.clone());
+                }
+            }
+        }
+        node_ids
+    }
+
+    /// Indexed entities, most mentioned first
+    pub fn entities(&self) -> Vec<&IndexedEntity> {
+        let mut entities: Vec<_> = self.entities.values().collect();
+        entities.sort_by(|a, b| {
+            b.node_ids
+                .len()
+                .cmp(&a.node_ids.len())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        entities
+    }
+
+    /// Indexed entities named in `text`, in order of first mention
+    ///
+    /// Each run of up to the longest indexed name's word count is looked up
+    /// by its normalized form, so the cost follows the text rather than the
+    /// size of the index. Punctuation around a run and a trailing possessive
+    /// are ignored: "(Claire Dubois's" names "claire dubois".
+    pub fn mentioned_in(&self, text: &str) -> Vec<&IndexedEntity> {
+        let lower = normalize(text);
+        let words: Vec<&str> = lower.split(' ').collect();
+        let mut mentioned: Vec<&IndexedEntity> = Vec::new();
+
+        for start in 0..words.len() {
+            let longest = self.longest_name_words.min(words.len() - start);
+            for len in 1..=longest {
+                let run = words[start..start + len].join(" ");
+                let phrase = run.trim_matches(|c: char| !c.is_alphanumeric());
+                for key in std::iter::once(phrase).chain(possessive_owner(phrase)) {
+                    if key.chars().count() < 2 {
+                        continue;
+                    }
+                    if let Some(entity) = self.entities.get(key) {
+                        if !mentioned.iter().any(|seen| std::ptr::eq(*seen, entity)) {
+                            mentioned.push(entity);
+                        }
+                    }
+                }
+            }
+        }
+        mentioned
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    fn note_name_length(&mut self, key: &str) {
+        let words = key.split(' ').count();
+        self.longest_name_words = self.longest_name_words.max(words);
+    }
+}
+
+impl SavedIndex for EntityIndex {
+    fn to_saved(&self) -> serde_json::Value {
+        serde_json::to_value(self.entities()).unwrap_or_default()
+    }
+
+    fn from_saved(saved: serde_json::Value) -> Option<Self> {
+        serde_json::from_value(saved).ok().map(Self::from_entities)
+    }
+}
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Extract entities from node content according to the configured mode
+    pub async fn extract_node_entities(&self, content: &str) -> ExtractedEntities {
+        match self.entity_extraction {
+            EntityExtraction::Disabled => ExtractedEntities::default(),
+            EntityExtraction::Rules => self.extract_entities_with_rules(content).await,
+            EntityExtraction::Model => match self.extract_entities_with_model(content).await {
+                Some(entities) => entities,
+                None => self.extract_entities_with_rules(content).await,
+            },
+        }
+    }
+
+    async fn extract_entities_with_model(&self, content: &str) -> Option<ExtractedEntities> {
+        let prompt = self
+            .prompts
+            .render(
+                prompts::names::EXTRACT_NODE_ENTITIES,
+                &[("content", content)],
+            )
+            .ok()?;
+        match self.generate_structured(&prompt).await {
+            Ok(entities) => Some(entities),
+            Err(e) => {
+                log::warn!("⚠️ Entity extraction failed, using rules: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Rule-based extraction with the workspace vocabulary as gazetteer
+    pub(crate) async fn extract_entities_with_rules(&self, text: &str) -> ExtractedEntities {
+        match self.loaded_entity_index().await {
+            Ok(index) => extract_with_rules(text, &*index.read().await),
+            Err(e) => {
+                log::warn!("⚠️ Entity index unavailable, extracting without it: {}", e);
+                extract_with_rules(text, &EntityIndex::new())
+            }
+        }
+    }
+
+    /// Extract a node's entities into its metadata before it is stored
+    pub(crate) async fn attach_entities(&self, node: &mut Node) -> Option<ExtractedEntities> {
+        // Date and period nodes hold calendar labels, not prose
+        if self.entity_extraction == EntityExtraction::Disabled
+            || matches!(node.r#type.as_str(), "date" | "week" | "month" | "year")
+        {
+            return None;
+        }
+        let content = node.content.as_str()?;
+        let mut entities = self.extract_node_entities(content).await;
+        if let Some(location) = gps_location(node) {
+            if !entities.locations.contains(&location) {
+                entities.locations.push(location);
+            }
+        }
+
+        let metadata = node
+            .metadata
+            .get_or_insert_with(|| serde_json::Value::Object(Default::default()));
+        let serde_json::Value::Object(fields) = metadata else {
+            // Non-object metadata is left alone; the index still gets the entities
+            return Some(entities);
+        };
+        match serde_json::to_value(&entities) {
+            Ok(value) => {
+                fields.insert(ENTITIES_METADATA_KEY.to_string(), value);
+            }
+            Err(e) => log::warn!("⚠️ Could not store entities of {}: {}", node.id, e),
+        }
+        Some(entities)
+    }
+
+    pub(crate) async fn index_entities(&self, node_id: &NodeId, entities: &ExtractedEntities) {
+        match self.loaded_entity_index().await {
+            Ok(index) => {
+                index.write().await.index_node(node_id, entities);
+                self.entity_index.schedule_save();
+            }
+            // The entities are in the node's metadata and return with the next rebuild
+            Err(e) => log::warn!("⚠️ Could not index entities of {}: {}", node_id, e),
+        }
+    }
+
+    pub(crate) async fn unindex_entities(&self, node_id: &NodeId) {
+        let index = match self.loaded_entity_index().await {
+            Ok(index) => index,
+            Err(e) => {
+                log::warn!("⚠️ Could not unindex entities of {}: {}", node_id, e);
+                return;
+            }
+        };
+        let removed = {
+            let mut index = index.write().await;
+            let indexed = index.node_entities.contains_key(node_id);
+            index.remove_node(node_id);
+            indexed
+        };
+        if removed {
+            self.entity_index.schedule_save();
+        }
+    }
+
+    /// The entity index, loaded from the index store or rebuilt on first use
+    pub(crate) async fn loaded_entity_index(&self) -> NodeSpaceResult<&RwLock<EntityIndex>> {
+        self.entity_index
+            .load_with(|| self.scan_entity_index())
+            .await
+    }
+
+    /// Rebuild the entity index from the entities stored in node metadata
+    ///
+    /// Reads every node once. Returns the number of indexed nodes.
+    pub async fn rebuild_entity_index(&self) -> NodeSpaceResult<usize> {
+        let index = self.scan_entity_index().await?;
+        let indexed = index.node_entities.len();
+        self.entity_index.replace(index).await;
+        Ok(indexed)
+    }
+
+    async fn scan_entity_index(&self) -> NodeSpaceResult<EntityIndex> {
+        let nodes = self.data_store.query_nodes("").await?;
+        let mut index = EntityIndex::new();
+        for node in &nodes {
+            if let Some(entities) = stored_entities(node) {
+                index.index_node(&node.id, &entities);
+            }
+        }
+        log::info!(
+            "🏷️ Rebuilt entity index: {} entities from {} nodes",
+            index.len(),
+            index.node_entities.len()
+        );
+        Ok(index)
+    }
+
+    /// Nodes mentioning an entity, from the entity index
+    pub async fn lookup_entity(&self, name: &str) -> NodeSpaceResult<Vec<NodeId>> {
+        Ok(self.loaded_entity_index().await?.read().await.lookup(name))
+    }
+
+    /// Indexed entities, most mentioned first
+    pub async fn known_entities(&self) -> NodeSpaceResult<Vec<IndexedEntity>> {
+        let index = self.loaded_entity_index().await?.read().await;
+        Ok(index.entities().into_iter().cloned().collect())
+    }
+}
+
+/// Entities stored in a node's metadata
+pub fn stored_entities(node: &Node) -> Option<ExtractedEntities> {
+    let value = node.metadata.as_ref()?.get(ENTITIES_METADATA_KEY)?;
+    serde_json::from_value(value.clone()).ok()
+}
+
+/// Rule-based entity extraction
+///
+/// Known entities from `index` are matched case-insensitively. New names are
+/// runs of capitalized words that do not start a sentence; a preceding "in",
+/// "at" or similar makes them locations. Events and objects come from word
+/// lists, with a possessive owner kept ("Claire's birthday").
+pub fn extract_with_rules(text: &str, index: &EntityIndex) -> ExtractedEntities {
+    let mut found = Found::default();
+
+    for entity in index.mentioned_in(text) {
+        found.add(&entity.name, entity.kind);
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut i = 0;
+    while i < words.len() {
+        let word = words[i];
+        let bare = trim_word(word);
+        let lower_bare = bare.to_lowercase();
+
+        if let Some(handle) = bare.strip_prefix('@').filter(|handle| !handle.is_empty()) {
+            found.add(handle, EntityKind::Person);
+            i += 1;
+            continue;
+        }
+
+        let singular = lower_bare.strip_suffix('s').unwrap_or(&lower_bare);
+        if EVENT_WORDS.contains(&lower_bare.as_str()) || EVENT_WORDS.contains(&singular) {
+            let owner = i
+                .checked_sub(1)
+                .map(|prev| trim_word(words[prev]))
+                .and_then(possessive_owner);
+            match owner {
+                Some(owner) => {
+                    found.add(&format!("{}'s {}", owner, lower_bare), EntityKind::Event);
+                    if starts_uppercase(owner) {
+                        found.add(owner, EntityKind::Person);
+                    }
+                }
+                None => found.add(&lower_bare, EntityKind::Event),
+            }
+            i += 1;
+            continue;
+        }
+        if OBJECT_WORDS.contains(&lower_bare.as_str()) || OBJECT_WORDS.contains(&singular) {
+            found.add(singular, EntityKind::Object);
+            i += 1;
+            continue;
+        }
+
+        let sentence_start = i == 0 || ends_sentence(words[i - 1]);
+        if !is_name_word(bare) || sentence_start {
+            i += 1;
+            continue;
+        }
+
+        // Run of capitalized words, ending at punctuation or a possessive
+        let mut name_words = Vec::new();
+        let mut j = i;
+        while j < words.len() && name_words.len() < MAX_NAME_WORDS {
+            let bare = trim_word(words[j]);
+            if !is_name_word(bare) {
+                break;
+            }
+            let possessive = possessive_owner(bare);
+            name_words.push(possessive.unwrap_or(bare));
+            j += 1;
+            if possessive.is_some() || words[j - 1].ends_with([',', '.', ';', ':', '!', '?']) {
+                break;
+            }
+        }
+
+        let cue = words[i - 1].to_lowercase();
+        let kind = if LOCATION_CUES.contains(&trim_word(&cue)) {
+            EntityKind::Location
+        } else {
+            EntityKind::Person
+        };
+        found.add(&name_words.join(" "), kind);
+        i = j.max(i + 1);
+    }
+
+    found.entities
+}
+
+/// Entities collected in first-seen order without duplicates
+#[derive(Default)]
+struct Found {
+    entities: ExtractedEntities,
+    seen: Vec<String>,
+}
+
+impl Found {
+    fn add(&mut self, name: &str, kind: EntityKind) {
+        let key = normalize(name);
+        if key.is_empty() || self.seen.contains(&key) {
+            return;
+        }
+        self.seen.push(key);
+        let list = match kind {
+            EntityKind::Person => &mut self.entities.people,
+            EntityKind::Event => &mut self.entities.events,
+            EntityKind::Object => &mut self.entities.objects,
+            EntityKind::Location => &mut self.entities.locations,
+        };
+        list.push(name.to_string());
+    }
+}
+
+fn entity_list(extracted: &ExtractedEntities) -> impl Iterator<Item = (&String, EntityKind)> {
+    [
+        (&extracted.people, EntityKind::Person),
+        (&extracted.events, EntityKind::Event),
+        (&extracted.objects, EntityKind::Object),
+        (&extracted.locations, EntityKind::Location),
+    ]
+    .into_iter()
+    .flat_map(|(names, kind)| names.iter().map(move |name| (name, kind)))
+}
+
+/// Lowercase with single spaces and a curly apostrophe straightened
+fn normalize(name: &str) -> String {
+    name.replace('\u{2019}', "'")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn trim_word(word: &str) -> &str {
+    word.trim_matches(|c: char| !(c.is_alphanumeric() || c == '@' || c == '\'' || c == '\u{2019}'))
+        .trim_end_matches(['\'', '\u{2019}'])
+}
+
+fn possessive_owner(word: &str) -> Option<&str> {
+    word.strip_suffix("'s")
+        .or_else(|| word.strip_suffix("\u{2019}s"))
+        .filter(|owner| !owner.is_empty())
+}
+
+fn starts_uppercase(word: &str) -> bool {
+    word.chars().next().is_some_and(char::is_uppercase)
+}
+
+fn is_name_word(word: &str) -> bool {
+    let base = possessive_owner(word).unwrap_or(word);
+    starts_uppercase(base)
+        && base.chars().all(|c| c.is_alphabetic() || c == '-')
+        && !NOT_NAMES.contains(&base.to_lowercase().as_str())
+}
+
+fn ends_sentence(word: &str) -> bool {
+    word.ends_with(['.', '!', '?', ':']) || word.starts_with(['-', '*', '#'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id(id: &str) -> NodeId {
+        NodeId::from_string(id.to_string())
+    }
+
+    #[test]
+    fn test_rules_extract_people_places_events_and_objects() {
+        let entities = extract_with_rules(
+            "Lunch with Claire Dubois in Lisbon. Planning Claire's birthday, \
+             she sent the photos and a contract to Marco and @ana.",
+            &EntityIndex::new(),
+        );
+
+        assert_eq!(
+            entities.people,
+            vec!["Claire Dubois", "Claire", "Marco", "ana"]
+        );
+        assert_eq!(entities.locations, vec!["Lisbon"]);
+        assert_eq!(entities.events, vec!["lunch", "Claire's birthday"]);
+        assert_eq!(entities.objects, vec!["photo", "contract"]);
+
+        // Sentence-initial capitals and calendar words are not names
+        let plain = extract_with_rules(
+            "Today I moved Friday's notes. Great work",
+            &EntityIndex::new(),
+        );
+        assert!(plain.people.is_empty() && plain.locations.is_empty());
+    }
+
+    #[test]
+    fn test_workspace_vocabulary_is_recognized_in_lowercase_queries() {
+        let mut index = EntityIndex::new();
+        index.index_node(
+            &node_id("n1"),
+            &ExtractedEntities {
+                people: vec!["Claire Dubois".to_string()],
+                locations: vec!["Lisbon".to_string()],
+                ..Default::default()
+            },
+        );
+
+        let entities = extract_with_rules("what did claire dubois say about lisbon?", &index);
+        assert_eq!(entities.people, vec!["Claire Dubois"]);
+        assert_eq!(entities.locations, vec!["Lisbon"]);
+
+        // Whole words only
+        let partial = extract_with_rules("the lisbonese recipe", &index);
+        assert!(partial.locations.is_empty());
+
+        // Surrounding punctuation and possessives do not hide a known name
+        let mentioned: Vec<_> = index
+            .mentioned_in("Flights (lisbon), then claire dubois's talk")
+            .into_iter()
+            .map(|entity| entity.name.as_str())
+            .collect();
+        assert_eq!(mentioned, vec!["Lisbon", "Claire Dubois"]);
+    }
+
+    #[test]
+    fn test_index_replaces_and_removes_node_entities() {
+        let mut index = EntityIndex::new();
+        let claire = |extra: &str| ExtractedEntities {
+            people: vec!["Claire Dubois".to_string(), extra.to_string()],
+            ..Default::default()
+        };
+        index.index_node(&node_id("n1"), &claire("Marco"));
+        index.index_node(&node_id("n2"), &claire("Ana"));
+
+        assert_eq!(
+            index.lookup("claire dubois"),
+            vec![node_id("n1"), node_id("n2")]
+        );
+        assert_eq!(index.lookup("Claire"), vec![node_id("n1"), node_id("n2")]);
+        assert_eq!(index.entities()[0].name, "Claire Dubois");
+
+        // Re-indexing a node drops entities it no longer mentions
+        index.index_node(&node_id("n1"), &claire("Ana"));
+        assert!(index.lookup("marco").is_empty());
+        assert_eq!(index.lookup("ana"), vec![node_id("n2"), node_id("n1")]);
+
+        index.remove_node(&node_id("n2"));
+        assert_eq!(index.lookup("ana"), vec![node_id("n1")]);
+        index.remove_node(&node_id("n1"));
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn test_saved_index_restores_lookups_and_removal() {
+        let mut index = EntityIndex::new();
+        let people = |names: &[&str]| ExtractedEntities {
+            people: names.iter().map(|name| name.to_string()).collect(),
+            ..Default::default()
+        };
+        index.index_node(&node_id("n1"), &people(&["Claire Dubois", "Marco"]));
+        index.index_node(&node_id("n2"), &people(&["Claire Dubois"]));
+
+        let saved = serde_json::to_value(index.entities()).unwrap();
+        let mut restored = EntityIndex::from_entities(serde_json::from_value(saved).unwrap());
+        assert_eq!(restored.entities(), index.entities());
+        assert_eq!(
+            restored.lookup("claire"),
+            vec![node_id("n1"), node_id("n2")]
+        );
+
+        restored.remove_node(&node_id("n1"));
+        assert!(restored.lookup("marco").is_empty());
+        assert_eq!(restored.lookup("claire dubois"), vec![node_id("n2")]);
+    }
+}
@@ -0,0 +1,230 @@
+//! Event-anchored temporal resolution
+//!
+//! A reference such as "during Claire's birthday" names an event instead of a
+//! date. The nodes describing the event are found by text match, falling back
+//! to semantic search, and the dates of their date-node roots become anchors.
+//! Temporal search then covers a window of days around each anchor.
+
+use crate::{
+    constants, CoreLogic, DataStore, NLPEngine, NodeSpaceService, SearchResult, TemporalReference,
+    TemporalType,
+};
+use chrono::{Duration, NaiveDate};
+use nodespace_core_types::{Node, NodeId, NodeSpaceResult};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+/// Date an event took place, with the nodes describing it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventAnchor {
+    pub date: NaiveDate,
+    /// Nodes under the date node that matched the event
+    pub node_ids: Vec<NodeId>,
+}
+
+/// Search result found in the window around an event anchor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnchoredSearchResult {
+    pub result: SearchResult,
+    pub anchor_date: NaiveDate,
+}
+
+/// Event anchors and the nodes found around them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventSearch {
+    pub event: String,
+    /// Best anchor first
+    pub anchors: Vec<EventAnchor>,
+    /// Best result first
+    pub results: Vec<AnchoredSearchResult>,
+}
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Dates on which an event took place, best first
+    pub async fn resolve_event_anchors(&self, event: &str) -> NodeSpaceResult<Vec<EventAnchor>> {
+        let mut candidates = self.data_store.query_nodes(event).await?;
+        if candidates.is_empty() {
+            candidates = match self
+                .semantic_search(event, constants::DEFAULT_SEARCH_LIMIT)
+                .await
+            {
+                Ok(results) => results
+                    .into_iter()
+                    .filter(|result| result.score >= constants::MIN_SEARCH_SCORE)
+                    .map(|result| result.node)
+                    .collect(),
+                Err(e) => {
+                    log::warn!("⚠️ Semantic search for event '{}' failed: {}", event, e);
+                    vec![]
+                }
+            };
+        }
+        Ok(group_anchors(candidates))
+    }
+
+    /// Nodes in the windows around an event's anchor dates
+    pub async fn search_around_event(&self, event: &str) -> NodeSpaceResult<EventSearch> {
+        let anchors = self.resolve_event_anchors(event).await?;
+
+        let mut results: Vec<AnchoredSearchResult> = Vec::new();
+        for anchor in &anchors {
+            let window = anchored_reference(event, anchor.date);
+            for result in self.search_by_temporal_ref(&window).await? {
+                let anchored = AnchoredSearchResult {
+                    result,
+                    anchor_date: anchor.date,
+                };
+                // Windows of close anchors overlap; keep each node's best score
+                match results
+                    .iter_mut()
+                    .find(|existing| existing.result.node_id == anchored.result.node_id)
+                {
+                    Some(existing) if existing.result.score >= anchored.result.score => {}
+                    Some(existing) => *existing = anchored,
+                    None => results.push(anchored),
+                }
+            }
+        }
+
+        results.sort_by(|a, b| {
+            b.result
+                .score
+                .partial_cmp(&a.result.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        results.truncate(constants::DEFAULT_MAX_RESULTS_PER_STRATEGY);
+
+        Ok(EventSearch {
+            event: event.to_string(),
+            anchors,
+            results,
+        })
+    }
+
+    /// Anchor unresolved event references to their best date
+    pub(crate) async fn resolve_event_references(&self, references: &mut [TemporalReference]) {
+        for reference in references.iter_mut().filter(|reference| {
+            reference.temporal_type == TemporalType::Event && reference.anchor_date.is_none()
+        }) {
+            match self.resolve_event_anchors(&reference.raw_text).await {
+                Ok(anchors) => {
+                    if let Some(anchor) = anchors.first() {
+                        *reference = anchored_reference(&reference.raw_text, anchor.date);
+                    }
+                }
+                Err(e) => log::warn!("⚠️ Could not resolve event '{}': {}", reference.raw_text, e),
+            }
+        }
+    }
+}
+
+/// Event reference covering the window around its anchor date
+pub(crate) fn anchored_reference(event: &str, anchor: NaiveDate) -> TemporalReference {
+    let window = Duration::days(constants::EVENT_WINDOW_DAYS);
+    TemporalReference {
+        raw_text: event.to_string(),
+        parsed_date: Some(anchor),
+        date_range: Some((anchor - window, anchor + window)),
+        temporal_type: TemporalType::Event,
+        anchor_date: Some(anchor),
+    }
+}
+
+/// Date of the date node a node belongs to
+pub fn anchor_date_of(node: &Node) -> Option<NaiveDate> {
+    let date_node_id = if node.r#type == "date" {
+        &node.id
+    } else {
+        node.root_id.as_ref()?
+    };
+    NaiveDate::parse_from_str(&date_node_id.to_string(), "%Y-%m-%d").ok()
+}
+
+/// Candidates grouped by date, most matches first, then the most recent
+fn group_anchors(candidates: Vec<Node>) -> Vec<EventAnchor> {
+    let mut anchors: Vec<EventAnchor> = Vec::new();
+    for node in candidates {
+        let Some(date) = anchor_date_of(&node) else {
+            continue;
+        };
+        match anchors.iter_mut().find(|anchor| anchor.date == date) {
+            Some(anchor) => anchor.node_ids.push(node.id),
+            None => anchors.push(EventAnchor {
+                date,
+                node_ids: vec![node.id],
+            }),
+        }
+    }
+    anchors.sort_by_key(|anchor| Reverse((anchor.node_ids.len(), anchor.date)));
+    anchors.truncate(constants::MAX_EVENT_ANCHORS);
+    anchors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn note_on(date_id: &str, content: &str) -> Node {
+        let mut node = Node::new("text".to_string(), json!(content));
+        node.root_id = Some(NodeId::from_string(date_id.to_string()));
+        node
+    }
+
+    #[test]
+    fn test_anchor_date_comes_from_the_date_node_root() {
+        assert_eq!(
+            anchor_date_of(&note_on("2026-03-14", "Claire's birthday dinner")),
+            Some(date(2026, 3, 14))
+        );
+        assert_eq!(
+            anchor_date_of(&Node::new_date_node(date(2026, 3, 14))),
+            Some(date(2026, 3, 14))
+        );
+
+        // Pages and period nodes are not dates
+        let page = Node::new("text".to_string(), json!("Birthday ideas"));
+        assert_eq!(anchor_date_of(&page), None);
+        assert_eq!(anchor_date_of(&note_on("2026-W11", "Birthday week")), None);
+    }
+
+    #[test]
+    fn test_anchors_prefer_dates_with_more_matches_then_recent_ones() {
+        let anchors = group_anchors(vec![
+            note_on("2025-03-14", "Claire's birthday"),
+            note_on("2026-03-14", "Claire's birthday party"),
+            note_on("2024-03-14", "Claire's birthday"),
+            note_on("2026-03-14", "Gift for Claire's birthday"),
+            note_on("2023-03-14", "Claire's birthday"),
+            Node::new("text".to_string(), json!("Claire's birthday list")),
+        ]);
+
+        let dates: Vec<_> = anchors.iter().map(|anchor| anchor.date).collect();
+        assert_eq!(
+            dates,
+            vec![date(2026, 3, 14), date(2025, 3, 14), date(2024, 3, 14)]
+        );
+        assert_eq!(anchors[0].node_ids.len(), 2);
+    }
+
+    #[test]
+    fn test_anchored_reference_covers_a_window_around_the_anchor() {
+        let reference = anchored_reference("Claire's birthday", date(2026, 3, 14));
+        let window = constants::EVENT_WINDOW_DAYS;
+
+        assert_eq!(reference.temporal_type, TemporalType::Event);
+        assert_eq!(reference.anchor_date, Some(date(2026, 3, 14)));
+        assert_eq!(reference.parsed_date, Some(date(2026, 3, 14)));
+        assert_eq!(
+            reference.date_range,
+            Some((
+                date(2026, 3, 14) - Duration::days(window),
+                date(2026, 3, 14) + Duration::days(window)
+            ))
+        );
+    }
+}
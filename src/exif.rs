@@ -0,0 +1,475 @@
+//! EXIF metadata from image bytes
+//!
+//! Reads the TIFF structure embedded in JPEG `APP1` segments and PNG `eXIf`
+//! chunks, or a bare TIFF file. Only the tags the service uses are decoded:
+//! capture time with its UTC offset, and the GPS position and timestamp.
+
+use crate::timezone::UserTimezone;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_DATE_TIME_DIGITIZED: u16 = 0x9004;
+const TAG_OFFSET_TIME: u16 = 0x9010;
+const TAG_OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+const TAG_OFFSET_TIME_DIGITIZED: u16 = 0x9012;
+
+const TAG_GPS_LATITUDE_REF: u16 = 0x0001;
+const TAG_GPS_LATITUDE: u16 = 0x0002;
+const TAG_GPS_LONGITUDE_REF: u16 = 0x0003;
+const TAG_GPS_LONGITUDE: u16 = 0x0004;
+const TAG_GPS_TIME_STAMP: u16 = 0x0007;
+const TAG_GPS_DATE_STAMP: u16 = 0x001D;
+
+const TYPE_ASCII: u16 = 2;
+const TYPE_LONG: u16 = 4;
+const TYPE_RATIONAL: u16 = 5;
+
+/// EXIF timestamps are local camera time without an offset
+const EXIF_DATE_TIME_FORMAT: &str = "%Y:%m:%d %H:%M:%S";
+const EXIF_DATE_FORMAT: &str = "%Y:%m:%d";
+
+/// Decoded EXIF fields
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExifData {
+    /// When the picture was taken, in camera local time
+    pub captured_at: Option<NaiveDateTime>,
+    /// UTC offset of `captured_at` in seconds, when the camera recorded one
+    pub utc_offset_secs: Option<i32>,
+    /// UTC time of the GPS fix
+    pub gps_time: Option<DateTime<Utc>>,
+    pub gps: Option<GpsPosition>,
+}
+
+/// GPS position in decimal degrees; south and west are negative
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GpsPosition {
+    /// Coordinates to four decimals, about 10 m, as used for location entities
+    pub fn label(&self) -> String {
+        format!("{:.4}, {:.4}", self.latitude, self.longitude)
+    }
+}
+
+impl ExifData {
+    /// Capture instant from the recorded UTC offset, else from the GPS time
+    pub fn captured_instant(&self) -> Option<DateTime<Utc>> {
+        match (self.captured_at, self.utc_offset_secs) {
+            (Some(local), Some(offset)) => {
+                Some((local - Duration::seconds(i64::from(offset))).and_utc())
+            }
+            _ => self.gps_time,
+        }
+    }
+
+    /// Local date of the capture in `timezone`
+    ///
+    /// A capture time without offset or GPS time is camera local time, taken
+    /// to be the user's own.
+    pub fn capture_date(&self, timezone: &UserTimezone) -> Option<NaiveDate> {
+        match self.captured_instant() {
+            Some(instant) => Some(timezone.date_at(instant)),
+            None => self.captured_at.map(|local| local.date()),
+        }
+    }
+}
+
+/// EXIF fields of a JPEG, PNG or TIFF image, or `None` without EXIF
+pub fn read_exif(image: &[u8]) -> Option<ExifData> {
+    let tiff = Tiff::new(tiff_block(image)?)?;
+    let ifd0 = tiff.ifd(tiff.u32(4)? as usize)?;
+    let sub_ifd = |tag| {
+        find(&ifd0, tag)
+            .and_then(|entry| tiff.long(entry))
+            .and_then(|offset| tiff.ifd(offset as usize))
+            .unwrap_or_default()
+    };
+    let exif_ifd = sub_ifd(TAG_EXIF_IFD);
+    let gps_ifd = sub_ifd(TAG_GPS_IFD);
+
+    // Each timestamp has its own offset tag
+    let timestamps = [
+        (&exif_ifd, TAG_DATE_TIME_ORIGINAL, TAG_OFFSET_TIME_ORIGINAL),
+        (
+            &exif_ifd,
+            TAG_DATE_TIME_DIGITIZED,
+            TAG_OFFSET_TIME_DIGITIZED,
+        ),
+        (&ifd0, TAG_DATE_TIME, TAG_OFFSET_TIME),
+    ];
+    let (captured_at, utc_offset_secs) = timestamps
+        .into_iter()
+        .find_map(|(ifd, date_tag, offset_tag)| {
+            let text = tiff.ascii(find(ifd, date_tag)?)?;
+            let captured_at =
+                NaiveDateTime::parse_from_str(text.trim(), EXIF_DATE_TIME_FORMAT).ok()?;
+            let offset = find(&exif_ifd, offset_tag)
+                .and_then(|entry| tiff.ascii(entry))
+                .and_then(parse_offset);
+            Some((Some(captured_at), offset))
+        })
+        .unwrap_or_default();
+
+    Some(ExifData {
+        captured_at,
+        utc_offset_secs,
+        gps_time: gps_time(&tiff, &gps_ifd),
+        gps: gps_position(&tiff, &gps_ifd),
+    })
+}
+
+/// Seconds east of UTC from an offset such as "+09:00" or "-05:30"
+fn parse_offset(text: &str) -> Option<i32> {
+    let text = text.trim();
+    let sign = match text.get(..1)? {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let (hours, minutes) = text[1..].split_once(':')?;
+    let (hours, minutes): (i32, i32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours <= 14 && minutes < 60).then_some(sign * (hours * 3600 + minutes * 60))
+}
+
+fn gps_position(tiff: &Tiff, gps_ifd: &[Entry]) -> Option<GpsPosition> {
+    let coordinate = |ref_tag, value_tag, negative| {
+        let degrees = match tiff.rationals(find(gps_ifd, value_tag)?)?[..] {
+            [degrees, minutes, seconds] => degrees + minutes / 60.0 + seconds / 3600.0,
+            _ => return None,
+        };
+        let reference = tiff.ascii(find(gps_ifd, ref_tag)?)?;
+        Some(if reference.trim() == negative {
+            -degrees
+        } else {
+            degrees
+        })
+    };
+    let latitude = coordinate(TAG_GPS_LATITUDE_REF, TAG_GPS_LATITUDE, "S")?;
+    let longitude = coordinate(TAG_GPS_LONGITUDE_REF, TAG_GPS_LONGITUDE, "W")?;
+    let valid = latitude.abs() <= 90.0 && longitude.abs() <= 180.0;
+    valid.then_some(GpsPosition {
+        latitude,
+        longitude,
+    })
+}
+
+fn gps_time(tiff: &Tiff, gps_ifd: &[Entry]) -> Option<DateTime<Utc>> {
+    let date_text = tiff.ascii(find(gps_ifd, TAG_GPS_DATE_STAMP)?)?;
+    let date = NaiveDate::parse_from_str(date_text.trim(), EXIF_DATE_FORMAT).ok()?;
+    let time = match tiff.rationals(find(gps_ifd, TAG_GPS_TIME_STAMP)?)?[..] {
+        [hours, minutes, seconds] => {
+            NaiveTime::from_hms_opt(hours as u32, minutes as u32, seconds as u32)?
+        }
+        _ => return None,
+    };
+    Some(date.and_time(time).and_utc())
+}
+
+/// TIFF structure holding the EXIF data of an image
+fn tiff_block(image: &[u8]) -> Option<&[u8]> {
+    if image.starts_with(b"II*\0") || image.starts_with(b"MM\0*") {
+        return Some(image);
+    }
+    if image.starts_with(&[0xFF, 0xD8]) {
+        return jpeg_exif(image);
+    }
+    if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return png_exif(image);
+    }
+    None
+}
+
+fn jpeg_exif(image: &[u8]) -> Option<&[u8]> {
+    let mut pos = 2;
+    while pos + 4 <= image.len() {
+        if image[pos] != 0xFF {
+            return None;
+        }
+        let marker = image[pos + 1];
+        // Start of scan: compressed data follows, no more metadata segments
+        if marker == 0xDA {
+            return None;
+        }
+        let length = u16::from_be_bytes([image[pos + 2], image[pos + 3]]) as usize;
+        let segment = image.get(pos + 4..pos + 2 + length)?;
+        if marker == 0xE1 {
+            if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                return Some(tiff);
+            }
+        }
+        pos += 2 + length;
+    }
+    None
+}
+
+fn png_exif(image: &[u8]) -> Option<&[u8]> {
+    let mut pos = 8;
+    while pos + 8 <= image.len() {
+        let length = u32::from_be_bytes(image[pos..pos + 4].try_into().ok()?) as usize;
+        let kind = &image[pos + 4..pos + 8];
+        let data = image.get(pos + 8..pos + 8 + length)?;
+        match kind {
+            b"eXIf" => return Some(data),
+            b"IDAT" | b"IEND" => return None,
+            _ => pos += 12 + length,
+        }
+    }
+    None
+}
+
+/// IFD entry; `value_at` is the offset of its 4-byte value field
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    value_at: usize,
+}
+
+fn find(entries: &[Entry], tag: u16) -> Option<&Entry> {
+    entries.iter().find(|entry| entry.tag == tag)
+}
+
+/// TIFF data with its byte order
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        let tiff = Self {
+            data,
+            little_endian,
+        };
+        (tiff.u16(2)? == 42).then_some(tiff)
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn ifd(&self, offset: usize) -> Option<Vec<Entry>> {
+        let count = self.u16(offset)? as usize;
+        (0..count)
+            .map(|index| {
+                let at = offset + 2 + index * 12;
+                Some(Entry {
+                    tag: self.u16(at)?,
+                    kind: self.u16(at + 2)?,
+                    count: self.u32(at + 4)?,
+                    value_at: at + 8,
+                })
+            })
+            .collect()
+    }
+
+    fn long(&self, entry: &Entry) -> Option<u32> {
+        (entry.kind == TYPE_LONG).then(|| self.u32(entry.value_at))?
+    }
+
+    fn rationals(&self, entry: &Entry) -> Option<Vec<f64>> {
+        if entry.kind != TYPE_RATIONAL {
+            return None;
+        }
+        // Eight bytes each, so always stored at an offset
+        let start = self.u32(entry.value_at)? as usize;
+        (0..entry.count as usize)
+            .map(|index| {
+                let numerator = self.u32(start + index * 8)?;
+                let denominator = self.u32(start + index * 8 + 4)?;
+                (denominator != 0).then(|| f64::from(numerator) / f64::from(denominator))
+            })
+            .collect()
+    }
+
+    fn ascii(&self, entry: &Entry) -> Option<&'a str> {
+        if entry.kind != TYPE_ASCII {
+            return None;
+        }
+        let length = entry.count as usize;
+        // Values up to four bytes are stored inline
+        let start = if length <= 4 {
+            entry.value_at
+        } else {
+            self.u32(entry.value_at)? as usize
+        };
+        let bytes = self.data.get(start..start + length)?;
+        let text = bytes.split(|b| *b == 0).next()?;
+        std::str::from_utf8(text).ok()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// Little-endian TIFF block with ASCII tags in IFD0 and the EXIF IFD
+    pub(crate) fn tiff_with_dates(ifd0: &[(u16, &str)], exif: &[(u16, &str)]) -> Vec<u8> {
+        fn ifd(tags: &[(u16, &str)], offset: usize, extra: &[(u16, u32)]) -> Vec<u8> {
+            let count = tags.len() + extra.len();
+            let mut data_at = offset + 2 + count * 12 + 4;
+            let (mut entries, mut data) = (Vec::new(), Vec::new());
+            entries.extend((count as u16).to_le_bytes());
+            for (tag, text) in tags {
+                let mut value = text.as_bytes().to_vec();
+                value.push(0);
+                entries.extend(tag.to_le_bytes());
+                entries.extend(TYPE_ASCII.to_le_bytes());
+                entries.extend((value.len() as u32).to_le_bytes());
+                entries.extend((data_at as u32).to_le_bytes());
+                data_at += value.len();
+                data.extend(value);
+            }
+            for (tag, value) in extra {
+                entries.extend(tag.to_le_bytes());
+                entries.extend(TYPE_LONG.to_le_bytes());
+                entries.extend(1u32.to_le_bytes());
+                entries.extend(value.to_le_bytes());
+            }
+            entries.extend(0u32.to_le_bytes());
+            entries.extend(data);
+            entries
+        }
+
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend(8u32.to_le_bytes());
+        let ifd0_size = ifd(ifd0, 8, &[(TAG_EXIF_IFD, 0)]).len();
+        let exif_at = (8 + ifd0_size) as u32;
+        tiff.extend(ifd(ifd0, 8, &[(TAG_EXIF_IFD, exif_at)]));
+        tiff.extend(ifd(exif, exif_at as usize, &[]));
+        tiff
+    }
+
+    /// Minimal JPEG carrying a TIFF block in an `APP1` segment
+    pub(crate) fn jpeg_with_exif(tiff: &[u8]) -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        jpeg.extend(((tiff.len() + 8) as u16).to_be_bytes());
+        jpeg.extend(b"Exif\0\0");
+        jpeg.extend(tiff);
+        jpeg.extend([0xFF, 0xDA, 0x00, 0x02, 0xFF, 0xD9]);
+        jpeg
+    }
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_capture_time_prefers_date_time_original() {
+        let tiff = tiff_with_dates(
+            &[(TAG_DATE_TIME, "2026:03:20 09:00:00")],
+            &[(TAG_DATE_TIME_ORIGINAL, "2026:03:14 18:30:00")],
+        );
+        let exif = read_exif(&jpeg_with_exif(&tiff)).unwrap();
+        assert_eq!(exif.captured_at, Some(at(2026, 3, 14, 18, 30)));
+
+        // Edited files may only carry the IFD0 modification time
+        let tiff = tiff_with_dates(&[(TAG_DATE_TIME, "2026:03:20 09:00:00")], &[]);
+        assert_eq!(
+            read_exif(&tiff).unwrap().captured_at,
+            Some(at(2026, 3, 20, 9, 0))
+        );
+    }
+
+    /// Image from `tests/fixtures/images`
+    pub(crate) fn fixture(name: &str) -> Vec<u8> {
+        let path = format!(
+            "{}/tests/fixtures/images/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        std::fs::read(&path).unwrap_or_else(|e| panic!("fixture {}: {}", path, e))
+    }
+
+    #[test]
+    fn test_fixture_offsets_and_gps_time_resolve_the_capture_instant() {
+        // Big-endian, taken 07:30 in Tokyo with the offset recorded
+        let tokyo = read_exif(&fixture("tokyo_offset_gps.jpg")).unwrap();
+        assert_eq!(tokyo.captured_at, Some(at(2026, 3, 15, 7, 30)));
+        assert_eq!(tokyo.utc_offset_secs, Some(9 * 3600));
+        assert_eq!(
+            tokyo.captured_instant(),
+            Some(at(2026, 3, 14, 22, 30).and_utc())
+        );
+        let new_york = UserTimezone::parse("America/New_York").unwrap();
+        assert_eq!(
+            tokyo.capture_date(&new_york),
+            NaiveDate::from_ymd_opt(2026, 3, 14)
+        );
+        let position = tokyo.gps.unwrap();
+        assert!((position.latitude - 35.658778).abs() < 1e-6);
+        assert!((position.longitude - 139.701083).abs() < 1e-6);
+        assert_eq!(position.label(), "35.6588, 139.7011");
+
+        // Without an offset, the GPS fix time places the picture
+        let sydney = read_exif(&fixture("gps_utc_stamp.jpg")).unwrap();
+        assert_eq!(sydney.utc_offset_secs, None);
+        assert_eq!(sydney.gps_time, Some(at(2026, 7, 5, 1, 15).and_utc()));
+        let los_angeles = UserTimezone::parse("America/Los_Angeles").unwrap();
+        assert_eq!(
+            sydney.capture_date(&los_angeles),
+            NaiveDate::from_ymd_opt(2026, 7, 4)
+        );
+        assert_eq!(sydney.gps.unwrap().label(), "-33.8696, 151.2101");
+    }
+
+    #[test]
+    fn test_fixture_without_offset_uses_camera_local_time() {
+        let exif = read_exif(&fixture("local_time_only.jpg")).unwrap();
+
+        assert_eq!(exif.captured_at, Some(at(2025, 12, 31, 23, 45)));
+        assert_eq!(exif.captured_instant(), None);
+        assert_eq!(exif.gps, None);
+        // Taken as the user's local time, whatever their timezone
+        let tokyo = UserTimezone::parse("Asia/Tokyo").unwrap();
+        assert_eq!(
+            exif.capture_date(&tokyo),
+            NaiveDate::from_ymd_opt(2025, 12, 31)
+        );
+        assert_eq!(parse_offset("-05:30"), Some(-(5 * 3600 + 30 * 60)));
+        assert_eq!(parse_offset("09:00"), None);
+    }
+
+    #[test]
+    fn test_images_without_exif_or_with_broken_exif() {
+        assert_eq!(read_exif(&[0xFF, 0xD8, 0xFF, 0xDA, 0x00, 0x02]), None);
+        assert_eq!(read_exif(b"GIF89a"), None);
+
+        // Truncated data and unparsable dates do not fail the read
+        let tiff = tiff_with_dates(&[], &[(TAG_DATE_TIME_ORIGINAL, "0000:00:00 00:00:00")]);
+        assert_eq!(read_exif(&tiff).unwrap().captured_at, None);
+        let jpeg = jpeg_with_exif(&tiff);
+        assert_eq!(read_exif(&jpeg[..jpeg.len() / 2]), None);
+    }
+}
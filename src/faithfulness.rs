@@ -0,0 +1,340 @@
+//! Post-generation faithfulness check
+//!
+//! Each answer sentence is checked against the retrieved context, by lexical
+//! overlap and optionally a yes/no verdict from the NLP engine. Depending on
+//! the mode, an insufficiently supported answer is reported as-is, revised to
+//! its supported sentences, or replaced by a "not found in your notes" answer.
+
+use crate::confidence::{sentence_spans, MAX_ENTAILMENT_CONTEXT_CHARS, SUPPORTED_SENTENCE_OVERLAP};
+use crate::constants;
+use crate::generation::TextGenerator;
+use crate::prompts::{names, PromptRegistry};
+use crate::reranker::lexical_overlap_score;
+use nodespace_core_types::NodeSpaceResult;
+use serde::{Deserialize, Serialize};
+
+/// Answer returned instead of an unsupported one
+pub const NOT_FOUND_ANSWER: &str = "I couldn't find an answer to that in your notes.";
+
+/// What to do with an answer whose sentences are not supported by the context
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaithfulnessMode {
+    /// Skip verification
+    #[default]
+    Off,
+    /// Verify and report, never change the answer
+    Report,
+    /// Drop unsupported sentences; abstain when nothing is supported
+    Revise,
+    /// Replace the answer with `NOT_FOUND_ANSWER` when support is insufficient
+    Abstain,
+}
+
+/// Per-call faithfulness settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaithfulnessOptions {
+    pub mode: FaithfulnessMode,
+    /// Share of sentences (0.0-1.0) that must be supported for the answer to pass
+    pub min_supported_ratio: f32,
+    /// Ask the NLP engine about sentences that lexical overlap does not support
+    pub verify_with_llm: bool,
+}
+
+impl Default for FaithfulnessOptions {
+    fn default() -> Self {
+        Self {
+            mode: FaithfulnessMode::Off,
+            min_supported_ratio: constants::DEFAULT_MIN_SUPPORTED_RATIO,
+            verify_with_llm: false,
+        }
+    }
+}
+
+impl FaithfulnessOptions {
+    pub fn with_mode(mode: FaithfulnessMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FaithfulnessOutcome {
+    /// Enough sentences were supported; the answer is unchanged
+    Passed,
+    /// Support was insufficient but the mode does not change answers
+    Flagged,
+    /// Unsupported sentences were removed
+    Revised,
+    /// The answer was replaced by `NOT_FOUND_ANSWER`
+    Abstained,
+}
+
+/// Support for one answer sentence
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SentenceSupport {
+    pub text: String,
+    /// Character span of the sentence in the original answer
+    pub start: usize,
+    pub end: usize,
+    pub supported: bool,
+    /// Best lexical overlap with any source
+    pub overlap: f32,
+    /// Index into the context of the best-overlapping source
+    pub source_index: Option<usize>,
+    /// NLP engine verdict, when asked
+    pub llm_verdict: Option<bool>,
+}
+
+/// Result of the faithfulness check, reported in `QueryResponse`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaithfulnessReport {
+    pub mode: FaithfulnessMode,
+    pub outcome: FaithfulnessOutcome,
+    pub supported_ratio: f32,
+    pub sentences: Vec<SentenceSupport>,
+    /// Answer as generated, when it was revised or replaced
+    pub original_answer: Option<String>,
+}
+
+/// Check every sentence of `answer` against `context`
+pub async fn check_sentences(
+    verifier: Option<&dyn TextGenerator>,
+    prompts: &PromptRegistry,
+    answer: &str,
+    context: &[String],
+) -> Vec<SentenceSupport> {
+    let chars: Vec<char> = answer.chars().collect();
+    let mut sentences = Vec::new();
+
+    for (start, end) in sentence_spans(answer) {
+        let text: String = chars[start..end]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .to_string();
+        let (source_index, overlap) = context
+            .iter()
+            .map(|source| lexical_overlap_score(&text, source))
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(index, overlap)| (Some(index), overlap))
+            .unwrap_or((None, 0.0));
+
+        let mut support = SentenceSupport {
+            text,
+            start,
+            end,
+            supported: overlap >= SUPPORTED_SENTENCE_OVERLAP,
+            overlap,
+            source_index,
+            llm_verdict: None,
+        };
+
+        if let Some(generator) = verifier.filter(|_| !support.supported && !context.is_empty()) {
+            let verified = match verification_prompt(prompts, &support.text, context) {
+                Ok(prompt) => generator.generate(&prompt).await,
+                Err(e) => Err(e),
+            };
+            match verified {
+                Ok(response) => {
+                    let verdict = parse_verdict(&response);
+                    support.llm_verdict = verdict;
+                    support.supported = verdict.unwrap_or(false);
+                }
+                Err(e) => log::warn!("⚠️ Sentence verification failed: {}", e),
+            }
+        }
+
+        sentences.push(support);
+    }
+
+    sentences
+}
+
+/// Apply the configured mode to a checked answer
+///
+/// Returns the answer to send (possibly unchanged) and the report.
+pub fn apply_faithfulness(
+    options: &FaithfulnessOptions,
+    answer: &str,
+    sentences: Vec<SentenceSupport>,
+) -> (String, FaithfulnessReport) {
+    let supported = sentences.iter().filter(|s| s.supported).count();
+    let supported_ratio = if sentences.is_empty() {
+        0.0
+    } else {
+        supported as f32 / sentences.len() as f32
+    };
+    let sufficient = !sentences.is_empty() && supported_ratio >= options.min_supported_ratio;
+
+    let (final_answer, outcome) = match options.mode {
+        _ if sufficient => (answer.to_string(), FaithfulnessOutcome::Passed),
+        FaithfulnessMode::Off | FaithfulnessMode::Report => {
+            (answer.to_string(), FaithfulnessOutcome::Flagged)
+        }
+        FaithfulnessMode::Revise if supported > 0 => (
+            supported_text(answer, &sentences),
+            FaithfulnessOutcome::Revised,
+        ),
+        FaithfulnessMode::Revise | FaithfulnessMode::Abstain => {
+            (NOT_FOUND_ANSWER.to_string(), FaithfulnessOutcome::Abstained)
+        }
+    };
+
+    let original_answer = match outcome {
+        FaithfulnessOutcome::Revised | FaithfulnessOutcome::Abstained => Some(answer.to_string()),
+        _ => None,
+    };
+
+    (
+        final_answer,
+        FaithfulnessReport {
+            mode: options.mode,
+            outcome,
+            supported_ratio,
+            sentences,
+            original_answer,
+        },
+    )
+}
+
+/// Supported sentences with their punctuation and trailing citation markers
+fn supported_text(answer: &str, sentences: &[SentenceSupport]) -> String {
+    let chars: Vec<char> = answer.chars().collect();
+    sentences
+        .iter()
+        .enumerate()
+        .filter(|(_, sentence)| sentence.supported)
+        .map(|(index, sentence)| {
+            let until = sentences
+                .get(index + 1)
+                .map(|next| next.start)
+                .unwrap_or(chars.len());
+            chars[sentence.start..until]
+                .iter()
+                .collect::<String>()
+                .trim()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn verification_prompt(
+    prompts: &PromptRegistry,
+    sentence: &str,
+    context: &[String],
+) -> NodeSpaceResult<String> {
+    let sources: String = context
+        .join("\n\n")
+        .chars()
+        .take(MAX_ENTAILMENT_CONTEXT_CHARS)
+        .collect();
+    prompts
+        .render(
+            names::VERIFY_STATEMENT,
+            &[("context", &sources), ("statement", sentence)],
+        )
+        .map(|prompt| prompt.text)
+}
+
+fn parse_verdict(response: &str) -> Option<bool> {
+    let word: String = response
+        .trim_start_matches(|c: char| !c.is_alphabetic())
+        .chars()
+        .take_while(|c| c.is_alphabetic())
+        .collect::<String>()
+        .to_lowercase();
+    match word.as_str() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    /// Verifier that accepts statements mentioning "March"
+    struct MarchVerifier;
+
+    #[async_trait]
+    impl TextGenerator for MarchVerifier {
+        async fn generate(&self, prompt: &str) -> NodeSpaceResult<String> {
+            let statement = prompt.split("Statement:").nth(1).unwrap_or("");
+            if statement.contains("March") {
+                Ok("Yes.".to_string())
+            } else {
+                Ok("No".to_string())
+            }
+        }
+    }
+
+    fn context() -> Vec<String> {
+        vec!["The Q3 marketing budget of $50k was approved by Claire".to_string()]
+    }
+
+    #[tokio::test]
+    async fn test_revise_keeps_supported_sentences_and_markers() {
+        let answer =
+            "Claire approved the marketing budget.[1] The office moves to Berlin next week [2].";
+        let sentences = check_sentences(None, &PromptRegistry::default(), answer, &context()).await;
+        let options = FaithfulnessOptions::with_mode(FaithfulnessMode::Revise);
+
+        let (revised, report) = apply_faithfulness(&options, answer, sentences);
+
+        assert_eq!(revised, "Claire approved the marketing budget.[1]");
+        assert_eq!(report.outcome, FaithfulnessOutcome::Revised);
+        assert_eq!(report.supported_ratio, 0.5);
+        assert_eq!(report.original_answer.as_deref(), Some(answer));
+        assert_eq!(report.sentences[0].source_index, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_abstains_when_support_is_insufficient() {
+        let answer = "The office moves to Berlin. Lunch is catered on Fridays.";
+        let sentences = check_sentences(None, &PromptRegistry::default(), answer, &context()).await;
+
+        let (abstained, report) = apply_faithfulness(
+            &FaithfulnessOptions::with_mode(FaithfulnessMode::Abstain),
+            answer,
+            sentences.clone(),
+        );
+        assert_eq!(abstained, NOT_FOUND_ANSWER);
+        assert_eq!(report.outcome, FaithfulnessOutcome::Abstained);
+
+        // Report mode flags the answer but never changes it
+        let (unchanged, report) = apply_faithfulness(
+            &FaithfulnessOptions::with_mode(FaithfulnessMode::Report),
+            answer,
+            sentences,
+        );
+        assert_eq!(unchanged, answer);
+        assert_eq!(report.outcome, FaithfulnessOutcome::Flagged);
+    }
+
+    #[tokio::test]
+    async fn test_llm_verdict_supports_paraphrased_sentence() {
+        let context = vec!["Launch moved to the third month of the year".to_string()];
+        let answer = "It happens in March. It will be in Berlin.";
+
+        let sentences = check_sentences(
+            Some(&MarchVerifier),
+            &PromptRegistry::default(),
+            answer,
+            &context,
+        )
+        .await;
+
+        assert_eq!(sentences[0].llm_verdict, Some(true));
+        assert!(sentences[0].supported);
+        assert_eq!(sentences[1].llm_verdict, Some(false));
+        assert!(!sentences[1].supported);
+    }
+}
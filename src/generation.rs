@@ -0,0 +1,56 @@
+//! Text generation seam for pluggable RAG stages
+//!
+//! Stages such as rerankers only need plain prompt-in / text-out generation.
+//! The `TextGenerator` trait captures that capability so stages can be driven
+//! by the service's `NLPEngine` in production and by lightweight doubles in tests.
+
+use crate::{NLPEngine, ResolvedGeneration};
+use async_trait::async_trait;
+use nodespace_core_types::NodeSpaceResult;
+use nodespace_nlp_engine::TextGenerationRequest;
+
+/// Minimal text generation capability consumed by RAG stages
+#[async_trait]
+pub trait TextGenerator: Send + Sync {
+    /// Generate a completion for the given prompt
+    async fn generate(&self, prompt: &str) -> NodeSpaceResult<String>;
+
+    /// Generate a completion with resolved generation parameters
+    ///
+    /// Generators without such parameters ignore them.
+    async fn generate_with(
+        &self,
+        prompt: &str,
+        _generation: &ResolvedGeneration,
+    ) -> NodeSpaceResult<String> {
+        self.generate(prompt).await
+    }
+}
+
+/// Every NLP engine can serve as a text generator through `generate_text`,
+/// or `generate_text_enhanced` when parameters are given
+#[async_trait]
+impl<N: NLPEngine + Send + Sync> TextGenerator for N {
+    async fn generate(&self, prompt: &str) -> NodeSpaceResult<String> {
+        self.generate_text(prompt).await
+    }
+
+    async fn generate_with(
+        &self,
+        prompt: &str,
+        generation: &ResolvedGeneration,
+    ) -> NodeSpaceResult<String> {
+        let request = TextGenerationRequest {
+            prompt: prompt.to_string(),
+            max_tokens: generation.max_tokens,
+            temperature: generation.temperature,
+            context_window: generation.context_window,
+            conversation_mode: false,
+            rag_context: None,
+            enable_link_generation: false,
+            node_metadata: vec![],
+        };
+        let response = self.generate_text_enhanced(request).await?;
+        Ok(response.text)
+    }
+}
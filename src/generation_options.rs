@@ -0,0 +1,286 @@
+//! Generation parameters for answer requests
+//!
+//! Temperature, answer length and context window are resolved in layers:
+//! per-call `GenerationOptions` override `PerformanceConfig`, which overrides
+//! the built-in defaults; the result is then fitted to the selected model's
+//! `ModelLimits`, taken from `ModelConfig` or set by the engine factory. The
+//! resolved values are returned with each answer, together with any adjustment
+//! made to fit the model.
+
+use crate::{constants, ModelConfig, PerformanceConfig};
+use nodespace_core_types::{NodeSpaceError, NodeSpaceResult, ValidationError};
+use serde::{Deserialize, Serialize};
+
+/// Per-call overrides; unset fields fall back to the service configuration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationOptions {
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+    #[serde(default)]
+    pub context_window: Option<usize>,
+}
+
+impl GenerationOptions {
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_context_window(mut self, context_window: usize) -> Self {
+        self.context_window = Some(context_window);
+        self
+    }
+}
+
+/// Limits of the text generation model requests are sent to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelLimits {
+    /// Largest context window the model accepts, in tokens
+    pub context_window: usize,
+    /// Largest answer the model will generate, in tokens
+    pub max_output_tokens: usize,
+}
+
+impl ModelLimits {
+    /// Limits configured for the text model, with the defaults for unset ones
+    pub fn from_config(config: &ModelConfig) -> Self {
+        let defaults = Self::default();
+        Self {
+            context_window: config.context_length.unwrap_or(defaults.context_window),
+            max_output_tokens: config
+                .max_output_tokens
+                .unwrap_or(defaults.max_output_tokens),
+        }
+    }
+}
+
+/// Fallback for models whose limits are not configured
+impl Default for ModelLimits {
+    fn default() -> Self {
+        Self {
+            context_window: constants::DEFAULT_MODEL_CONTEXT_WINDOW,
+            max_output_tokens: constants::DEFAULT_MODEL_MAX_OUTPUT_TOKENS,
+        }
+    }
+}
+
+/// Parameters actually used for a generation request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolvedGeneration {
+    pub temperature: f32,
+    pub max_tokens: usize,
+    pub context_window: usize,
+    /// Values lowered to fit the model's limits
+    #[serde(default)]
+    pub adjustments: Vec<String>,
+}
+
+/// Resolve generation parameters for one request
+///
+/// `default_max_tokens` is the answer length of the calling preset. Values
+/// above the model's limits are lowered and reported in `adjustments`;
+/// values that cannot produce a valid request are rejected.
+pub fn resolve_generation(
+    overrides: &GenerationOptions,
+    config: &PerformanceConfig,
+    limits: &ModelLimits,
+    default_max_tokens: usize,
+) -> NodeSpaceResult<ResolvedGeneration> {
+    let temperature = overrides
+        .temperature
+        .or(config.temperature)
+        .unwrap_or(constants::DEFAULT_TEMPERATURE);
+    if !(constants::MIN_TEMPERATURE..=constants::MAX_TEMPERATURE).contains(&temperature) {
+        return Err(invalid_option(
+            "temperature",
+            &format!(
+                "a value between {} and {}",
+                constants::MIN_TEMPERATURE,
+                constants::MAX_TEMPERATURE
+            ),
+            &temperature.to_string(),
+        ));
+    }
+
+    let mut adjustments = Vec::new();
+
+    let mut context_window = overrides
+        .context_window
+        .or(config.context_window)
+        .unwrap_or(constants::DEFAULT_CONTEXT_WINDOW);
+    if context_window == 0 {
+        return Err(invalid_option(
+            "context_window",
+            "a positive token count",
+            "0",
+        ));
+    }
+    if context_window > limits.context_window {
+        adjustments.push(format!(
+            "context_window lowered from {} to the model limit of {}",
+            context_window, limits.context_window
+        ));
+        context_window = limits.context_window;
+    }
+
+    let mut max_tokens = overrides.max_tokens.unwrap_or(default_max_tokens);
+    if max_tokens == 0 {
+        return Err(invalid_option("max_tokens", "a positive token count", "0"));
+    }
+    if max_tokens > limits.max_output_tokens {
+        adjustments.push(format!(
+            "max_tokens lowered from {} to the model limit of {}",
+            max_tokens, limits.max_output_tokens
+        ));
+        max_tokens = limits.max_output_tokens;
+    }
+    if max_tokens >= context_window {
+        return Err(invalid_option(
+            "max_tokens",
+            &format!("fewer tokens than the context window of {}", context_window),
+            &max_tokens.to_string(),
+        ));
+    }
+
+    for adjustment in &adjustments {
+        log::warn!("⚠️ Generation options adjusted: {}", adjustment);
+    }
+
+    Ok(ResolvedGeneration {
+        temperature,
+        max_tokens,
+        context_window,
+        adjustments,
+    })
+}
+
+fn invalid_option(field: &str, expected: &str, actual: &str) -> NodeSpaceError {
+    NodeSpaceError::Validation(ValidationError::InvalidFormat {
+        field: format!("generation option '{}'", field),
+        expected: expected.to_string(),
+        actual: actual.to_string(),
+        examples: vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(temperature: Option<f32>, context_window: Option<usize>) -> PerformanceConfig {
+        PerformanceConfig {
+            max_batch_size: None,
+            context_window,
+            temperature,
+            max_siblings_context: None,
+            max_children_context: None,
+        }
+    }
+
+    #[test]
+    fn test_overrides_take_precedence_over_config_and_defaults() {
+        let limits = ModelLimits::default();
+
+        let from_config = resolve_generation(
+            &GenerationOptions::default(),
+            &config(Some(0.2), Some(2048)),
+            &limits,
+            500,
+        )
+        .unwrap();
+        assert_eq!(from_config.temperature, 0.2);
+        assert_eq!(from_config.context_window, 2048);
+        assert_eq!(from_config.max_tokens, 500);
+
+        let overridden = resolve_generation(
+            &GenerationOptions::default()
+                .with_temperature(0.9)
+                .with_max_tokens(300),
+            &config(Some(0.2), None),
+            &limits,
+            500,
+        )
+        .unwrap();
+        assert_eq!(overridden.temperature, 0.9);
+        assert_eq!(overridden.max_tokens, 300);
+        assert_eq!(overridden.context_window, constants::DEFAULT_CONTEXT_WINDOW);
+        assert!(overridden.adjustments.is_empty());
+    }
+
+    #[test]
+    fn test_values_above_model_limits_are_lowered_and_reported() {
+        let limits = ModelLimits {
+            context_window: 4096,
+            max_output_tokens: 1024,
+        };
+
+        let resolved = resolve_generation(
+            &GenerationOptions::default().with_context_window(32_768),
+            &config(None, None),
+            &limits,
+            2000,
+        )
+        .unwrap();
+
+        assert_eq!(resolved.context_window, 4096);
+        assert_eq!(resolved.max_tokens, 1024);
+        assert_eq!(resolved.adjustments.len(), 2);
+    }
+
+    #[test]
+    fn test_limits_come_from_model_config() {
+        let mut model_config = crate::NodeSpaceConfig::default().model_config;
+        assert_eq!(
+            ModelLimits::from_config(&model_config),
+            ModelLimits::default()
+        );
+
+        model_config.context_length = Some(2048);
+        let limits = ModelLimits::from_config(&model_config);
+        assert_eq!(limits.context_window, 2048);
+        assert_eq!(
+            limits.max_output_tokens,
+            constants::DEFAULT_MODEL_MAX_OUTPUT_TOKENS
+        );
+
+        // A small configured window stops requests sized for the fallback
+        let resolved = resolve_generation(
+            &GenerationOptions::default(),
+            &config(None, Some(4096)),
+            &limits,
+            500,
+        )
+        .unwrap();
+        assert_eq!(resolved.context_window, 2048);
+        assert_eq!(resolved.adjustments.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        let limits = ModelLimits::default();
+        let defaults = config(None, None);
+
+        for overrides in [
+            GenerationOptions::default().with_temperature(-0.1),
+            GenerationOptions::default().with_temperature(f32::NAN),
+            GenerationOptions::default().with_max_tokens(0),
+            GenerationOptions::default().with_context_window(0),
+            GenerationOptions::default()
+                .with_context_window(1024)
+                .with_max_tokens(1024),
+        ] {
+            assert!(
+                resolve_generation(&overrides, &defaults, &limits, 500).is_err(),
+                "{:?} should be rejected",
+                overrides
+            );
+        }
+    }
+}
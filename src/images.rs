@@ -0,0 +1,765 @@
+//! Image ingestion with captions and visual attribute indexing
+//!
+//! An ingested image becomes an `image` node whose content is its caption, so
+//! it is embedded and searched like text. Dimensions and EXIF data are read
+//! from the bytes; imported photos go under the date they were taken, with
+//! their GPS position as a location entity. Caption and `VisualAttributes`
+//! come from the multimodal model behind `ImageAnalyzer`, by default the
+//! Ollama model named in `ModelConfig`. Attribute words are kept in a visual
+//! index, so "the photo with the red shirt" scores image nodes by how many
+//! requested attributes they show; like the entity index, it is saved
+//! through the service's `IndexStore` and loaded on first use. The image
+//! bytes are not stored.
+
+use crate::exif::{read_exif, ExifData};
+use crate::index_store::SavedIndex;
+use crate::prompts::{names, PromptRegistry};
+use crate::structured_output::{generate_structured, ResponseObject, StructuredOutputMetrics};
+use crate::{
+    constants, DataStore, HierarchyComputation, NLPEngine, NodeSpaceService, SearchResult,
+    TextGenerator, VisualAttributes,
+};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use nodespace_core_types::{Node, NodeId, NodeSpaceError, NodeSpaceResult};
+use nodespace_data_store::NodeType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+
+/// Node metadata key holding `ImageMetadata`
+pub const IMAGE_METADATA_KEY: &str = "image";
+
+/// Node metadata key holding the image's `VisualAttributes`
+pub const VISUAL_METADATA_KEY: &str = "visual";
+
+/// Name of the visual index in the index store
+pub const VISUAL_INDEX_NAME: &str = "visual_index";
+
+/// Query words asking for images, with their plurals
+const IMAGE_WORDS: &[&str] = &[
+    "photo",
+    "photos",
+    "picture",
+    "pictures",
+    "image",
+    "images",
+    "screenshot",
+    "screenshots",
+    "selfie",
+    "selfies",
+    "pic",
+    "pics",
+];
+
+/// Words too common to tell images apart
+const VISUAL_STOPWORDS: &[&str] = &["the", "and", "with", "wearing", "of", "in", "on", "a", "an"];
+
+/// Multimodal model describing images from a prompt
+#[async_trait]
+pub trait ImageAnalyzer: Send + Sync {
+    /// Generate a completion for the prompt about the given image
+    async fn describe_image(&self, image: &[u8], prompt: &str) -> NodeSpaceResult<String>;
+}
+
+/// Image to ingest
+#[derive(Debug, Clone)]
+pub enum ImageSource {
+    Bytes {
+        data: Vec<u8>,
+        file_name: Option<String>,
+    },
+    Path(PathBuf),
+}
+
+/// Metadata stored on image nodes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageMetadata {
+    pub file_name: Option<String>,
+    /// Source path, when ingested from a file
+    pub path: Option<String>,
+    /// "png", "jpeg", "gif" or "webp"
+    pub format: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub byte_size: usize,
+    pub exif: Option<ExifData>,
+    pub caption: Option<String>,
+}
+
+/// Result of ingesting an image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestedImage {
+    pub node_id: NodeId,
+    pub metadata: ImageMetadata,
+    pub visual: VisualAttributes,
+}
+
+/// Model description of an image
+///
+/// The caption is required; missing attribute lists are empty.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "ResponseObject")]
+struct ImageDescription {
+    caption: String,
+    visual: VisualAttributes,
+}
+
+impl TryFrom<ResponseObject> for ImageDescription {
+    type Error = String;
+
+    fn try_from(object: ResponseObject) -> Result<Self, String> {
+        let caption = match object.get("caption") {
+            Some(serde_json::Value::String(caption)) => caption.clone(),
+            _ => return Err("expected an object with a \"caption\" string".to_string()),
+        };
+        Ok(Self {
+            caption,
+            visual: VisualAttributes::from_object(&object)?,
+        })
+    }
+}
+
+/// Format and pixel size read from an image header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageDimensions {
+    pub format: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Mapping from visual attribute words to the image nodes showing them
+#[derive(Debug, Default)]
+pub struct VisualIndex {
+    terms: HashMap<String, Vec<NodeId>>,
+    node_terms: HashMap<NodeId, Vec<String>>,
+}
+
+impl VisualIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index a node's attributes, replacing what was indexed for it before
+    pub fn index_node(&mut self, node_id: &NodeId, visual: &VisualAttributes) {
+        self.remove_node(node_id);
+        let terms = visual_terms(visual);
+        for term in &terms {
+            self.terms
+                .entry(term.clone())
+                .or_default()
+                .push(node_id.clone());
+        }
+        if !terms.is_empty() {
+            self.node_terms.insert(node_id.clone(), terms);
+        }
+    }
+
+    pub fn remove_node(&mut self, node_id: &NodeId) {
+        for term in self.node_terms.remove(node_id).unwrap_or_default() {
+            if let Some(node_ids) = self.terms.get_mut(&term) {
+                node_ids.retain(|id| id != node_id);
+                if node_ids.is_empty() {
+                    self.terms.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// Nodes showing any of the requested attributes, with the share matched
+    ///
+    /// Best match first; ties keep the order nodes were indexed in.
+    pub fn search(&self, visual: &VisualAttributes) -> Vec<(NodeId, f32)> {
+        let wanted = visual_terms(visual);
+        if wanted.is_empty() {
+            return vec![];
+        }
+        let mut matches: Vec<(NodeId, usize)> = Vec::new();
+        for node_id in wanted
+            .iter()
+            .filter_map(|term| self.terms.get(term))
+            .flatten()
+        {
+            match matches.iter_mut().find(|(id, _)| id == node_id) {
+                Some((_, count)) => *count += 1,
+                None => matches.push((node_id.clone(), 1)),
+            }
+        }
+        matches.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        matches
+            .into_iter()
+            .map(|(node_id, count)| (node_id, count as f32 / wanted.len() as f32))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.node_terms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.node_terms.is_empty()
+    }
+}
+
+impl SavedIndex for VisualIndex {
+    fn to_saved(&self) -> serde_json::Value {
+        serde_json::to_value(&self.terms).unwrap_or_default()
+    }
+
+    fn from_saved(saved: serde_json::Value) -> Option<Self> {
+        let terms: HashMap<String, Vec<NodeId>> = serde_json::from_value(saved).ok()?;
+        let mut node_terms: HashMap<NodeId, Vec<String>> = HashMap::new();
+        for (term, node_ids) in &terms {
+            for node_id in node_ids {
+                node_terms
+                    .entry(node_id.clone())
+                    .or_default()
+                    .push(term.clone());
+            }
+        }
+        Some(Self { terms, node_terms })
+    }
+}
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Ingest an image as an `image` node under the given date
+    ///
+    /// When the image analyzer is unset or fails, the node is stored with its
+    /// file name as content and no visual attributes.
+    pub async fn ingest_image(
+        &self,
+        source: ImageSource,
+        date: NaiveDate,
+    ) -> NodeSpaceResult<IngestedImage> {
+        self.store_image(source, Some(date)).await
+    }
+
+    /// Import a photo under the date node of its capture date
+    ///
+    /// The EXIF capture time is converted to the user's timezone using the
+    /// recorded UTC offset or GPS time; images without a capture time go
+    /// under today.
+    pub async fn import_image(&self, source: ImageSource) -> NodeSpaceResult<IngestedImage> {
+        self.store_image(source, None).await
+    }
+
+    async fn store_image(
+        &self,
+        source: ImageSource,
+        date: Option<NaiveDate>,
+    ) -> NodeSpaceResult<IngestedImage> {
+        let (data, file_name, path) = match source {
+            ImageSource::Bytes { data, file_name } => (data, file_name, None),
+            ImageSource::Path(path) => {
+                let data =
+                    tokio::fs::read(&path)
+                        .await
+                        .map_err(|e| NodeSpaceError::InternalError {
+                            message: format!("Cannot read image {}: {}", path.display(), e),
+                            service: "core-logic".to_string(),
+                        })?;
+                let file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned());
+                (data, file_name, Some(path.display().to_string()))
+            }
+        };
+
+        let dimensions = image_dimensions(&data);
+        let exif = read_exif(&data);
+        let date = date
+            .or_else(|| exif.as_ref()?.capture_date(self.timezone()))
+            .unwrap_or_else(|| self.today());
+        let mut metadata = ImageMetadata {
+            file_name,
+            path,
+            format: dimensions.map(|d| d.format.to_string()),
+            width: dimensions.map(|d| d.width),
+            height: dimensions.map(|d| d.height),
+            byte_size: data.len(),
+            exif,
+            caption: None,
+        };
+
+        let description = self.describe_image(&data).await;
+        let caption = description
+            .as_ref()
+            .map(|description| description.caption.trim().to_string())
+            .filter(|caption| !caption.is_empty());
+        metadata.caption = caption.clone();
+        let visual = description
+            .map(|description| description.visual)
+            .unwrap_or_default();
+
+        let content = caption
+            .or_else(|| metadata.file_name.clone())
+            .unwrap_or_else(|| "Image".to_string());
+        let node_metadata = serde_json::json!({
+            IMAGE_METADATA_KEY: metadata,
+            VISUAL_METADATA_KEY: visual,
+        });
+        let node_id = NodeId::new();
+        self.create_node_for_date_with_id(
+            node_id.clone(),
+            date,
+            &content,
+            NodeType::Image,
+            Some(node_metadata),
+            None,
+            None,
+        )
+        .await?;
+        match self.loaded_visual_index().await {
+            Ok(index) => {
+                index.write().await.index_node(&node_id, &visual);
+                self.visual_index.schedule_save();
+            }
+            // The attributes are in the node's metadata and return with the next rebuild
+            Err(e) => log::warn!("⚠️ Could not index image {}: {}", node_id, e),
+        }
+
+        log::info!(
+            "🖼️ Ingested image {} ({} bytes) under {}",
+            node_id,
+            metadata.byte_size,
+            date
+        );
+        Ok(IngestedImage {
+            node_id,
+            metadata,
+            visual,
+        })
+    }
+
+    /// Caption and visual attributes from the image analyzer, when configured
+    async fn describe_image(&self, image: &[u8]) -> Option<ImageDescription> {
+        let analyzer = self.image_analyzer.as_deref()?;
+        describe(
+            analyzer,
+            &self.prompts,
+            &self.structured_output_metrics,
+            image,
+        )
+        .await
+    }
+
+    /// Image nodes scored by the share of requested attributes they show
+    pub(crate) async fn search_visual_index(
+        &self,
+        visual_refs: &VisualAttributes,
+    ) -> NodeSpaceResult<Vec<SearchResult>> {
+        let matches = self
+            .loaded_visual_index()
+            .await?
+            .read()
+            .await
+            .search(visual_refs);
+        let mut results = Vec::new();
+        for (node_id, share) in matches
+            .into_iter()
+            .take(constants::DEFAULT_MAX_RESULTS_PER_STRATEGY)
+        {
+            if let Some(node) = self.data_store.get_node(&node_id).await? {
+                results.push(SearchResult {
+                    node_id,
+                    node,
+                    score: constants::BASE_CONFIDENCE_WITH_CONTEXT * share,
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    /// The visual index, loaded from the index store or rebuilt on first use
+    pub(crate) async fn loaded_visual_index(&self) -> NodeSpaceResult<&RwLock<VisualIndex>> {
+        self.visual_index
+            .load_with(|| self.scan_visual_index())
+            .await
+    }
+
+    /// Rebuild the visual index from the attributes stored on image nodes
+    ///
+    /// Reads every node once. Returns the number of indexed images.
+    pub async fn rebuild_visual_index(&self) -> NodeSpaceResult<usize> {
+        let index = self.scan_visual_index().await?;
+        let indexed = index.len();
+        self.visual_index.replace(index).await;
+        Ok(indexed)
+    }
+
+    async fn scan_visual_index(&self) -> NodeSpaceResult<VisualIndex> {
+        let nodes = self.data_store.query_nodes("").await?;
+        let mut index = VisualIndex::new();
+        for node in nodes.iter().filter(|node| node.r#type == "image") {
+            if let Some(visual) = stored_visual_attributes(node) {
+                index.index_node(&node.id, &visual);
+            }
+        }
+        log::info!("🖼️ Rebuilt visual index: {} images", index.len());
+        Ok(index)
+    }
+
+    pub(crate) async fn unindex_visual_attributes(&self, node_id: &NodeId) {
+        let index = match self.loaded_visual_index().await {
+            Ok(index) => index,
+            Err(e) => {
+                log::warn!("⚠️ Could not unindex image {}: {}", node_id, e);
+                return;
+            }
+        };
+        let removed = {
+            let mut index = index.write().await;
+            let indexed = index.node_terms.contains_key(node_id);
+            index.remove_node(node_id);
+            indexed
+        };
+        if removed {
+            self.visual_index.schedule_save();
+        }
+    }
+}
+
+/// Ask the analyzer for a caption and visual attributes, `None` on failure
+async fn describe(
+    analyzer: &dyn ImageAnalyzer,
+    prompts: &PromptRegistry,
+    metrics: &RwLock<StructuredOutputMetrics>,
+    image: &[u8],
+) -> Option<ImageDescription> {
+    let prompt = prompts.render(names::DESCRIBE_IMAGE, &[]).ok()?;
+    let generator = ImagePrompt { analyzer, image };
+    match generate_structured(&generator, prompts, &prompt, metrics).await {
+        Ok(description) => Some(description),
+        Err(e) => {
+            log::warn!("⚠️ Image description failed: {}", e);
+            None
+        }
+    }
+}
+
+/// Image analyzer bound to one image, usable where a text generator is expected
+struct ImagePrompt<'a> {
+    analyzer: &'a dyn ImageAnalyzer,
+    image: &'a [u8],
+}
+
+#[async_trait]
+impl TextGenerator for ImagePrompt<'_> {
+    async fn generate(&self, prompt: &str) -> NodeSpaceResult<String> {
+        self.analyzer.describe_image(self.image, prompt).await
+    }
+}
+
+/// Whether a query is looking for images, where visual attributes matter
+///
+/// Whole words only, so "topics" or "imagine" do not count.
+pub(crate) fn mentions_images(query: &str) -> bool {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| IMAGE_WORDS.contains(&word.to_lowercase().as_str()))
+}
+
+/// Location entity for where an image node's photo was taken
+pub(crate) fn gps_location(node: &Node) -> Option<String> {
+    let value = node.metadata.as_ref()?.get(IMAGE_METADATA_KEY)?;
+    let metadata: ImageMetadata = serde_json::from_value(value.clone()).ok()?;
+    Some(metadata.exif?.gps?.label())
+}
+
+/// Visual attributes stored on an image node
+pub fn stored_visual_attributes(node: &Node) -> Option<VisualAttributes> {
+    let value = node.metadata.as_ref()?.get(VISUAL_METADATA_KEY)?;
+    serde_json::from_value(value.clone()).ok()
+}
+
+/// Distinct lowercase words of all attributes
+fn visual_terms(visual: &VisualAttributes) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    let values = visual
+        .colors
+        .iter()
+        .chain(&visual.objects)
+        .chain(&visual.scene_types)
+        .chain(&visual.people_descriptions);
+    for word in values.flat_map(|value| value.split(|c: char| !c.is_alphanumeric())) {
+        let word = word.to_lowercase();
+        let word = word
+            .strip_suffix('s')
+            .filter(|w| w.len() > 2)
+            .unwrap_or(&word);
+        if word.len() > 1 && !VISUAL_STOPWORDS.contains(&word) && !terms.iter().any(|t| t == word) {
+            terms.push(word.to_string());
+        }
+    }
+    terms
+}
+
+/// Format and size from a PNG, JPEG, GIF or WebP header
+pub fn image_dimensions(image: &[u8]) -> Option<ImageDimensions> {
+    let be16 = |at: usize| Some(u16::from_be_bytes(image.get(at..at + 2)?.try_into().ok()?));
+    let le16 = |at: usize| Some(u16::from_le_bytes(image.get(at..at + 2)?.try_into().ok()?));
+    let be32 = |at: usize| Some(u32::from_be_bytes(image.get(at..at + 4)?.try_into().ok()?));
+    let le24 = |at: usize| {
+        let bytes = image.get(at..at + 3)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+    };
+    let sized = |format, width: u32, height: u32| {
+        Some(ImageDimensions {
+            format,
+            width,
+            height,
+        })
+    };
+
+    if image.starts_with(b"\x89PNG\r\n\x1a\n") && image.get(12..16) == Some(b"IHDR") {
+        return sized("png", be32(16)?, be32(20)?);
+    }
+    if image.starts_with(b"GIF87a") || image.starts_with(b"GIF89a") {
+        return sized("gif", le16(6)? as u32, le16(8)? as u32);
+    }
+    if image.starts_with(b"RIFF") && image.get(8..12) == Some(b"WEBP") {
+        return match image.get(12..16)? {
+            b"VP8X" => sized("webp", le24(24)? + 1, le24(27)? + 1),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(image.get(21..25)?.try_into().ok()?);
+                sized("webp", (bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1)
+            }
+            b"VP8 " => sized(
+                "webp",
+                (le16(26)? & 0x3FFF) as u32,
+                (le16(28)? & 0x3FFF) as u32,
+            ),
+            _ => None,
+        };
+    }
+    if image.starts_with(&[0xFF, 0xD8]) {
+        let mut pos = 2;
+        while image.get(pos) == Some(&0xFF) {
+            let marker = *image.get(pos + 1)?;
+            let length = be16(pos + 2)? as usize;
+            // Start-of-frame markers, excluding DHT, JPG and DAC
+            if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+                return sized("jpeg", be16(pos + 7)? as u32, be16(pos + 5)? as u32);
+            }
+            pos += 2 + length;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exif::tests::{fixture, jpeg_with_exif, tiff_with_dates};
+
+    fn visual(colors: &[&str], objects: &[&str]) -> VisualAttributes {
+        VisualAttributes {
+            colors: colors.iter().map(|c| c.to_string()).collect(),
+            objects: objects.iter().map(|o| o.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn node_id(id: &str) -> NodeId {
+        NodeId::from_string(id.to_string())
+    }
+
+    #[test]
+    fn test_dimensions_from_image_headers() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend(640u32.to_be_bytes());
+        png.extend(480u32.to_be_bytes());
+        assert_eq!(
+            image_dimensions(&png),
+            Some(ImageDimensions {
+                format: "png",
+                width: 640,
+                height: 480
+            })
+        );
+
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend([0x20, 0x03, 0x58, 0x02]);
+        assert_eq!(
+            image_dimensions(&gif).map(|d| (d.width, d.height)),
+            Some((800, 600))
+        );
+
+        // EXIF segments before the frame header are skipped
+        let mut jpeg = jpeg_with_exif(&tiff_with_dates(&[], &[]));
+        jpeg.truncate(jpeg.len() - 6);
+        jpeg.extend([0xFF, 0xC0, 0x00, 0x11, 0x08, 0x0B, 0xB8, 0x0F, 0xA0]);
+        let dimensions = image_dimensions(&jpeg).unwrap();
+        assert_eq!(
+            (dimensions.format, dimensions.width, dimensions.height),
+            ("jpeg", 4000, 3000)
+        );
+
+        assert_eq!(image_dimensions(b"not an image"), None);
+    }
+
+    #[test]
+    fn test_visual_search_ranks_images_by_matched_attributes() {
+        let mut index = VisualIndex::new();
+        index.index_node(
+            &node_id("red-shirt"),
+            &visual(&["red"], &["shirt", "bicycle"]),
+        );
+        index.index_node(&node_id("red-car"), &visual(&["Red"], &["car"]));
+        index.index_node(&node_id("blue-shirts"), &visual(&["blue"], &["Shirts"]));
+
+        let results = index.search(&visual(&["red"], &["shirt"]));
+        assert_eq!(results[0], (node_id("red-shirt"), 1.0));
+        assert_eq!(results[1..].len(), 2);
+        assert!(results[1..].iter().all(|(_, share)| *share == 0.5));
+
+        // A model may return the whole phrase as one object
+        let phrase = index.search(&visual(&[], &["the red shirt"]));
+        assert_eq!(phrase[0], (node_id("red-shirt"), 1.0));
+
+        assert!(index.search(&VisualAttributes::default()).is_empty());
+    }
+
+    #[test]
+    fn test_gps_position_of_a_photo_is_its_location_entity() {
+        let photo = fixture("tokyo_offset_gps.jpg");
+        let metadata = ImageMetadata {
+            exif: read_exif(&photo),
+            byte_size: photo.len(),
+            ..Default::default()
+        };
+        let mut node = Node::new("image".to_string(), serde_json::json!("Shibuya crossing"));
+        node.metadata = Some(serde_json::json!({ IMAGE_METADATA_KEY: metadata }));
+        assert_eq!(gps_location(&node), Some("35.6588, 139.7011".to_string()));
+
+        // The fixture decodes as an 8x8 baseline JPEG
+        let dimensions = image_dimensions(&photo).unwrap();
+        assert_eq!((dimensions.width, dimensions.height), (8, 8));
+
+        let without_gps = ImageMetadata {
+            exif: read_exif(&fixture("local_time_only.jpg")),
+            ..Default::default()
+        };
+        node.metadata = Some(serde_json::json!({ IMAGE_METADATA_KEY: without_gps }));
+        assert_eq!(gps_location(&node), None);
+    }
+
+    #[tokio::test]
+    async fn test_analyzed_image_gets_a_caption_and_indexed_attributes() {
+        use crate::ollama::tests::{accept_request, local_server};
+        use base64::Engine as _;
+        use tokio::io::AsyncWriteExt;
+
+        let (listener, client) = local_server().await;
+        let client = client.with_multimodal_model("llava:7b");
+        let server = tokio::spawn(async move {
+            let (mut socket, body) = accept_request(&listener).await;
+            let answer = serde_json::json!({
+                "response": "```json\n{\"caption\": \"A red kite over the beach\", \
+                             \"colors\": [\"red\"], \"objects\": [\"kite\"], \
+                             \"scene_types\": [\"beach\"]}\n```",
+                "done": true,
+            })
+            .to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                answer.len(),
+                answer
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            body
+        });
+
+        let image = fixture("gps_utc_stamp.jpg");
+        let metrics = RwLock::new(StructuredOutputMetrics::default());
+        let description = describe(&client, &PromptRegistry::default(), &metrics, &image)
+            .await
+            .unwrap();
+        assert_eq!(description.caption, "A red kite over the beach");
+
+        let mut index = VisualIndex::new();
+        index.index_node(&node_id("kite"), &description.visual);
+        assert_eq!(
+            index.search(&visual(&["red"], &["kite"])),
+            vec![(node_id("kite"), 1.0)]
+        );
+
+        let body = server.await.unwrap();
+        assert_eq!(body["model"], "llava:7b");
+        assert_eq!(body["stream"], false);
+        let sent = base64::engine::general_purpose::STANDARD
+            .decode(body["images"][0].as_str().unwrap())
+            .unwrap();
+        assert_eq!(sent, image);
+    }
+
+    #[test]
+    fn test_descriptions_without_a_caption_are_rejected() {
+        use crate::structured_output::parse_json;
+        let description: ImageDescription =
+            parse_json("{\"caption\": \"A dog on a beach\", \"colors\": [\"blue\"]}").unwrap();
+        assert_eq!(description.caption, "A dog on a beach");
+        assert_eq!(description.visual, visual(&["blue"], &[]));
+
+        assert!(parse_json::<ImageDescription>("{\"description\": \"A dog\"}").is_err());
+        assert!(parse_json::<ImageDescription>("{\"caption\": [\"A dog\"]}").is_err());
+        assert!(parse_json::<VisualAttributes>("{\"caption\": \"A dog\"}").is_err());
+    }
+
+    #[test]
+    fn test_images_are_described_only_with_a_configured_model() {
+        let mut config = crate::NodeSpaceConfig::default().model_config;
+        assert!(crate::default_image_analyzer(&config).is_none());
+
+        config.multimodal_model = Some("llava:7b".to_string());
+        assert!(crate::default_image_analyzer(&config).is_some());
+    }
+
+    #[test]
+    fn test_visual_index_replaces_and_removes_nodes() {
+        let mut index = VisualIndex::new();
+        index.index_node(&node_id("photo"), &visual(&["red"], &["shirt"]));
+        index.index_node(&node_id("photo"), &visual(&["green"], &["tree"]));
+
+        assert!(index.search(&visual(&["red"], &[])).is_empty());
+        assert_eq!(index.search(&visual(&["green"], &[])).len(), 1);
+        assert_eq!(index.len(), 1);
+
+        index.remove_node(&node_id("photo"));
+        assert!(index.is_empty());
+        assert!(index.search(&visual(&["green"], &[])).is_empty());
+    }
+
+    #[test]
+    fn test_saved_visual_index_restores_search_and_removal() {
+        let mut index = VisualIndex::new();
+        index.index_node(&node_id("red-shirt"), &visual(&["red"], &["shirt"]));
+        index.index_node(&node_id("red-car"), &visual(&["red"], &["car"]));
+
+        let mut restored = VisualIndex::from_saved(index.to_saved()).unwrap();
+        let wanted = visual(&["red"], &["shirt"]);
+        assert_eq!(restored.search(&wanted), index.search(&wanted));
+
+        restored.remove_node(&node_id("red-shirt"));
+        assert_eq!(restored.search(&wanted), vec![(node_id("red-car"), 0.5)]);
+        assert!(VisualIndex::from_saved(serde_json::json!(["red"])).is_none());
+    }
+
+    #[test]
+    fn test_image_queries_are_recognized_by_whole_words() {
+        assert!(mentions_images("the photo with the red shirt"));
+        assert!(mentions_images("Pics from the beach"));
+        assert!(mentions_images("screenshots of the dashboard"));
+
+        for query in [
+            "meeting topics",
+            "picnic plans",
+            "an epic day",
+            "imagine that",
+        ] {
+            assert!(!mentions_images(query), "{}", query);
+        }
+    }
+}
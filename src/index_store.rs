@@ -0,0 +1,438 @@
+//! Persistence for indexes derived from node metadata
+//!
+//! The entity and visual indexes are built from what write-time extraction
+//! stores on each node. Rather than rescanning the node table on startup, they
+//! are saved through an `IndexStore` kept outside the node table, so they never
+//! appear in search results or scans. Indexes load on first use: from the store
+//! when a saved copy exists, otherwise by one rebuild from node metadata. Saves
+//! are debounced, so a burst of writes costs one save.
+//!
+//! A saved copy is only trusted if it has the current format and was written
+//! by `shutdown`. Loading a copy marks it in use, so after a crash the next
+//! start rebuilds instead of reading an index that missed the last writes.
+
+use async_trait::async_trait;
+use nodespace_core_types::{NodeSpaceError, NodeSpaceResult};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, OnceCell, RwLock};
+
+/// Time a changed index waits for further changes before it is saved
+pub const INDEX_SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// Format of saved index copies; copies in another format are rebuilt
+pub const INDEX_FORMAT_VERSION: u32 = 1;
+
+/// Storage for serialized indexes, keyed by index name
+#[async_trait]
+pub trait IndexStore: Send + Sync {
+    /// Saved copy of the named index, `None` when it was never saved
+    async fn load_index(&self, name: &str) -> NodeSpaceResult<Option<serde_json::Value>>;
+
+    async fn save_index(&self, name: &str, index: &serde_json::Value) -> NodeSpaceResult<()>;
+}
+
+/// Index store keeping one JSON file per index in a directory
+#[derive(Debug, Clone)]
+pub struct FileIndexStore {
+    dir: PathBuf,
+}
+
+impl FileIndexStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Store next to a database directory, `None` for an in-memory database
+    pub fn beside_database(database_path: &str) -> Option<Self> {
+        if database_path == "memory" || database_path.is_empty() {
+            return None;
+        }
+        let database_path = database_path.trim_end_matches(['/', '\\']);
+        Some(Self::new(format!("{}.indexes", database_path)))
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+}
+
+#[async_trait]
+impl IndexStore for FileIndexStore {
+    async fn load_index(&self, name: &str) -> NodeSpaceResult<Option<serde_json::Value>> {
+        let data = match tokio::fs::read(self.path(name)).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(index_error(name, e)),
+        };
+        serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|e| index_error(name, e))
+    }
+
+    async fn save_index(&self, name: &str, index: &serde_json::Value) -> NodeSpaceResult<()> {
+        let data = serde_json::to_vec(index).map_err(|e| index_error(name, e))?;
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| index_error(name, e))?;
+
+        // Written beside the saved copy and renamed, so a crash never leaves half a file
+        let path = self.path(name);
+        let partial = path.with_extension("json.tmp");
+        tokio::fs::write(&partial, data)
+            .await
+            .map_err(|e| index_error(name, e))?;
+        tokio::fs::rename(&partial, &path)
+            .await
+            .map_err(|e| index_error(name, e))
+    }
+}
+
+fn index_error(name: &str, e: impl std::fmt::Display) -> NodeSpaceError {
+    NodeSpaceError::InternalError {
+        message: format!("Index '{}' could not be read or saved: {}", name, e),
+        service: "core-logic".to_string(),
+    }
+}
+
+/// Saved index with the markers deciding whether it can be trusted
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedCopy {
+    format: u32,
+    /// Written at shutdown; `false` while a service has the index in use
+    clean: bool,
+    index: serde_json::Value,
+}
+
+/// Index that can be saved to and restored from an `IndexStore`
+pub trait SavedIndex: Default + Send + Sync + 'static {
+    fn to_saved(&self) -> serde_json::Value;
+
+    /// Index from a saved copy, `None` when the copy is unreadable
+    fn from_saved(saved: serde_json::Value) -> Option<Self>;
+}
+
+/// In-memory index that loads on first use and saves changes in the background
+pub(crate) struct PersistedIndex<T> {
+    name: &'static str,
+    index: Arc<RwLock<T>>,
+    loaded: OnceCell<()>,
+    store: Option<Arc<dyn IndexStore>>,
+    save_delay: Duration,
+    save_pending: Arc<AtomicBool>,
+    // Held while saving so a slower save cannot overwrite a newer snapshot
+    saving: Arc<Mutex<()>>,
+}
+
+impl<T: SavedIndex> PersistedIndex<T> {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            index: Arc::new(RwLock::new(T::default())),
+            loaded: OnceCell::new(),
+            store: None,
+            save_delay: INDEX_SAVE_DELAY,
+            save_pending: Arc::new(AtomicBool::new(false)),
+            saving: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn with_store(mut self, store: Arc<dyn IndexStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with_save_delay(mut self, delay: Duration) -> Self {
+        self.save_delay = delay;
+        self
+    }
+
+    /// The index, loaded from the store or built by `rebuild` on first use
+    ///
+    /// Saved copies in an old format or not saved at shutdown are rebuilt. A
+    /// failed load is retried on the next call.
+    pub async fn load_with<F, Fut>(&self, rebuild: F) -> NodeSpaceResult<&RwLock<T>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = NodeSpaceResult<T>>,
+    {
+        self.loaded
+            .get_or_try_init(|| async {
+                if let Some(index) = self.load_saved().await {
+                    *self.index.write().await = index;
+                    return Ok(());
+                }
+                let index = rebuild().await?;
+                *self.index.write().await = index;
+                self.schedule_save();
+                Ok::<(), NodeSpaceError>(())
+            })
+            .await?;
+        Ok(&self.index)
+    }
+
+    /// Replace the index contents and save them
+    pub async fn replace(&self, index: T) {
+        *self.index.write().await = index;
+        // A rebuilt index is current even if it was never loaded
+        let _ = self.loaded.set(());
+        self.schedule_save();
+    }
+
+    async fn load_saved(&self) -> Option<T> {
+        let store = self.store.as_ref()?;
+        let saved = match store.load_index(self.name).await {
+            Ok(saved) => saved?,
+            Err(e) => {
+                log::warn!("⚠️ Could not load {}, rebuilding it: {}", self.name, e);
+                return None;
+            }
+        };
+
+        let copy = match serde_json::from_value::<SavedCopy>(saved) {
+            Ok(copy) if copy.format != INDEX_FORMAT_VERSION => {
+                log::info!(
+                    "🔄 Saved {} has format {}, rebuilding it",
+                    self.name,
+                    copy.format
+                );
+                return None;
+            }
+            Ok(copy) if !copy.clean => {
+                log::warn!(
+                    "⚠️ Saved {} was not closed at shutdown, rebuilding it",
+                    self.name
+                );
+                return None;
+            }
+            Ok(copy) => copy,
+            Err(_) => {
+                log::warn!("⚠️ Saved {} is unreadable, rebuilding it", self.name);
+                return None;
+            }
+        };
+        let Some(index) = T::from_saved(copy.index.clone()) else {
+            log::warn!("⚠️ Saved {} is unreadable, rebuilding it", self.name);
+            return None;
+        };
+
+        // Until shutdown closes it again, a crash leaves this copy rebuildable
+        let in_use = SavedCopy {
+            clean: false,
+            ..copy
+        };
+        match serde_json::to_value(&in_use) {
+            Ok(in_use) => {
+                if let Err(e) = store.save_index(self.name, &in_use).await {
+                    log::warn!("⚠️ Could not mark {} in use: {}", self.name, e);
+                }
+            }
+            Err(e) => log::warn!("⚠️ Could not mark {} in use: {}", self.name, e),
+        }
+        Some(index)
+    }
+
+    /// Save the index after `INDEX_SAVE_DELAY`, once for all changes made until then
+    pub fn schedule_save(&self) {
+        let Some(store) = self.store.clone() else {
+            return;
+        };
+        if self.save_pending.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let (name, delay) = (self.name, self.save_delay);
+        let index = self.index.clone();
+        let pending = self.save_pending.clone();
+        let saving = self.saving.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _saving = saving.lock().await;
+            // Changes from here on schedule another save; a flush may have saved already
+            if pending.swap(false, Ordering::SeqCst) {
+                save(store.as_ref(), name, &index, false).await;
+            }
+        });
+    }
+
+    /// Save the index and mark the saved copy clean, for shutdown
+    ///
+    /// An index that was never loaded is left as saved.
+    pub async fn flush(&self) {
+        let Some(store) = self.store.as_deref() else {
+            return;
+        };
+        if self.loaded.get().is_none() {
+            return;
+        }
+        let _saving = self.saving.lock().await;
+        self.save_pending.store(false, Ordering::SeqCst);
+        save(store, self.name, &self.index, true).await;
+    }
+}
+
+async fn save<T: SavedIndex>(store: &dyn IndexStore, name: &str, index: &RwLock<T>, clean: bool) {
+    let copy = SavedCopy {
+        format: INDEX_FORMAT_VERSION,
+        clean,
+        index: index.read().await.to_saved(),
+    };
+    let result = match serde_json::to_value(&copy) {
+        Ok(copy) => store.save_index(name, &copy).await,
+        Err(e) => Err(index_error(name, e)),
+    };
+    match result {
+        Ok(()) => log::debug!("💾 Saved {}", name),
+        Err(e) => log::warn!("⚠️ Could not save {}: {}", name, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicUsize;
+
+    #[derive(Default)]
+    struct MemoryIndexStore {
+        saved: std::sync::Mutex<HashMap<String, serde_json::Value>>,
+        saves: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl IndexStore for MemoryIndexStore {
+        async fn load_index(&self, name: &str) -> NodeSpaceResult<Option<serde_json::Value>> {
+            Ok(self.saved.lock().unwrap().get(name).cloned())
+        }
+
+        async fn save_index(&self, name: &str, index: &serde_json::Value) -> NodeSpaceResult<()> {
+            self.saves.fetch_add(1, Ordering::SeqCst);
+            self.saved
+                .lock()
+                .unwrap()
+                .insert(name.to_string(), index.clone());
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Words(Vec<String>);
+
+    impl SavedIndex for Words {
+        fn to_saved(&self) -> serde_json::Value {
+            serde_json::json!(self.0)
+        }
+
+        fn from_saved(saved: serde_json::Value) -> Option<Self> {
+            serde_json::from_value(saved).ok().map(Words)
+        }
+    }
+
+    /// Words of the index, rebuilt as `[rebuilt]` if it has not been loaded yet
+    async fn words(index: &PersistedIndex<Words>, rebuilt: &str) -> Vec<String> {
+        let rebuilt = vec![rebuilt.to_string()];
+        let index = index
+            .load_with(|| async { Ok(Words(rebuilt)) })
+            .await
+            .unwrap();
+        let words = index.read().await.0.clone();
+        words
+    }
+
+    #[tokio::test]
+    async fn test_index_is_rebuilt_once_then_loaded_from_the_store() {
+        let store = Arc::new(MemoryIndexStore::default());
+        let index = PersistedIndex::<Words>::new("words").with_store(store.clone());
+
+        assert_eq!(words(&index, "first").await, vec!["first"]);
+        assert_eq!(words(&index, "second").await, vec!["first"]);
+        index.flush().await;
+        assert_eq!(store.saves.load(Ordering::SeqCst), 1);
+
+        // A new service instance reads the saved copy instead of rebuilding
+        let reopened = PersistedIndex::<Words>::new("words").with_store(store.clone());
+        assert_eq!(words(&reopened, "third").await, vec!["first"]);
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_saved_once_per_burst() {
+        let store = Arc::new(MemoryIndexStore::default());
+        let index = PersistedIndex::<Words>::new("words")
+            .with_store(store.clone())
+            .with_save_delay(Duration::from_millis(20));
+        words(&index, "rebuilt").await;
+
+        for word in ["a", "b", "c"] {
+            index.index.write().await.0.push(word.to_string());
+            index.schedule_save();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // The rebuild and the three changes were all saved by one delayed save
+        assert_eq!(store.saves.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            store.load_index("words").await.unwrap(),
+            Some(serde_json::json!({
+                "format": INDEX_FORMAT_VERSION,
+                "clean": false,
+                "index": ["rebuilt", "a", "b", "c"],
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_copies_not_closed_at_shutdown_are_rebuilt() {
+        let store = Arc::new(MemoryIndexStore::default());
+        let index = PersistedIndex::<Words>::new("words")
+            .with_store(store.clone())
+            .with_save_delay(Duration::from_millis(20));
+        words(&index, "first").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Saved while in use, then the process died before shutdown
+        let crashed = PersistedIndex::<Words>::new("words").with_store(store.clone());
+        assert_eq!(words(&crashed, "second").await, vec!["second"]);
+        crashed.flush().await;
+
+        // Loading the closed copy marks it in use until the next shutdown
+        let reopened = PersistedIndex::<Words>::new("words").with_store(store.clone());
+        assert_eq!(words(&reopened, "third").await, vec!["second"]);
+        let after_crash = PersistedIndex::<Words>::new("words").with_store(store.clone());
+        assert_eq!(words(&after_crash, "fourth").await, vec!["fourth"]);
+    }
+
+    #[tokio::test]
+    async fn test_copies_in_another_format_are_rebuilt() {
+        let store = Arc::new(MemoryIndexStore::default());
+        store
+            .save_index("words", &serde_json::json!(["unversioned"]))
+            .await
+            .unwrap();
+        let index = PersistedIndex::<Words>::new("words").with_store(store.clone());
+        assert_eq!(words(&index, "rebuilt").await, vec!["rebuilt"]);
+
+        let old_format = serde_json::json!({
+            "format": INDEX_FORMAT_VERSION + 1,
+            "clean": true,
+            "index": ["other"],
+        });
+        store.save_index("words", &old_format).await.unwrap();
+        let index = PersistedIndex::<Words>::new("words").with_store(store.clone());
+        assert_eq!(words(&index, "rebuilt").await, vec!["rebuilt"]);
+    }
+
+    #[test]
+    fn test_file_store_sits_beside_the_database() {
+        assert!(FileIndexStore::beside_database("memory").is_none());
+        let store = FileIndexStore::beside_database("../data/lance_db/development.db/").unwrap();
+        assert_eq!(
+            store.path("entity_index"),
+            PathBuf::from("../data/lance_db/development.db.indexes/entity_index.json")
+        );
+    }
+}
@@ -3,6 +3,8 @@ use chrono::{DateTime, NaiveDate, Utc};
 use nodespace_core_types::{
     DatabaseError, Node, NodeContext, NodeId, NodeSpaceError, NodeSpaceResult, ProcessingError, ValidationError,
 };
+use context_expansion::ExpandedHit;
+use index_store::PersistedIndex;
 use nodespace_data_store::NodeType;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -16,12 +18,72 @@ use tokio::sync::RwLock;
 pub mod desktop_integration;
 pub use desktop_integration::{EnhancedQueryResponse, NodeSource};
 
+// Pluggable RAG stages
+pub mod citations;
+pub mod confidence;
+pub mod context_budget;
+pub mod context_expansion;
+pub mod conversation;
+pub mod date_range;
+pub mod digest;
+pub mod entities;
+pub mod event_anchors;
+pub mod exif;
+pub mod faithfulness;
+pub mod generation;
+pub mod generation_options;
+pub mod images;
+pub mod index_store;
+pub mod ollama;
+pub mod periods;
+pub mod pipeline;
+pub mod prompts;
+pub mod related_queries;
+pub mod reranker;
+pub mod scoped_query;
+pub mod streaming;
+pub mod structured_output;
+pub mod summarization;
+pub mod temporal_parser;
+pub mod temporal_search;
+#[cfg(test)]
+mod test_support;
+pub mod timezone;
+pub use citations::AnswerCitation;
+pub use confidence::ConfidenceBreakdown;
+pub use context_budget::{
+    ContextBudgetReport, ContextPlan, HeuristicTokenCounter, TokenBudget, TokenCounter,
+};
+pub use context_expansion::HierarchyContextOptions;
+pub use conversation::{ChatRole, ConversationSession, ConversationTurn};
+pub use date_range::{CalendarDay, CalendarSummary, NodeCountSource};
+pub use digest::{Digest, DigestItem, DigestMode, DigestOptions, DigestTopic};
+pub use entities::{EntityExtraction, EntityIndex, EntityKind, IndexedEntity};
+pub use event_anchors::{AnchoredSearchResult, EventAnchor, EventSearch};
+pub use exif::{ExifData, GpsPosition};
+pub use faithfulness::{FaithfulnessMode, FaithfulnessOptions, FaithfulnessReport};
+pub use generation::TextGenerator;
+pub use generation_options::{GenerationOptions, ModelLimits, ResolvedGeneration};
+pub use images::{ImageAnalyzer, ImageMetadata, ImageSource, IngestedImage, VisualIndex};
+pub use index_store::{FileIndexStore, IndexStore};
+pub use ollama::{OllamaClient, DEFAULT_OLLAMA_MODEL, DEFAULT_OLLAMA_URL};
+pub use periods::{Period, PeriodKind, PeriodOverview};
+pub use pipeline::{PipelineStage, PromptStyle, RagPipeline, Retrieval, StageTiming};
+pub use prompts::{PromptConfig, PromptRegistry, PromptTemplate, PromptVersion};
+pub use reranker::{DeterministicReranker, LlmPointwiseReranker, Reranker};
+pub use scoped_query::QueryScope;
+pub use streaming::{QueryStreamEvent, StreamingTextGenerator};
+pub use structured_output::StructuredOutputMetrics;
+pub use summarization::{HierarchicalSummary, SummarizationOptions};
+pub use temporal_parser::TemporalParser;
+pub use timezone::UserTimezone;
+
 // Import traits from their respective repositories
 pub use nodespace_data_store::DataStore;
 pub use nodespace_nlp_engine::NLPEngine;
 
 // Import enhanced text generation types
-use nodespace_nlp_engine::{RAGContext, TextGenerationRequest};
+use nodespace_nlp_engine::TextGenerationRequest;
 
 // Import additional types for embedding generation bridge
 use nodespace_data_store::DataStoreError;
@@ -739,6 +801,64 @@ pub mod constants {
     pub const DEFAULT_EMBEDDING_DIMENSION: usize = 768;
     /// Reserved space for prompt structure in context window
     pub const PROMPT_STRUCTURE_RESERVE: usize = 200;
+    /// Default answer token limit for single-shot RAG queries
+    pub const DEFAULT_RAG_MAX_TOKENS: usize = 500;
+    /// Default answer token limit for desktop AI responses
+    pub const DEFAULT_AI_RESPONSE_MAX_TOKENS: usize = 2000;
+    /// Search results retrieved for AI responses without caller-provided context
+    pub const DEFAULT_AI_RESPONSE_SEARCH_LIMIT: usize = 5;
+    /// Global search candidates filtered down to a query scope
+    pub const DEFAULT_SCOPED_SEARCH_CANDIDATES: usize = 50;
+    /// Default length of a node or merge summary in map-reduce summarization
+    pub const DEFAULT_SUMMARY_MAX_TOKENS: usize = 300;
+    /// Longest date range accepted by `summarize_date_range`, in days
+    pub const MAX_SUMMARY_RANGE_DAYS: i64 = 366;
+    /// Longest date range accepted by range queries over date nodes, in days
+    pub const MAX_DATE_RANGE_DAYS: i64 = 366;
+    /// Base score of nodes found under a date a temporal reference covers
+    pub const TEMPORAL_MATCH_SCORE: f32 = 0.9;
+    /// Days from a fuzzy reference's center at which a date's score halves
+    pub const TEMPORAL_DECAY_HALF_LIFE_DAYS: f32 = 7.0;
+    /// Days either side of an event's anchor date covered by event-anchored search
+    pub const EVENT_WINDOW_DAYS: i64 = 3;
+    /// Most anchor dates an event reference resolves to
+    pub const MAX_EVENT_ANCHORS: usize = 3;
+    /// Longest date range accepted by `generate_digest`, in days
+    pub const MAX_DIGEST_RANGE_DAYS: i64 = 31;
+    /// Default number of key topics reported in a digest
+    pub const DEFAULT_DIGEST_TOPICS: usize = 8;
+    /// Fallback context window of the text model when `ModelConfig` sets none
+    ///
+    /// Matches the default text model; configure `context_length` for others.
+    pub const DEFAULT_MODEL_CONTEXT_WINDOW: usize = 8192;
+    /// Fallback answer length limit when `ModelConfig` sets no `max_output_tokens`
+    pub const DEFAULT_MODEL_MAX_OUTPUT_TOKENS: usize = 4000;
+    /// Lowest accepted generation temperature
+    pub const MIN_TEMPERATURE: f32 = 0.0;
+    /// Highest accepted generation temperature
+    pub const MAX_TEMPERATURE: f32 = 2.0;
+    /// Default number of retrieval candidates passed to the reranker
+    pub const DEFAULT_RERANK_TOP_N: usize = 10;
+    /// Default weight of the original retrieval score when blending reranker scores
+    pub const DEFAULT_RERANK_RETRIEVAL_WEIGHT: f32 = 0.3;
+    /// Maximum characters of candidate content sent to the LLM reranker
+    pub const DEFAULT_RERANK_MAX_CANDIDATE_CHARS: usize = 1000;
+    /// Default number of LLM reranker scoring calls in flight at once
+    pub const DEFAULT_RERANK_CONCURRENCY: usize = 4;
+    /// Weight of retrieval strength in grounded answer confidence
+    pub const CONFIDENCE_RETRIEVAL_WEIGHT: f32 = 0.4;
+    /// Weight of answer-to-source support in grounded answer confidence
+    pub const CONFIDENCE_GROUNDING_WEIGHT: f32 = 0.35;
+    /// Weight of citation coverage in grounded answer confidence
+    pub const CONFIDENCE_CITATION_WEIGHT: f32 = 0.25;
+    /// Default share of answer sentences that must be supported by the context
+    pub const DEFAULT_MIN_SUPPORTED_RATIO: f32 = 0.75;
+    /// Maximum number of related query suggestions per answer
+    pub const DEFAULT_RELATED_QUERY_LIMIT: usize = 3;
+    /// Minimum search score for a related query to count as having matching notes
+    pub const MIN_RELATED_QUERY_SCORE: f32 = 0.3;
+    /// Default number of prior turns included in conversational prompts
+    pub const DEFAULT_CONVERSATION_HISTORY_TURNS: usize = 6;
 
     // Resource bounds for hierarchical operations
     /// Maximum recursion depth for hierarchical operations
@@ -760,6 +880,12 @@ pub struct NodeSpaceConfig {
     pub performance_config: PerformanceConfig,
     /// Offline operation settings
     pub offline_config: OfflineConfig,
+    /// Prompt template overrides
+    #[serde(default)]
+    pub prompt_config: PromptConfig,
+    /// User timezone for "today" and for placing new content on date nodes
+    #[serde(default)]
+    pub timezone: UserTimezone,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -772,6 +898,20 @@ pub struct ModelConfig {
     pub download_timeout: Option<u64>,
     /// Local model cache directory
     pub cache_dir: Option<String>,
+    /// Context window of the text model, in tokens
+    #[serde(default)]
+    pub context_length: Option<usize>,
+    /// Longest answer the text model generates, in tokens
+    #[serde(default)]
+    pub max_output_tokens: Option<usize>,
+    /// Ollama server describing images (default: local server)
+    ///
+    /// Images are described only when this or `multimodal_model` is set.
+    #[serde(default)]
+    pub ollama_url: Option<String>,
+    /// Multimodal model describing images (default: gemma3:12b)
+    #[serde(default)]
+    pub multimodal_model: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -780,7 +920,7 @@ pub struct PerformanceConfig {
     pub max_batch_size: Option<usize>,
     /// Context window size for text generation
     pub context_window: Option<usize>,
-    /// Temperature for text generation (0.0-1.0)
+    /// Temperature for text generation (0.0-2.0)
     pub temperature: Option<f32>,
     /// Maximum number of siblings to include in contextual embeddings
     pub max_siblings_context: Option<usize>,
@@ -806,6 +946,25 @@ pub enum OfflineFallback {
     Cache,
 }
 
+impl OfflineFallback {
+    /// Canned answer for a failed generation over `sources` retrieved nodes
+    ///
+    /// `OfflineFallback::Error` never produces one.
+    pub fn answer(&self, sources: usize) -> Option<String> {
+        match self {
+            OfflineFallback::Error => None,
+            OfflineFallback::Stub => Some("I apologize, but I'm currently unable to generate a response due to AI system limitations. Please try again later.".to_string()),
+            OfflineFallback::Cache => {
+                if sources == 0 {
+                    Some("I found no relevant information to answer your question.".to_string())
+                } else {
+                    Some(format!("I found {} related documents but cannot generate a detailed response at this time. Please review the source materials directly.", sources))
+                }
+            }
+        }
+    }
+}
+
 impl Default for NodeSpaceConfig {
     fn default() -> Self {
         Self {
@@ -814,6 +973,10 @@ impl Default for NodeSpaceConfig {
                 text_model: Some(constants::DEFAULT_TEXT_MODEL.to_string()),
                 download_timeout: Some(constants::DEFAULT_DOWNLOAD_TIMEOUT),
                 cache_dir: None, // Use system default
+                context_length: None,
+                max_output_tokens: None,
+                ollama_url: None,
+                multimodal_model: None,
             },
             performance_config: PerformanceConfig {
                 max_batch_size: Some(constants::DEFAULT_MAX_BATCH_SIZE),
@@ -826,6 +989,8 @@ impl Default for NodeSpaceConfig {
                 enable_offline: true,
                 offline_fallback: OfflineFallback::Cache,
             },
+            prompt_config: PromptConfig::default(),
+            timezone: UserTimezone::default(),
         }
     }
 }
@@ -849,6 +1014,19 @@ pub struct NodeSpaceService<D: DataStore + Send + Sync, N: NLPEngine + Send + Sy
     performance_monitor: monitoring::PerformanceMonitor,
     hierarchy_cache: Arc<RwLock<HierarchyCache>>,
     embedding_cache: Arc<RwLock<smart_embedding_cache::SmartEmbeddingCache>>,
+    reranker: Option<Arc<dyn Reranker>>,
+    token_counter: Arc<dyn TokenCounter>,
+    hierarchy_context: HierarchyContextOptions,
+    entailment_confidence: bool,
+    prompts: PromptRegistry,
+    model_limits: ModelLimits,
+    entity_index: PersistedIndex<EntityIndex>,
+    entity_extraction: EntityExtraction,
+    structured_output_metrics: Arc<RwLock<StructuredOutputMetrics>>,
+    image_analyzer: Option<Arc<dyn ImageAnalyzer>>,
+    visual_index: PersistedIndex<VisualIndex>,
+    streaming_generator: Option<Arc<dyn StreamingTextGenerator>>,
+    node_count_source: Option<Arc<dyn NodeCountSource>>,
 }
 
 impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
@@ -859,6 +1037,16 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
 
     /// Create a new NodeSpace service with custom configuration
     pub fn with_config(data_store: D, nlp_engine: N, config: NodeSpaceConfig) -> Self {
+        let model_limits = ModelLimits::from_config(&config.model_config);
+        let image_analyzer = default_image_analyzer(&config.model_config);
+        let prompts = PromptRegistry::from_config(&config.prompt_config).unwrap_or_else(|e| {
+            log::warn!(
+                "⚠️ Invalid prompt configuration, using built-in templates: {}",
+                e
+            );
+            PromptRegistry::default()
+        });
+
         Self {
             data_store,
             nlp_engine,
@@ -869,9 +1057,110 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
             embedding_cache: Arc::new(RwLock::new(
                 smart_embedding_cache::SmartEmbeddingCache::new(),
             )),
+            reranker: None,
+            token_counter: Arc::new(HeuristicTokenCounter),
+            hierarchy_context: HierarchyContextOptions::default(),
+            entailment_confidence: false,
+            prompts,
+            model_limits,
+            entity_index: PersistedIndex::new(entities::ENTITY_INDEX_NAME),
+            entity_extraction: EntityExtraction::default(),
+            structured_output_metrics: Arc::new(RwLock::new(StructuredOutputMetrics::default())),
+            image_analyzer,
+            visual_index: PersistedIndex::new(images::VISUAL_INDEX_NAME),
+            streaming_generator: None,
+            node_count_source: None,
         }
     }
 
+    /// Enable a second-stage reranker for retrieval results
+    pub fn with_reranker(mut self, reranker: Arc<dyn Reranker>) -> Self {
+        self.reranker = Some(reranker);
+        self
+    }
+
+    /// Use a model-specific token counter for context budgeting
+    pub fn with_token_counter(mut self, token_counter: Arc<dyn TokenCounter>) -> Self {
+        self.token_counter = token_counter;
+        self
+    }
+
+    /// Expand retrieved RAG context with ancestors, siblings and children
+    pub fn with_hierarchy_context(mut self, options: HierarchyContextOptions) -> Self {
+        self.hierarchy_context = options;
+        self
+    }
+
+    /// Ask the NLP engine to grade answer support when scoring confidence
+    ///
+    /// Costs one extra generation call per answer; lexical grounding is used otherwise.
+    pub fn with_entailment_confidence(mut self, enabled: bool) -> Self {
+        self.entailment_confidence = enabled;
+        self
+    }
+
+    /// Choose how entities are extracted from node content on write
+    ///
+    /// `Rules` avoids a generation call per write; `Disabled` leaves the entity
+    /// index empty so entity search falls back to text matching.
+    pub fn with_entity_extraction(mut self, mode: EntityExtraction) -> Self {
+        self.entity_extraction = mode;
+        self
+    }
+
+    /// Caption ingested images with this analyzer instead of the configured Ollama model
+    pub fn with_image_analyzer(mut self, analyzer: Arc<dyn ImageAnalyzer>) -> Self {
+        self.image_analyzer = Some(analyzer);
+        self
+    }
+
+    /// Stream answer tokens from this generator as they are produced
+    ///
+    /// Without one, streaming queries send the engine's whole answer as a
+    /// single chunk.
+    pub fn with_streaming_generator(mut self, generator: Arc<dyn StreamingTextGenerator>) -> Self {
+        self.streaming_generator = Some(generator);
+        self
+    }
+
+    /// Count nodes per date for calendar summaries without loading them
+    ///
+    /// The data store has no count query, so without one calendar summaries
+    /// load every node in the range to count it. Set one for calendar views.
+    pub fn with_node_count_source(mut self, counts: Arc<dyn NodeCountSource>) -> Self {
+        self.node_count_source = Some(counts);
+        self
+    }
+
+    /// Save derived indexes to this store instead of rebuilding them from every node
+    ///
+    /// Without one, the entity and visual indexes are rebuilt from node
+    /// metadata on first use.
+    pub fn with_index_store(mut self, store: Arc<dyn IndexStore>) -> Self {
+        self.entity_index = self.entity_index.with_store(store.clone());
+        self.visual_index = self.visual_index.with_store(store);
+        self
+    }
+
+    /// Use a custom prompt template registry
+    pub fn with_prompt_registry(mut self, prompts: PromptRegistry) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Prompt templates used by this service
+    pub fn prompt_registry(&self) -> &PromptRegistry {
+        &self.prompts
+    }
+
+    /// Limits of the text model, used to cap generation parameters
+    ///
+    /// Overrides the limits taken from `ModelConfig`.
+    pub fn with_model_limits(mut self, limits: ModelLimits) -> Self {
+        self.model_limits = limits;
+        self
+    }
+
     /// Get performance monitor for metrics access
     pub fn performance_monitor(&self) -> &monitoring::PerformanceMonitor {
         &self.performance_monitor
@@ -1258,7 +1547,12 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
 
     /// Generate embeddings with full hierarchical context and store node
     /// This is the enhanced version that includes complete ancestry for rich semantic search
-    async fn store_node_with_hierarchical_embedding(&self, node: Node) -> NodeSpaceResult<NodeId> {
+    async fn store_node_with_hierarchical_embedding(
+        &self,
+        mut node: Node,
+    ) -> NodeSpaceResult<NodeId> {
+        let entities = self.attach_entities(&mut node).await;
+
         // Generate the full hierarchical context
         let hierarchical_content = self.build_hierarchical_content(&node).await?;
 
@@ -1281,6 +1575,10 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
 
         log::info!("💾 Stored node {} with hierarchical embedding", node_id);
 
+        if let Some(entities) = entities {
+            self.index_entities(&node_id, &entities).await;
+        }
+
         Ok(node_id)
     }
 }
@@ -1319,7 +1617,7 @@ impl NodeSpaceService<nodespace_data_store::LanceDataStore, nodespace_nlp_engine
         // This eliminates the GPU usage during simple database lookups
         // Note: Embeddings can still be generated on-demand via the service layer
 
-        Ok(Self::new(data_store, nlp_engine))
+        Ok(Self::new(data_store, nlp_engine).with_database_index_store(database_path))
     }
 
     /// Factory method for development environment
@@ -1368,7 +1666,7 @@ impl NodeSpaceService<nodespace_data_store::LanceDataStore, nodespace_nlp_engine
         })?;
 
         // Create service WITHOUT calling initialize() - no GPU usage
-        Ok(Self::new(data_store, nlp_engine))
+        Ok(Self::new(data_store, nlp_engine).with_database_index_store(database_path))
     }
 
     /// Factory method with background NLP initialization
@@ -1400,7 +1698,8 @@ impl NodeSpaceService<nodespace_data_store::LanceDataStore, nodespace_nlp_engine
         })?;
 
         // Create service immediately wrapped in Arc
-        let service = Arc::new(Self::new(data_store, nlp_engine));
+        let service =
+            Arc::new(Self::new(data_store, nlp_engine).with_database_index_store(database_path));
 
         // Start background initialization (non-blocking)
         let service_clone = Arc::clone(&service);
@@ -1429,10 +1728,8 @@ impl NodeSpaceService<nodespace_data_store::LanceDataStore, nodespace_nlp_engine
         };
 
         // Store values for logging before creating config
-        let base_url = ollama_base_url
-            .unwrap_or("http://localhost:11434")
-            .to_string();
-        let model_name = ollama_model.unwrap_or("gemma3:12b").to_string();
+        let base_url = ollama_base_url.unwrap_or(DEFAULT_OLLAMA_URL).to_string();
+        let model_name = ollama_model.unwrap_or(DEFAULT_OLLAMA_MODEL).to_string();
 
         // Configure NLP engine with real Ollama integration
         let ollama_config = OllamaConfig {
@@ -1484,6 +1781,17 @@ impl NodeSpaceService<nodespace_data_store::LanceDataStore, nodespace_nlp_engine
             },
         };
 
+        // Stream answers and describe images straight from Ollama; the engine
+        // returns whole completions and takes no images
+        let ollama = OllamaClient::new(&base_url, &model_name)?
+            .with_multimodal_model(&nlp_config.models.ollama.multimodal_model);
+
+        // Cap generation at what the engine is configured to accept
+        let model_limits = ModelLimits {
+            context_window: nlp_config.models.text_generation.max_context_length,
+            max_output_tokens: nlp_config.models.ollama.max_tokens,
+        };
+
         // Initialize NLP engine with real Ollama configuration
         // FIXED: Create only one NLP engine instance to avoid dual GPU usage
         let nlp_engine = LocalNLPEngine::with_config(nlp_config);
@@ -1503,7 +1811,11 @@ impl NodeSpaceService<nodespace_data_store::LanceDataStore, nodespace_nlp_engine
         // The service layer will handle embedding generation explicitly when needed
 
         // Create service with real Ollama configuration
-        let service = Self::new(data_store, nlp_engine);
+        let service = Self::new(data_store, nlp_engine)
+            .with_database_index_store(database_path)
+            .with_model_limits(model_limits)
+            .with_image_analyzer(Arc::new(ollama.clone()))
+            .with_streaming_generator(Arc::new(ollama));
 
         // Initialize the service to load models and establish Ollama connection
         service.initialize().await?;
@@ -1514,6 +1826,14 @@ impl NodeSpaceService<nodespace_data_store::LanceDataStore, nodespace_nlp_engine
 
         Ok(service)
     }
+
+    /// Save derived indexes beside the database at `database_path`
+    fn with_database_index_store(self, database_path: &str) -> Self {
+        match FileIndexStore::beside_database(database_path) {
+            Some(store) => self.with_index_store(Arc::new(store)),
+            None => self,
+        }
+    }
 }
 
 impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
@@ -1615,6 +1935,8 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
 
     /// Graceful shutdown of the service
     pub async fn shutdown(&self) -> NodeSpaceResult<()> {
+        self.entity_index.flush().await;
+        self.visual_index.flush().await;
         let mut state = self.state.write().await;
         *state = ServiceState::Uninitialized;
         Ok(())
@@ -1655,6 +1977,40 @@ pub trait CoreLogic: Send + Sync {
         date: NaiveDate,
     ) -> NodeSpaceResult<HierarchicalNodes>;
 
+    /// Find an existing week, month or year node by its predictable ID
+    async fn find_period_node(&self, period: Period) -> NodeSpaceResult<Option<NodeId>>;
+
+    /// Ensure a week, month or year node exists, creating it if necessary
+    async fn ensure_period_node_exists(&self, period: Period) -> NodeSpaceResult<NodeId>;
+
+    /// Get the direct children of each date node from `start` to `end`, ordered by date
+    async fn get_nodes_for_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> NodeSpaceResult<Vec<(NaiveDate, Vec<Node>)>>;
+
+    /// Get hierarchical nodes for each date from `start` to `end`, ordered by date
+    async fn get_hierarchical_nodes_for_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> NodeSpaceResult<Vec<HierarchicalNodes>>;
+
+    /// Get per-day node counts from `start` to `end` without assembling hierarchies
+    ///
+    /// Loads every node in the range unless a `NodeCountSource` is configured.
+    async fn get_calendar_summary(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> NodeSpaceResult<CalendarSummary>;
+
+    /// Calendar summary of one month
+    ///
+    /// Loads every node in the month unless a `NodeCountSource` is configured.
+    async fn get_calendar_month(&self, year: i32, month: u32) -> NodeSpaceResult<CalendarSummary>;
+
     /// Search for nodes using semantic similarity
     async fn semantic_search(
         &self,
@@ -1833,8 +2189,11 @@ pub struct SearchResult {
     pub score: f32,
 }
 
-/// Cross-modal search entities extracted from queries
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Named entities extracted from queries and node content
+///
+/// Missing lists are empty, but an object with none of them is rejected.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "serde_json::Map<String, serde_json::Value>")]
 pub struct ExtractedEntities {
     pub people: Vec<String>,
     pub events: Vec<String>,
@@ -1842,25 +2201,50 @@ pub struct ExtractedEntities {
     pub locations: Vec<String>,
 }
 
+impl TryFrom<serde_json::Map<String, serde_json::Value>> for ExtractedEntities {
+    type Error = String;
+
+    fn try_from(object: serde_json::Map<String, serde_json::Value>) -> Result<Self, String> {
+        use structured_output::{require_any_field, string_list};
+        require_any_field(&object, &["people", "events", "objects", "locations"])?;
+        Ok(Self {
+            people: string_list(&object, "people")?,
+            events: string_list(&object, "events")?,
+            objects: string_list(&object, "objects")?,
+            locations: string_list(&object, "locations")?,
+        })
+    }
+}
+
 /// Temporal references extracted from queries
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TemporalReference {
     pub raw_text: String,
     pub parsed_date: Option<NaiveDate>,
     pub date_range: Option<(NaiveDate, NaiveDate)>,
     pub temporal_type: TemporalType,
+    /// Date an `Event` reference was resolved to
+    #[serde(default)]
+    pub anchor_date: Option<NaiveDate>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TemporalType {
-    Exact,    // "on June 15"
+    #[serde(alias = "exact")]
+    Exact, // "on June 15"
+    #[serde(alias = "relative")]
     Relative, // "yesterday", "last week"
-    Event,    // "during Claire's birthday"
-    Fuzzy,    // "around that time"
+    #[serde(alias = "event")]
+    Event, // "during Claire's birthday"
+    #[serde(alias = "fuzzy")]
+    Fuzzy, // "around that time"
 }
 
 /// Visual attributes extracted from queries
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Missing lists are empty, but an object with none of them is rejected.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "serde_json::Map<String, serde_json::Value>")]
 pub struct VisualAttributes {
     pub colors: Vec<String>,
     pub objects: Vec<String>,
@@ -1868,6 +2252,51 @@ pub struct VisualAttributes {
     pub people_descriptions: Vec<String>,
 }
 
+impl TryFrom<serde_json::Map<String, serde_json::Value>> for VisualAttributes {
+    type Error = String;
+
+    fn try_from(object: serde_json::Map<String, serde_json::Value>) -> Result<Self, String> {
+        structured_output::require_any_field(&object, Self::FIELDS)?;
+        Self::from_object(&object)
+    }
+}
+
+impl VisualAttributes {
+    const FIELDS: &'static [&'static str] =
+        &["colors", "objects", "scene_types", "people_descriptions"];
+
+    /// Attributes in a response object, empty where missing
+    pub(crate) fn from_object(
+        object: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Self, String> {
+        use structured_output::string_list;
+        Ok(Self {
+            colors: string_list(object, "colors")?,
+            objects: string_list(object, "objects")?,
+            scene_types: string_list(object, "scene_types")?,
+            people_descriptions: string_list(object, "people_descriptions")?,
+        })
+    }
+
+    /// Add attributes from `other` that are not already present
+    fn merge(&mut self, other: VisualAttributes) {
+        let pairs = [
+            (&mut self.colors, other.colors),
+            (&mut self.objects, other.objects),
+            (&mut self.scene_types, other.scene_types),
+            (&mut self.people_descriptions, other.people_descriptions),
+        ];
+        for (existing, added) in pairs {
+            for value in added {
+                let value = value.trim().to_lowercase();
+                if !value.is_empty() && !existing.contains(&value) {
+                    existing.push(value);
+                }
+            }
+        }
+    }
+}
+
 /// Multi-strategy search configuration for intelligent fusion
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MultiStrategyConfig {
@@ -1886,6 +2315,58 @@ pub struct QueryResponse {
     pub sources: Vec<NodeId>,
     pub confidence: f32,
     pub related_queries: Vec<String>,
+    /// Components behind `confidence`
+    #[serde(default)]
+    pub confidence_breakdown: ConfidenceBreakdown,
+    /// Token accounting for the sources packed into the prompt
+    #[serde(default)]
+    pub context_budget: ContextBudgetReport,
+    /// Inline citation markers in `answer`, mapped to `sources`
+    #[serde(default)]
+    pub citations: Vec<AnswerCitation>,
+    /// Sentence support check, when faithfulness verification ran
+    #[serde(default)]
+    pub faithfulness: Option<FaithfulnessReport>,
+    /// Template the answer prompt was rendered from
+    #[serde(default)]
+    pub prompt_template: Option<PromptVersion>,
+    /// Generation parameters used for the answer
+    #[serde(default)]
+    pub generation: Option<ResolvedGeneration>,
+    /// Time spent in each pipeline stage
+    #[serde(default)]
+    pub stage_timings: Vec<StageTiming>,
+}
+
+/// Per-call options for `process_query_with_options`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryOptions {
+    /// Post-generation verification of the answer against its sources
+    #[serde(default)]
+    pub faithfulness: FaithfulnessOptions,
+    /// Overrides of the configured generation parameters
+    #[serde(default)]
+    pub generation: GenerationOptions,
+}
+
+/// AI answer together with the numbered sources it was grounded on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroundedAnswer {
+    pub answer: String,
+    /// Sources in the order they were numbered in the prompt
+    pub sources: Vec<NodeId>,
+    /// Inline citation markers in `answer`, mapped to `sources`
+    pub citations: Vec<AnswerCitation>,
+    pub confidence: ConfidenceBreakdown,
+    /// Template the answer prompt was rendered from
+    #[serde(default)]
+    pub prompt_template: Option<PromptVersion>,
+    /// Generation parameters used for the answer
+    #[serde(default)]
+    pub generation: Option<ResolvedGeneration>,
+    /// Time spent in each pipeline stage
+    #[serde(default)]
+    pub stage_timings: Vec<StageTiming>,
 }
 
 /// Hierarchical response with properly structured data for frontend consumption
@@ -2052,57 +2533,8 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> CoreLogic for NodeS
     }
 
     async fn process_query(&self, query: &str) -> NodeSpaceResult<QueryResponse> {
-        log::info!("🚀 ===== RAG PIPELINE STARTED =====");
-        log::info!("📝 INPUT QUERY: '{}'", query);
-
-        let timer = self
-            .performance_monitor
-            .start_operation("process_query")
-            .with_metadata("query_length".to_string(), query.len().to_string());
-
-        // Check if service is ready
-        if !self.is_ready().await {
-            let state = self.get_state().await;
-            let error = NodeSpaceError::InternalError {
-                message: format!("Service not ready: {:?}", state),
-                service: "core-logic".to_string(),
-            };
-            timer.complete_error(error.to_string());
-            return Err(error);
-        }
-
-        // Step 1: Gather context from semantic search
-        log::info!("🔍 === STEP 1: CONTEXT GATHERING ===");
-        let (context, sources) = self.gather_query_context(query).await?;
-
-        // Step 2: Build and execute prompt
-        log::info!("🏗️ === STEP 2: PROMPT BUILDING ===");
-        let prompt = self.build_contextual_prompt(query, &context);
-
-        log::info!("🤖 === STEP 3: LLM GENERATION ===");
-        let answer = self.generate_contextual_answer(&prompt, &sources).await?;
-
-        // Step 4: Calculate confidence and generate suggestions
-        log::info!("📊 === STEP 4: RESPONSE ASSEMBLY ===");
-        let confidence = self.calculate_response_confidence(&context, &answer);
-        log::info!("   Calculated confidence: {:.3}", confidence);
-
-        let related_queries = self.generate_related_queries(query);
-        log::info!("   Generated {} related queries", related_queries.len());
-
-        let response = QueryResponse {
-            answer: answer.clone(),
-            sources: sources.clone(),
-            confidence,
-            related_queries,
-        };
-
-        log::info!("✅ ===== RAG PIPELINE COMPLETE =====");
-        log::info!("📤 FINAL ANSWER: '{}'", answer);
-        log::info!("📚 SOURCES USED: {} nodes", sources.len());
-
-        timer.complete_success();
-        Ok(response)
+        self.process_query_with_options(query, &QueryOptions::default())
+            .await
     }
 
     async fn get_batch_related_nodes(
@@ -2127,10 +2559,14 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> CoreLogic for NodeS
         // Update content and timestamp
         node.content = serde_json::Value::String(content.to_string());
         node.updated_at = chrono::Utc::now().to_rfc3339();
+        let entities = self.attach_entities(&mut node).await;
 
         // Use the data store's update method which handles embedding regeneration automatically
         // The LanceDB data store now detects content changes and regenerates embeddings as needed
         self.data_store.update_node(node).await?;
+        if let Some(entities) = entities {
+            self.index_entities(node_id, &entities).await;
+        }
 
         // Invalidate embedding cache for this node and its dependents
         self.invalidate_node_cache(node_id).await;
@@ -2206,34 +2642,8 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> CoreLogic for NodeS
     }
 
     async fn generate_insights(&self, node_ids: Vec<NodeId>) -> NodeSpaceResult<String> {
-        if node_ids.is_empty() {
-            return Ok("No nodes provided for insight generation.".to_string());
-        }
-
-        // Collect content from all specified nodes
-        let mut contents = Vec::new();
-        for node_id in &node_ids {
-            if let Ok(Some(node)) = self.data_store.get_node(node_id).await {
-                if let Some(content_str) = node.content.as_str() {
-                    contents.push(content_str.to_string());
-                }
-            }
-        }
-
-        if contents.is_empty() {
-            return Ok("No readable content found in the specified nodes.".to_string());
-        }
-
-        // Generate insights using LLM
-        let combined_content = contents.join("\n\n---\n\n");
-        let prompt = format!(
-            "Analyze the following content and provide key insights, patterns, and connections:\n\n{}\n\nProvide a concise summary with 3-5 key insights:",
-            combined_content
-        );
-
-        let insights = self.nlp_engine.generate_text(&prompt).await?;
-
-        Ok(insights)
+        self.generate_insights_with_options(node_ids, &GenerationOptions::default())
+            .await
     }
 
     /// OPTIMIZATION: Batch create knowledge nodes with bulk embedding generation
@@ -2263,6 +2673,7 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> CoreLogic for NodeS
             let mut node = Node::new("text".to_string(), json!(content));
             node.id = NodeId::new();
             node.metadata = Some(metadata);
+            let entities = self.attach_entities(&mut node).await;
             // root_id will be set appropriately by business logic
 
             // Store node with pre-computed embedding from batch
@@ -2280,6 +2691,9 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> CoreLogic for NodeS
                 .data_store
                 .store_node_with_embedding(node, embedding.clone())
                 .await?;
+            if let Some(entities) = entities {
+                self.index_entities(&node_id, &entities).await;
+            }
             node_ids.push(node_id);
         }
 
@@ -2382,6 +2796,8 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> CoreLogic for NodeS
 
         // 2. Delete the node using existing data store method
         self.data_store.delete_node(node_id).await?;
+        self.unindex_entities(node_id).await;
+        self.unindex_visual_attributes(node_id).await;
 
         // 3. Invalidate hierarchy cache after structural change
         std::mem::drop(self.invalidate_hierarchy_cache());
@@ -2609,6 +3025,128 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> CoreLogic for NodeS
             })
         }
     }
+
+    async fn find_period_node(&self, period: Period) -> NodeSpaceResult<Option<NodeId>> {
+        let timer = self
+            .performance_monitor
+            .start_operation("find_period_node")
+            .with_metadata("period".to_string(), period.to_string());
+
+        match self.find_period_node_by_id(period).await {
+            Ok(node_id) => {
+                timer.complete_success();
+                Ok(node_id)
+            }
+            Err(e) => {
+                timer.complete_error(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    async fn ensure_period_node_exists(&self, period: Period) -> NodeSpaceResult<NodeId> {
+        let timer = self
+            .performance_monitor
+            .start_operation("ensure_period_node_exists")
+            .with_metadata("period".to_string(), period.to_string());
+
+        if let Some(existing_id) = self.find_period_node(period).await? {
+            timer.complete_success();
+            return Ok(existing_id);
+        }
+
+        match self.create_period_node(period).await {
+            Ok(node_id) => {
+                timer.complete_success();
+                Ok(node_id)
+            }
+            Err(e) => {
+                timer.complete_error(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    async fn get_nodes_for_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> NodeSpaceResult<Vec<(NaiveDate, Vec<Node>)>> {
+        let timer = self
+            .performance_monitor
+            .start_operation("get_nodes_for_range")
+            .with_metadata("start".to_string(), start.to_string())
+            .with_metadata("end".to_string(), end.to_string());
+
+        match self.nodes_for_range(start, end).await {
+            Ok(days) => {
+                timer.complete_success();
+                Ok(days)
+            }
+            Err(e) => {
+                timer.complete_error(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    async fn get_hierarchical_nodes_for_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> NodeSpaceResult<Vec<HierarchicalNodes>> {
+        let timer = self
+            .performance_monitor
+            .start_operation("get_hierarchical_nodes_for_range")
+            .with_metadata("start".to_string(), start.to_string())
+            .with_metadata("end".to_string(), end.to_string());
+
+        match self.hierarchical_nodes_for_range(start, end).await {
+            Ok(days) => {
+                timer.complete_success();
+                Ok(days)
+            }
+            Err(e) => {
+                timer.complete_error(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    async fn get_calendar_summary(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> NodeSpaceResult<CalendarSummary> {
+        let timer = self
+            .performance_monitor
+            .start_operation("get_calendar_summary")
+            .with_metadata("start".to_string(), start.to_string())
+            .with_metadata("end".to_string(), end.to_string());
+
+        match self.calendar_summary_for_range(start, end).await {
+            Ok(summary) => {
+                timer.complete_success();
+                Ok(summary)
+            }
+            Err(e) => {
+                timer.complete_error(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    async fn get_calendar_month(&self, year: i32, month: u32) -> NodeSpaceResult<CalendarSummary> {
+        let (start, end) = date_range::month_bounds(year, month).ok_or_else(|| {
+            NodeSpaceError::Validation(ValidationError::InvalidFormat {
+                field: "month".to_string(),
+                expected: "a year and a month between 1 and 12".to_string(),
+                actual: format!("{}-{}", year, month),
+                examples: vec!["2024-06".to_string()],
+            })
+        })?;
+        self.calendar_summary_for_range(start, end).await
+    }
 }
 
 /// Legacy CoreLogic interface for backward compatibility
@@ -3291,68 +3829,39 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> CrossModalSearch
             .await?;
 
         // Step 4: Intelligent fusion and ranking
-        self.intelligent_result_fusion(search_results, query).await
+        let fused_results = self
+            .intelligent_result_fusion(search_results, query)
+            .await?;
+
+        // Step 5: Optional second-stage reranking
+        Ok(self.rerank_results(query, fused_results).await)
     }
 
     async fn extract_entities(&self, query: &str) -> NodeSpaceResult<ExtractedEntities> {
         // Use LLM for entity extraction with structured prompt
-        let extraction_prompt = format!(
-            "Extract entities from this query and respond in JSON format:
-Query: '{}'
-
-Extract:
-- people: names of people mentioned
-- events: events or occasions mentioned  
-- objects: physical objects or items mentioned
-- locations: places or locations mentioned
-
-Respond with JSON: {{\"people\": [...], \"events\": [...], \"objects\": [...], \"locations\": [...]}}",
-            query
-        );
+        let extraction_prompt = self
+            .prompts
+            .render(prompts::names::EXTRACT_ENTITIES, &[("query", query)])?;
 
-        match self.nlp_engine.generate_text(&extraction_prompt).await {
-            Ok(response) => {
-                // Parse JSON response
-                match serde_json::from_str::<ExtractedEntities>(&response) {
-                    Ok(entities) => Ok(entities),
-                    Err(_) => {
-                        // Fallback: simple pattern matching if JSON parsing fails
-                        Ok(self.extract_entities_fallback(query))
-                    }
-                }
-            }
-            Err(_) => {
-                // Fallback to pattern matching if LLM fails
-                Ok(self.extract_entities_fallback(query))
+        match self.generate_structured(&extraction_prompt).await {
+            Ok(entities) => Ok(entities),
+            Err(e) => {
+                // Fallback to rules with the workspace vocabulary
+                log::warn!("⚠️ Entity extraction failed, using rules: {}", e);
+                Ok(self.extract_entities_with_rules(query).await)
             }
         }
     }
 
     async fn extract_temporal_refs(&self, query: &str) -> NodeSpaceResult<Vec<TemporalReference>> {
-        let temporal_prompt = format!(
-            "Extract temporal references from this query. Look for dates, times, or event-based temporal references:
-Query: '{}'
-
-Find references like:
-- Specific dates (June 15, 2023-06-15)
-- Relative times (yesterday, last week)  
-- Event-based times (during birthday, at the meeting)
-- Fuzzy times (around that time, recently)
-
-For each reference, determine if it's exact, relative, event-based, or fuzzy.",
-            query
-        );
-
-        match self.nlp_engine.generate_text(&temporal_prompt).await {
-            Ok(_response) => {
-                // For MVP, do simple pattern matching
-                Ok(self.extract_temporal_refs_fallback(query))
-            }
-            Err(_) => {
-                // Fallback to pattern matching
-                Ok(self.extract_temporal_refs_fallback(query))
-            }
-        }
+        // Rule-based parsing resolves dates against the user's today; the model
+        // is only asked about time words the rules could not place
+        let mut references = self.extract_temporal_refs_fallback(query);
+        if references.is_empty() && temporal_parser::mentions_time(query) {
+            references = self.extract_temporal_refs_with_model(query).await;
+        }
+        self.resolve_event_references(&mut references).await;
+        Ok(references)
     }
 
     async fn extract_visual_refs(&self, query: &str) -> NodeSpaceResult<VisualAttributes> {
@@ -3362,12 +3871,22 @@ For each reference, determine if it's exact, relative, event-based, or fuzzy.",
         let scene_types = self.extract_scene_types(query);
         let people_descriptions = self.extract_people_descriptions(query);
 
-        Ok(VisualAttributes {
+        let mut visual_refs = VisualAttributes {
             colors,
             objects,
             scene_types,
             people_descriptions,
-        })
+        };
+        if images::mentions_images(query) {
+            let prompt = self
+                .prompts
+                .render(prompts::names::EXTRACT_VISUAL, &[("query", query)])?;
+            match self.generate_structured::<VisualAttributes>(&prompt).await {
+                Ok(model_refs) => visual_refs.merge(model_refs),
+                Err(e) => log::warn!("⚠️ Visual extraction failed, using rules: {}", e),
+            }
+        }
+        Ok(visual_refs)
     }
 
     async fn multi_strategy_search(
@@ -3403,8 +3922,14 @@ For each reference, determine if it's exact, relative, event-based, or fuzzy.",
         }
 
         // Strategy 2: Entity-based search
-        for person in &entities.people {
-            if let Ok(entity_results) = self.search_by_entity(person).await {
+        for entity in entities
+            .people
+            .iter()
+            .chain(&entities.events)
+            .chain(&entities.objects)
+            .chain(&entities.locations)
+        {
+            if let Ok(entity_results) = self.search_by_entity(entity).await {
                 all_results.extend(entity_results);
             }
         }
@@ -3450,96 +3975,38 @@ For each reference, determine if it's exact, relative, event-based, or fuzzy.",
 }
 
 impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
-    /// Fallback entity extraction using pattern matching
-    fn extract_entities_fallback(&self, query: &str) -> ExtractedEntities {
-        let query_lower = query.to_lowercase();
-        let mut people = Vec::new();
-        let mut events = Vec::new();
-        let mut objects = Vec::new();
-        let locations = Vec::new();
-
-        // Common name patterns (basic detection)
-        let name_patterns = ["claire", "john", "mary", "david", "sarah", "mike", "anna"];
-        for pattern in &name_patterns {
-            if query_lower.contains(pattern) {
-                people.push(pattern.to_string());
-            }
-        }
+    /// Rule-based temporal reference extraction
+    fn extract_temporal_refs_fallback(&self, query: &str) -> Vec<TemporalReference> {
+        TemporalParser::new(self.today()).parse(query)
+    }
 
-        // Event patterns
-        let event_patterns = [
-            "birthday",
-            "meeting",
-            "party",
-            "conference",
-            "dinner",
-            "lunch",
-        ];
-        for pattern in &event_patterns {
-            if query_lower.contains(pattern) {
-                events.push(pattern.to_string());
+    /// Temporal references resolved by the model, or none when it fails
+    async fn extract_temporal_refs_with_model(&self, query: &str) -> Vec<TemporalReference> {
+        let today = self.today().to_string();
+        let prompt = match self.prompts.render(
+            prompts::names::EXTRACT_TEMPORAL,
+            &[("query", query), ("today", &today)],
+        ) {
+            Ok(prompt) => prompt,
+            Err(e) => {
+                log::warn!("⚠️ Temporal extraction prompt failed: {}", e);
+                return vec![];
             }
-        }
-
-        // Object patterns
-        let object_patterns = [
-            "shirt",
-            "document",
-            "photo",
-            "screenshot",
-            "diagram",
-            "chart",
-        ];
-        for pattern in &object_patterns {
-            if query_lower.contains(pattern) {
-                objects.push(pattern.to_string());
+        };
+        match self
+            .generate_structured::<Vec<temporal_parser::ModelTemporalReference>>(&prompt)
+            .await
+        {
+            Ok(references) => references
+                .into_iter()
+                .filter(|reference| !reference.text.trim().is_empty())
+                .map(temporal_parser::ModelTemporalReference::into_reference)
+                .collect(),
+            Err(e) => {
+                log::warn!("⚠️ Temporal extraction failed: {}", e);
+                vec![]
             }
         }
-
-        ExtractedEntities {
-            people,
-            events,
-            objects,
-            locations,
-        }
-    }
-
-    /// Fallback temporal reference extraction
-    fn extract_temporal_refs_fallback(&self, query: &str) -> Vec<TemporalReference> {
-        let query_lower = query.to_lowercase();
-        let mut refs = Vec::new();
-
-        // Look for relative time patterns
-        if query_lower.contains("yesterday") {
-            refs.push(TemporalReference {
-                raw_text: "yesterday".to_string(),
-                parsed_date: Some(chrono::Utc::now().date_naive() - chrono::Duration::days(1)),
-                date_range: None,
-                temporal_type: TemporalType::Relative,
-            });
-        }
-
-        if query_lower.contains("birthday") {
-            refs.push(TemporalReference {
-                raw_text: "birthday".to_string(),
-                parsed_date: None,
-                date_range: None,
-                temporal_type: TemporalType::Event,
-            });
-        }
-
-        if query_lower.contains("last week") {
-            let start_date = chrono::Utc::now().date_naive() - chrono::Duration::days(7);
-            let end_date = chrono::Utc::now().date_naive();
-            refs.push(TemporalReference {
-                raw_text: "last week".to_string(),
-                parsed_date: None,
-                date_range: Some((start_date, end_date)),
-                temporal_type: TemporalType::Relative,
-            });
-        }
-
-        refs
     }
 
     /// Extract color references from query
@@ -3605,15 +4072,39 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
         descriptions
     }
 
-    /// Search nodes by entity mentions
+    /// Search nodes by entity, through the entity index
     async fn search_by_entity(&self, entity: &str) -> NodeSpaceResult<Vec<SearchResult>> {
-        // For LanceDB: Simple content search by entity
-        let all_nodes = self.data_store.query_nodes(entity).await?;
+        let node_ids = self.lookup_entity(entity).await?;
+        if node_ids.is_empty() {
+            // Nodes stored before entity extraction are only found by text
+            return self.search_by_text_mention(entity).await;
+        }
+
+        let mut nodes = Vec::new();
+        for node_id in node_ids
+            .iter()
+            .take(constants::DEFAULT_MAX_RESULTS_PER_STRATEGY)
+        {
+            if let Some(node) = self.data_store.get_node(node_id).await? {
+                nodes.push(node);
+            }
+        }
+        Ok(Self::ranked_mentions(nodes))
+    }
+
+    /// Search nodes whose content mentions a term
+    async fn search_by_text_mention(&self, term: &str) -> NodeSpaceResult<Vec<SearchResult>> {
+        let all_nodes = self.data_store.query_nodes(term).await?;
         let nodes: Vec<_> = all_nodes
             .into_iter()
             .take(constants::DEFAULT_MAX_RESULTS_PER_STRATEGY)
             .collect();
-        let results = nodes
+        Ok(Self::ranked_mentions(nodes))
+    }
+
+    /// Mention results in retrieval order with decaying scores
+    fn ranked_mentions(nodes: Vec<Node>) -> Vec<SearchResult> {
+        nodes
             .into_iter()
             .enumerate()
             .map(|(index, node)| SearchResult {
@@ -3622,51 +4113,7 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
                 score: constants::BASE_CONFIDENCE_WITH_CONTEXT
                     - (index as f32 * constants::SCORE_DECAY_FACTOR * 0.5),
             })
-            .collect();
-
-        Ok(results)
-    }
-
-    /// Search nodes by temporal reference
-    async fn search_by_temporal_ref(
-        &self,
-        temporal_ref: &TemporalReference,
-    ) -> NodeSpaceResult<Vec<SearchResult>> {
-        match &temporal_ref.temporal_type {
-            TemporalType::Event => {
-                // Search for event mentions
-                self.search_by_entity(&temporal_ref.raw_text).await
-            }
-            TemporalType::Exact | TemporalType::Relative => {
-                if let Some(date) = temporal_ref.parsed_date {
-                    let date_str = date.format("%Y-%m-%d").to_string();
-                    // For LanceDB: Get all nodes and filter by date
-                    let all_nodes = self.data_store.query_nodes("").await?;
-                    let nodes: Vec<_> = all_nodes
-                        .into_iter()
-                        .filter(|node| node.created_at.starts_with(&date_str))
-                        .take(constants::DEFAULT_MAX_RESULTS_PER_STRATEGY)
-                        .collect();
-                    let results = nodes
-                        .into_iter()
-                        .enumerate()
-                        .map(|(index, node)| SearchResult {
-                            node_id: node.id.clone(),
-                            node,
-                            score: 0.9 - (index as f32 * constants::SCORE_DECAY_FACTOR * 0.5),
-                        })
-                        .collect();
-
-                    Ok(results)
-                } else {
-                    Ok(Vec::new())
-                }
-            }
-            TemporalType::Fuzzy => {
-                // For fuzzy temporal references, return empty for now
-                Ok(Vec::new())
-            }
-        }
+            .collect()
     }
 
     /// Search nodes by visual attributes
@@ -3674,18 +4121,24 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
         &self,
         visual_refs: &VisualAttributes,
     ) -> NodeSpaceResult<Vec<SearchResult>> {
+        let indexed = self.search_visual_index(visual_refs).await?;
+        if !indexed.is_empty() {
+            return Ok(indexed);
+        }
+
+        // Images stored without indexed attributes only match by text
         let mut results = Vec::new();
 
         // Search for color mentions
         for color in &visual_refs.colors {
-            if let Ok(color_results) = self.search_by_entity(color).await {
+            if let Ok(color_results) = self.search_by_text_mention(color).await {
                 results.extend(color_results);
             }
         }
 
         // Search for object mentions
         for object in &visual_refs.objects {
-            if let Ok(object_results) = self.search_by_entity(object).await {
+            if let Ok(object_results) = self.search_by_text_mention(object).await {
                 results.extend(object_results);
             }
         }
@@ -3761,141 +4214,175 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
         Ok(result_map)
     }
 
-    /// Helper method to gather context for query processing
-    async fn gather_query_context(
+    /// Apply the configured reranker to the top-N retrieval candidates
+    /// Candidates beyond top-N keep their retrieval order after the reranked head;
+    /// reranker failures fall back to the original ordering
+    pub(crate) async fn rerank_results(
         &self,
         query: &str,
-    ) -> NodeSpaceResult<(Vec<String>, Vec<NodeId>)> {
-        log::info!("🔍 STEP 1: Starting semantic search for query: '{}'", query);
-
-        let search_results = self
-            .semantic_search(query, constants::DEFAULT_SEARCH_LIMIT)
-            .await?;
+        results: Vec<SearchResult>,
+    ) -> Vec<SearchResult> {
+        pipeline::rerank_with(
+            self.reranker.as_deref(),
+            &self.nlp_engine,
+            &self.prompts,
+            query,
+            results,
+        )
+        .await
+    }
 
-        log::info!(
-            "📊 STEP 1 RESULTS: Found {} semantic search results",
-            search_results.len()
-        );
+    /// RAG pipeline over this service's stages, with `process_query` defaults
+    pub fn rag_pipeline(&self) -> RagPipeline<'_, D, N> {
+        RagPipeline::new(self)
+    }
 
-        // Log each search result with score and snippet
-        for (i, result) in search_results.iter().enumerate() {
-            let snippet = result
-                .node
-                .content
-                .as_str()
-                .map(|s| s.chars().take(100).collect::<String>())
-                .unwrap_or_else(|| "No content".to_string());
-            log::info!(
-                "   {}. Score: {:.3} | Snippet: '{}'...",
-                i + 1,
-                result.score,
-                snippet
-            );
+    /// Generate insights from a set of nodes with per-call generation options
+    pub async fn generate_insights_with_options(
+        &self,
+        node_ids: Vec<NodeId>,
+        options: &GenerationOptions,
+    ) -> NodeSpaceResult<String> {
+        if node_ids.is_empty() {
+            return Ok("No nodes provided for insight generation.".to_string());
         }
 
-        let context: Vec<String> = search_results
-            .iter()
-            .filter_map(|result| result.node.content.as_str().map(|s| s.to_string()))
-            .collect();
-
-        let sources: Vec<NodeId> = search_results
-            .iter()
-            .map(|result| result.node_id.clone())
-            .collect();
+        // Collect content from all specified nodes
+        let mut contents = Vec::new();
+        for node_id in &node_ids {
+            if let Ok(Some(node)) = self.data_store.get_node(node_id).await {
+                if let Some(content_str) = node.content.as_str() {
+                    contents.push(content_str.to_string());
+                }
+            }
+        }
 
-        log::info!(
-            "📝 STEP 1 CONTEXT: Gathered {} context pieces from {} sources",
-            context.len(),
-            sources.len()
-        );
+        if contents.is_empty() {
+            return Ok("No readable content found in the specified nodes.".to_string());
+        }
 
-        Ok((context, sources))
+        // Generate insights using LLM, summarizing first if the content would overflow
+        let scaffold = self
+            .prompts
+            .render(prompts::names::INSIGHTS, &[("content", "")])?;
+        let generation = generation_options::resolve_generation(
+            options,
+            &self.config.performance_config,
+            &self.model_limits,
+            constants::DEFAULT_RAG_MAX_TOKENS,
+        )?;
+        let available_tokens = generation
+            .context_window
+            .saturating_sub(generation.max_tokens)
+            .saturating_sub(self.token_counter.count_tokens(&scaffold.text));
+        let combined_content = self
+            .condense_to_fit(contents, "\n\n---\n\n", available_tokens)
+            .await?;
+        generate_insights_from(
+            &self.nlp_engine,
+            &self.prompts,
+            &combined_content,
+            &generation,
+        )
+        .await
     }
 
-    /// Helper method to build contextual prompt with length management
-    fn build_contextual_prompt(&self, query: &str, context: &[String]) -> String {
-        log::info!("🏗️ STEP 2: Building contextual prompt");
-        log::info!("   Query: '{}'", query);
-        log::info!("   Context pieces: {}", context.len());
+    /// Process a natural language query with per-call options
+    pub async fn process_query_with_options(
+        &self,
+        query: &str,
+        options: &QueryOptions,
+    ) -> NodeSpaceResult<QueryResponse> {
+        log::info!("🚀 ===== RAG PIPELINE STARTED =====");
+        log::info!("📝 INPUT QUERY: '{}'", query);
 
-        let context_text = context.join("\n\n");
-        log::info!("   Combined context length: {} chars", context_text.len());
+        let timer = self
+            .performance_monitor
+            .start_operation("process_query")
+            .with_metadata("query_length".to_string(), query.len().to_string());
 
-        let max_context_len = self
-            .config
-            .performance_config
-            .context_window
-            .unwrap_or(constants::DEFAULT_CONTEXT_WINDOW)
-            .saturating_sub(query.len() + constants::PROMPT_STRUCTURE_RESERVE);
+        let output = match self
+            .rag_pipeline()
+            .with_faithfulness(options.faithfulness.clone())
+            .with_generation_options(options.generation)
+            .with_related_queries(true)
+            .with_offline_fallback(true)
+            .run(query)
+            .await
+        {
+            Ok(output) => output,
+            Err(e) => {
+                timer.complete_error(e.to_string());
+                return Err(e);
+            }
+        };
 
-        log::info!("   Max context length allowed: {} chars", max_context_len);
+        log::info!("✅ ===== RAG PIPELINE COMPLETE =====");
+        log::info!("📤 FINAL ANSWER: '{}'", output.answer);
+        log::info!("📚 SOURCES USED: {} nodes", output.sources.len());
 
-        let truncated_context = if context_text.len() > max_context_len {
-            log::info!(
-                "   ⚠️ Context truncated from {} to {} chars",
-                context_text.len(),
-                max_context_len
-            );
-            format!("{}...", &context_text[..max_context_len])
-        } else {
-            log::info!("   ✅ Context fits within limits");
-            context_text
-        };
+        timer.complete_success();
+        Ok(QueryResponse::from(output))
+    }
 
-        let final_prompt = if truncated_context.is_empty() {
-            log::info!("   📝 Using general knowledge prompt (no context)");
-            format!(
-                "Please provide a detailed and helpful answer to this question: {}\n\nProvide a comprehensive response with explanations and context where appropriate.",
-                query
-            )
-        } else {
-            log::info!("   📝 Using conversational contextual prompt");
-            format!(
-                "Using the context below, provide a helpful answer that's both informative and conversational:\n\nContext:\n{}\n\nQuestion: {}\n\nAnswer directly but include relevant context that helps explain the 'why' behind the information. Keep it engaging and professional.\n\nAnswer:",
-                truncated_context, query
-            )
-        };
+    /// Expand a retrieved node with its ancestry path, siblings and children
+    async fn expand_search_hit(
+        &self,
+        result: &SearchResult,
+        options: &HierarchyContextOptions,
+    ) -> ExpandedHit {
+        // Nearest ancestors first from get_ancestors; outline renders root first
+        let mut ancestors: Vec<Node> = self
+            .get_ancestors(&result.node_id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .take(options.max_ancestors)
+            .collect();
+        ancestors.reverse();
 
-        log::info!(
-            "🎯 STEP 2 COMPLETE: Final prompt length: {} chars",
-            final_prompt.len()
-        );
+        let (siblings, children) = match self.build_node_context(&result.node).await {
+            Ok(context) => (
+                context
+                    .siblings
+                    .into_iter()
+                    .take(options.max_siblings)
+                    .collect(),
+                context
+                    .related_nodes
+                    .into_iter()
+                    .take(options.max_children)
+                    .collect(),
+            ),
+            Err(e) => {
+                log::warn!(
+                    "⚠️ Could not build hierarchy context for {}: {}",
+                    result.node_id,
+                    e
+                );
+                (Vec::new(), Vec::new())
+            }
+        };
 
-        final_prompt
+        ExpandedHit {
+            node: result.node.clone(),
+            score: result.score,
+            ancestors,
+            siblings,
+            children,
+        }
     }
 
-    /// Helper method to generate contextual answer with fallback handling
-    async fn generate_contextual_answer(
+    /// Generate an answer for a packed RAG request
+    async fn generate_answer(
         &self,
-        prompt: &str,
+        text_request: TextGenerationRequest,
         sources: &[NodeId],
     ) -> NodeSpaceResult<String> {
-        log::info!("🤖 STEP 3: Starting LLM text generation");
-        log::info!("   Prompt length: {} chars", prompt.len());
+        log::info!("🤖 Starting LLM text generation");
+        log::info!("   Prompt length: {} chars", text_request.prompt.len());
         log::info!("   Source nodes: {}", sources.len());
-        log::info!("   📝 FULL PROMPT SENT TO LLM:\n{}", prompt);
-
-        // Create enhanced text generation request with improved parameters for richer responses
-        let text_request = TextGenerationRequest {
-            prompt: prompt.to_string(),
-            max_tokens: 500,          // Increased for detailed responses (was 100)
-            temperature: 0.7, // Balanced creativity (reduced from 1.0 for more focused answers)
-            context_window: 8192, // Standard context window
-            conversation_mode: false, // Not a conversation, single RAG query
-            rag_context: Some(RAGContext {
-                knowledge_sources: sources
-                    .iter()
-                    .map(|id| format!("node:{}", id.as_str()))
-                    .collect(),
-                retrieval_confidence: 0.8, // High confidence in our retrieval
-                context_summary: "Campaign management data retrieved from semantic search"
-                    .to_string(),
-                suggested_links: vec![], // No smart links for now
-            }),
-            enable_link_generation: false, // Disable for simplicity
-            node_metadata: vec![],         // No metadata for now
-        };
-
+        log::info!("   📝 FULL PROMPT SENT TO LLM:\n{}", text_request.prompt);
         log::info!(
             "   🎯 Using enhanced generation: temp={}, max_tokens={}",
             text_request.temperature,
@@ -3904,7 +4391,7 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
 
         match self.nlp_engine.generate_text_enhanced(text_request).await {
             Ok(response) => {
-                log::info!("✅ STEP 3 SUCCESS: Enhanced LLM generated response");
+                log::info!("✅ Enhanced LLM generated response");
                 log::info!("   Response length: {} chars", response.text.len());
                 log::info!("   Tokens used: {}", response.tokens_used);
                 log::info!(
@@ -3915,31 +4402,26 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
                 Ok(response.text)
             }
             Err(e) => {
-                log::error!("❌ STEP 3 FAILED: LLM generation error: {}", e);
-                // Handle text generation failure based on configuration
-                match self.config.offline_config.offline_fallback {
-                    OfflineFallback::Error => {
-                        Err(NodeSpaceError::Processing(ProcessingError::model_error(
-                            "core-logic",
-                            "text-generation",
-                            &format!("Text generation failed: {}", e)
-                        )))
-                    }
-                    OfflineFallback::Stub => {
-                        Ok("I apologize, but I'm currently unable to generate a response due to AI system limitations. Please try again later.".to_string())
-                    }
-                    OfflineFallback::Cache => {
-                        if sources.is_empty() {
-                            Ok("I found no relevant information to answer your question.".to_string())
-                        } else {
-                            Ok(format!("I found {} related documents but cannot generate a detailed response at this time. Please review the source materials directly.", sources.len()))
-                        }
-                    }
-                }
+                log::error!("❌ LLM generation error: {}", e);
+                Err(NodeSpaceError::Processing(ProcessingError::model_error(
+                    "core-logic",
+                    "text-generation",
+                    &format!("Text generation failed: {}", e),
+                )))
             }
         }
     }
 
+    /// Canned answer for a failed generation under the configured offline policy
+    ///
+    /// Only used by pipelines that opt into the offline fallback.
+    fn offline_fallback_answer(&self, sources: &[NodeId]) -> Option<String> {
+        self.config
+            .offline_config
+            .offline_fallback
+            .answer(sources.len())
+    }
+
     /// Generate intelligent AI response using real Ollama integration
     /// This method implements the REAL AI functionality required by NS-127
     pub async fn generate_ai_response(
@@ -3947,148 +4429,126 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
         query: &str,
         context_nodes: &[NodeId],
     ) -> NodeSpaceResult<String> {
-        log::info!("🤖 Generating REAL AI response using Ollama integration");
-        log::info!("   Query: '{}'", query);
+        self.generate_grounded_response(query, context_nodes)
+            .await
+            .map(|grounded| grounded.answer)
+    }
+
+    /// Generate an AI response with numbered sources and parsed inline citations
+    ///
+    /// Answers from `context_nodes` in the given order, or from a semantic
+    /// search when none are given.
+    pub async fn generate_grounded_response(
+        &self,
+        query: &str,
+        context_nodes: &[NodeId],
+    ) -> NodeSpaceResult<GroundedAnswer> {
+        log::info!("🤖 Generating AI response for '{}'", query);
         log::info!("   Context nodes: {}", context_nodes.len());
 
-        // Check if service is ready
-        if !self.is_ready().await {
-            return Err(NodeSpaceError::InternalError {
-                message: "Service not ready for AI response generation".to_string(),
-                service: "core-logic".to_string(),
-            });
-        }
+        let output = self.grounded_pipeline(context_nodes).run(query).await?;
 
-        // Step 1: Perform semantic search if no context provided
-        let relevant_nodes = if context_nodes.is_empty() {
-            log::info!("   No context provided, performing semantic search");
-            match self.semantic_search(query, 5).await {
-                Ok(results) => results.into_iter().map(|r| r.node_id).collect(),
-                Err(e) => {
-                    log::warn!("   Semantic search failed: {}, using empty context", e);
-                    vec![]
-                }
+        Ok(GroundedAnswer {
+            answer: output.answer,
+            sources: output.sources,
+            citations: output.citations,
+            confidence: output.confidence,
+            prompt_template: Some(output.prompt_template),
+            generation: Some(output.generation),
+            stage_timings: output.stage_timings,
+        })
+    }
+
+    /// Preset for document-style AI responses over given nodes or a short search
+    pub(crate) fn grounded_pipeline(&self, context_nodes: &[NodeId]) -> RagPipeline<'_, D, N> {
+        let retrieval = if context_nodes.is_empty() {
+            Retrieval::Search {
+                limit: constants::DEFAULT_AI_RESPONSE_SEARCH_LIMIT,
             }
         } else {
-            context_nodes.to_vec()
+            Retrieval::Nodes(context_nodes.to_vec())
         };
 
-        // Step 2: Build rich RAG context from nodes
-        let mut context_texts = Vec::new();
-        for node_id in &relevant_nodes {
-            if let Ok(Some(node)) = self.data_store.get_node(node_id).await {
-                context_texts.push(format!("Document: {}", node.content));
-            }
-        }
+        self.rag_pipeline()
+            .with_retrieval(retrieval)
+            .with_prompt(PromptStyle::Document)
+            .with_max_tokens(constants::DEFAULT_AI_RESPONSE_MAX_TOKENS)
+            .with_link_generation(true)
+    }
 
-        let context_text = if context_texts.is_empty() {
-            String::new()
+    /// The NLP engine as entailment grader, when entailment confidence is enabled
+    fn entailment_generator(&self) -> Option<&dyn TextGenerator> {
+        if self.entailment_confidence {
+            Some(&self.nlp_engine)
         } else {
-            context_texts.join("\n\n")
-        };
+            None
+        }
+    }
 
-        // Step 3: Create enhanced prompt for RAG
-        let prompt = if context_text.is_empty() {
-            format!("Answer this question using your knowledge: {}", query)
-        } else {
-            format!(
-                "Context Information:\n{}\n\nBased on the context above, answer this question: {}\n\nProvide a helpful and accurate response:",
-                context_text,
-                query
-            )
-        };
+    /// Score an answer against the context plan it was generated from
+    async fn assess_answer_confidence(
+        &self,
+        answer: &str,
+        plan: &ContextPlan,
+        citations: &[AnswerCitation],
+    ) -> ConfidenceBreakdown {
+        let scores: Vec<f32> = plan.included.iter().map(|c| c.score).collect();
+        confidence::assess_confidence(
+            self.entailment_generator(),
+            &self.prompts,
+            answer,
+            &plan.context_texts(),
+            &scores,
+            citations,
+        )
+        .await
+    }
 
-        // Step 4: Use REAL enhanced text generation with Ollama
-        let text_request = TextGenerationRequest {
-            prompt: prompt.clone(),
-            max_tokens: 2000,         // Increased for richer responses
-            temperature: 0.7,         // Balanced creativity
-            context_window: 8192,     // Full context window
-            conversation_mode: false, // Single query mode
-            rag_context: Some(RAGContext {
-                knowledge_sources: relevant_nodes
-                    .iter()
-                    .map(|id| format!("node:{}", id.as_str()))
-                    .collect(),
-                retrieval_confidence: if context_text.is_empty() { 0.3 } else { 0.8 },
-                context_summary: if context_text.is_empty() {
-                    "General knowledge query".to_string()
-                } else {
-                    "Relevant documents retrieved from knowledge base".to_string()
-                },
-                suggested_links: vec![],
-            }),
-            enable_link_generation: true, // Enable smart linking
-            node_metadata: vec![],
+    /// Suggest follow-up questions grounded in the sources of an answer
+    async fn generate_related_queries(
+        &self,
+        query: &str,
+        answer: &str,
+        plan: &ContextPlan,
+    ) -> Vec<String> {
+        let context = plan.context_texts();
+        if context.is_empty() {
+            return Vec::new();
+        }
+
+        let candidates = match related_queries::generate_with_llm(
+            &self.nlp_engine,
+            &self.prompts,
+            query,
+            answer,
+            &context,
+        )
+        .await
+        {
+            Some(candidates) if !candidates.is_empty() => candidates,
+            _ => related_queries::fallback_suggestions(&context),
         };
 
-        log::info!(
-            "   Sending request to Ollama (temp={}, max_tokens={})",
-            text_request.temperature,
-            text_request.max_tokens
-        );
-
-        // Step 5: Generate response using real Ollama AI
-        match self.nlp_engine.generate_text_enhanced(text_request).await {
-            Ok(response) => {
-                log::info!("✅ REAL AI response generated successfully");
-                log::info!("   Response length: {} chars", response.text.len());
-                log::info!("   Tokens used: {}", response.tokens_used);
-                log::info!(
-                    "   Generation time: {}ms",
-                    response.generation_metrics.generation_time_ms
-                );
-                log::info!(
-                    "   Context utilization: references={}, score={:.2}",
-                    response.context_utilization.context_referenced,
-                    response.context_utilization.relevance_score
-                );
-
-                // Return the actual AI-generated response
-                Ok(response.text)
+        // Only suggest follow-ups that the notes can actually answer
+        let mut grounded = Vec::new();
+        for candidate in related_queries::dedupe_suggestions(query, candidates) {
+            if grounded.len() >= constants::DEFAULT_RELATED_QUERY_LIMIT {
+                break;
             }
-            Err(e) => {
-                log::error!("❌ REAL AI generation failed: {}", e);
-                Err(NodeSpaceError::Processing(ProcessingError::model_error(
-                    "core-logic",
-                    "ai-response-generation",
-                    &format!("Real Ollama AI generation failed: {}", e),
-                )))
+            match self.semantic_search(&candidate, 1).await {
+                Ok(results)
+                    if results
+                        .first()
+                        .is_some_and(|r| r.score >= constants::MIN_RELATED_QUERY_SCORE) =>
+                {
+                    grounded.push(candidate)
+                }
+                Ok(_) => log::debug!("   Dropping related query without matches: '{}'", candidate),
+                Err(e) => log::warn!("⚠️ Related query search failed: {}", e),
             }
         }
-    }
 
-    /// Helper method to calculate response confidence
-    fn calculate_response_confidence(&self, context: &[String], answer: &str) -> f32 {
-        let base_confidence = if context.is_empty() {
-            constants::BASE_CONFIDENCE_NO_CONTEXT
-        } else {
-            constants::BASE_CONFIDENCE_WITH_CONTEXT
-        };
-
-        if answer.contains("currently unable") || answer.contains("cannot generate") {
-            base_confidence * constants::FALLBACK_CONFIDENCE_FACTOR
-        } else {
-            base_confidence
-        }
-    }
-
-    /// Helper method to generate related queries
-    fn generate_related_queries(&self, query: &str) -> Vec<String> {
-        vec![
-            format!(
-                "What else about {}?",
-                query.split_whitespace().last().unwrap_or("this topic")
-            ),
-            format!(
-                "How does {} work?",
-                query
-                    .split_whitespace()
-                    .take(3)
-                    .collect::<Vec<_>>()
-                    .join(" ")
-            ),
-        ]
+        grounded
     }
 
     /// Efficient hierarchical structure building using single-query optimization
@@ -4386,6 +4846,56 @@ impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D,
     }
 }
 
+/// Warn about citation markers that point at sources missing from the prompt
+fn log_invalid_citations(citations: &[AnswerCitation]) {
+    for citation in citations::invalid_citations(citations) {
+        log::warn!(
+            "⚠️ Answer cites nonexistent source [{}] at chars {}..{}",
+            citation.marker,
+            citation.marker_start,
+            citation.marker_end
+        );
+    }
+}
+
+/// Insights for already condensed node content, generated with `generation`
+async fn generate_insights_from(
+    generator: &dyn TextGenerator,
+    prompts: &PromptRegistry,
+    content: &str,
+    generation: &ResolvedGeneration,
+) -> NodeSpaceResult<String> {
+    let prompt = prompts.render(prompts::names::INSIGHTS, &[("content", content)])?;
+    log::debug!(
+        "Insights prompt template: {} ({})",
+        prompt.template.name,
+        prompt.template.version
+    );
+    generator.generate_with(&prompt.text, generation).await
+}
+
+/// Ollama multimodal model from the model configuration
+///
+/// `None` unless an Ollama URL or a multimodal model is configured, or for an
+/// invalid URL.
+fn default_image_analyzer(config: &ModelConfig) -> Option<Arc<dyn ImageAnalyzer>> {
+    if config.ollama_url.is_none() && config.multimodal_model.is_none() {
+        return None;
+    }
+    let url = config.ollama_url.as_deref().unwrap_or(DEFAULT_OLLAMA_URL);
+    let model = config
+        .multimodal_model
+        .as_deref()
+        .unwrap_or(DEFAULT_OLLAMA_MODEL);
+    match OllamaClient::new(url, model) {
+        Ok(client) => Some(Arc::new(client)),
+        Err(e) => {
+            log::warn!("⚠️ Invalid Ollama URL, images will not be described: {}", e);
+            None
+        }
+    }
+}
+
 /// Count total nodes in hierarchical structure (recursive)
 fn count_hierarchical_nodes(nodes: &[HierarchicalNode]) -> usize {
     let mut count = nodes.len();
@@ -4422,3 +4932,54 @@ mod collective_sibling_tests {
         assert!(context.siblings.iter().any(|s| s.content.get("text").unwrap() == "Sibling 2"));
     }
 }
+
+#[cfg(test)]
+mod insights_tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Generator double recording the temperature it was asked to use
+    #[derive(Default)]
+    struct TemperatureRecorder(Mutex<Option<f32>>);
+
+    #[async_trait]
+    impl TextGenerator for TemperatureRecorder {
+        async fn generate(&self, _prompt: &str) -> NodeSpaceResult<String> {
+            Ok("Spending is concentrated in Q3.".to_string())
+        }
+
+        async fn generate_with(
+            &self,
+            prompt: &str,
+            generation: &ResolvedGeneration,
+        ) -> NodeSpaceResult<String> {
+            *self.0.lock().unwrap() = Some(generation.temperature);
+            self.generate(prompt).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insights_temperature_override_reaches_the_generator() {
+        let config = NodeSpaceConfig::default();
+        let generation = generation_options::resolve_generation(
+            &GenerationOptions::default().with_temperature(0.1),
+            &config.performance_config,
+            &ModelLimits::default(),
+            constants::DEFAULT_RAG_MAX_TOKENS,
+        )
+        .unwrap();
+        let recorder = TemperatureRecorder::default();
+
+        let insights = generate_insights_from(
+            &recorder,
+            &PromptRegistry::default(),
+            "The Q3 marketing budget was approved",
+            &generation,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(insights, "Spending is concentrated in Q3.");
+        assert_eq!(*recorder.0.lock().unwrap(), Some(0.1));
+    }
+}
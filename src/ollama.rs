@@ -0,0 +1,362 @@
+//! Ollama HTTP client for token streaming and image description
+//!
+//! `NLPEngine::generate_text_enhanced` returns the whole completion at once
+//! and takes no images. For streaming answers the service calls the Ollama
+//! server directly: `/api/generate` with `stream: true` answers with one JSON
+//! line per batch of tokens, which are forwarded as they arrive. Images are
+//! sent base64-encoded to the multimodal model in a single request.
+
+use crate::images::ImageAnalyzer;
+use crate::streaming::StreamingTextGenerator;
+use async_trait::async_trait;
+use base64::Engine as _;
+use futures::StreamExt;
+use nodespace_core_types::{NodeSpaceError, NodeSpaceResult, ValidationError};
+use nodespace_nlp_engine::TextGenerationRequest;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Address of a local Ollama server
+pub const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+
+/// Model used when none is configured; it accepts images as well as text
+pub const DEFAULT_OLLAMA_MODEL: &str = "gemma3:12b";
+
+/// Longest wait for the connection or the next piece of a response
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Client for the Ollama generate API
+#[derive(Debug, Clone)]
+pub struct OllamaClient {
+    http: reqwest::Client,
+    base_url: String,
+    model: String,
+    /// Model describing images, `model` unless set
+    multimodal_model: String,
+}
+
+/// One line of a generate response
+#[derive(Debug, Deserialize)]
+struct GenerateLine {
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+}
+
+impl OllamaClient {
+    /// Client for `model` on the server at `base_url`
+    pub fn new(base_url: &str, model: &str) -> NodeSpaceResult<Self> {
+        let valid = reqwest::Url::parse(base_url)
+            .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host_str().is_some());
+        if !valid {
+            return Err(NodeSpaceError::Validation(ValidationError::InvalidFormat {
+                field: "ollama_url".to_string(),
+                expected: "an http or https URL".to_string(),
+                actual: base_url.to_string(),
+                examples: vec![DEFAULT_OLLAMA_URL.to_string()],
+            }));
+        }
+
+        Ok(Self {
+            http: http_client(DEFAULT_TIMEOUT)?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            multimodal_model: model.to_string(),
+        })
+    }
+
+    /// Model asked to describe images
+    pub fn with_multimodal_model(mut self, model: &str) -> Self {
+        self.multimodal_model = model.to_string();
+        self
+    }
+
+    /// Longest wait for the connection or the next piece of a response
+    pub fn with_timeout(mut self, timeout: Duration) -> NodeSpaceResult<Self> {
+        self.http = http_client(timeout)?;
+        Ok(self)
+    }
+
+    fn endpoint(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+
+    /// POST a JSON body, failing on responses other than success
+    async fn post(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+    ) -> NodeSpaceResult<reqwest::Response> {
+        let response = self
+            .http
+            .post(self.endpoint(path))
+            .json(body)
+            .send()
+            .await
+            .map_err(ollama_error)?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(ollama_error(format!("HTTP {}: {}", status, message.trim())));
+        }
+        Ok(response)
+    }
+}
+
+/// Streams tokens from `/api/generate` as the model produces them
+///
+/// Dropping the chunk receiver closes the connection, which stops generation
+/// on the server.
+#[async_trait]
+impl StreamingTextGenerator for OllamaClient {
+    async fn generate_stream(
+        &self,
+        request: TextGenerationRequest,
+        chunks: mpsc::Sender<String>,
+    ) -> NodeSpaceResult<Option<String>> {
+        let body = serde_json::json!({
+            "model": self.model,
+            "prompt": request.prompt,
+            "stream": true,
+            "options": {
+                "temperature": request.temperature,
+                "num_predict": request.max_tokens,
+                "num_ctx": request.context_window,
+            },
+        });
+
+        let generation = async {
+            let mut stream = self.post("api/generate", &body).await?.bytes_stream();
+            let mut pending = Vec::new();
+            let mut text = String::new();
+            let mut finished = false;
+            while !finished {
+                match stream.next().await {
+                    Some(piece) => pending.extend_from_slice(&piece.map_err(ollama_error)?),
+                    None => {
+                        pending.push(b'\n');
+                        finished = true;
+                    }
+                }
+                // One JSON object per line; a line may span several pieces
+                while let Some(newline) = pending.iter().position(|&byte| byte == b'\n') {
+                    let line: Vec<u8> = pending.drain(..=newline).collect();
+                    let Some(line) = parse_line(&String::from_utf8_lossy(&line))? else {
+                        continue;
+                    };
+                    if !line.response.is_empty() {
+                        text.push_str(&line.response);
+                        if chunks.send(line.response).await.is_err() {
+                            return Ok(None);
+                        }
+                    }
+                    if line.done {
+                        return Ok(Some(text));
+                    }
+                }
+            }
+            Ok(Some(text))
+        };
+
+        tokio::select! {
+            _ = chunks.closed() => Ok(None),
+            result = generation => result,
+        }
+    }
+}
+
+/// Describes images with the multimodal model in one non-streaming request
+#[async_trait]
+impl ImageAnalyzer for OllamaClient {
+    async fn describe_image(&self, image: &[u8], prompt: &str) -> NodeSpaceResult<String> {
+        let body = serde_json::json!({
+            "model": self.multimodal_model,
+            "prompt": prompt,
+            "images": [base64::engine::general_purpose::STANDARD.encode(image)],
+            "stream": false,
+        });
+        let text = self
+            .post("api/generate", &body)
+            .await?
+            .text()
+            .await
+            .map_err(ollama_error)?;
+        match parse_line(&text)? {
+            Some(line) => Ok(line.response),
+            None => Err(ollama_error("empty response")),
+        }
+    }
+}
+
+fn http_client(timeout: Duration) -> NodeSpaceResult<reqwest::Client> {
+    reqwest::Client::builder()
+        .connect_timeout(timeout)
+        .read_timeout(timeout)
+        .build()
+        .map_err(ollama_error)
+}
+
+/// Parse a response line, skipping blank ones and surfacing server errors
+fn parse_line(line: &str) -> NodeSpaceResult<Option<GenerateLine>> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let parsed: GenerateLine = serde_json::from_str(line)
+        .map_err(|e| ollama_error(format!("invalid response line {:?}: {}", line, e)))?;
+    match parsed.error {
+        Some(error) => Err(ollama_error(error)),
+        None => Ok(Some(parsed)),
+    }
+}
+
+fn ollama_error(message: impl std::fmt::Display) -> NodeSpaceError {
+    NodeSpaceError::InternalError {
+        message: format!("Ollama request failed: {}", message),
+        service: "core-logic".to_string(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::oneshot;
+
+    pub(crate) fn chunk(data: &str) -> String {
+        format!("{:x}\r\n{}\r\n", data.len(), data)
+    }
+
+    /// Accept one request, returning the connection and the JSON body
+    pub(crate) async fn accept_request(listener: &TcpListener) -> (TcpStream, serde_json::Value) {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut reader = BufReader::new(socket);
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).await.unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some(length) = header.to_lowercase().strip_prefix("content-length:") {
+                content_length = length.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).await.unwrap();
+        (reader.into_inner(), serde_json::from_slice(&body).unwrap())
+    }
+
+    fn request(prompt: &str) -> TextGenerationRequest {
+        TextGenerationRequest {
+            prompt: prompt.to_string(),
+            max_tokens: 64,
+            temperature: 0.2,
+            context_window: 4096,
+            conversation_mode: false,
+            rag_context: None,
+            enable_link_generation: false,
+            node_metadata: vec![],
+        }
+    }
+
+    pub(crate) async fn local_server() -> (TcpListener, OllamaClient) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let client = OllamaClient::new(&url, DEFAULT_OLLAMA_MODEL)
+            .unwrap()
+            .with_timeout(Duration::from_secs(5))
+            .unwrap();
+        (listener, client)
+    }
+
+    #[tokio::test]
+    async fn test_first_chunk_arrives_before_generation_finishes() {
+        let (listener, client) = local_server().await;
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            let (mut socket, body) = accept_request(&listener).await;
+            let head = "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nTransfer-Encoding: chunked\r\n\r\n";
+            socket.write_all(head.as_bytes()).await.unwrap();
+            let first = chunk("{\"response\":\"The launch\",\"done\":false}\n");
+            socket.write_all(first.as_bytes()).await.unwrap();
+            // The rest is only produced once the client has seen the first chunk
+            release_rx.await.unwrap();
+            let rest = [
+                chunk("{\"response\":\" is on Monday\",\"done\":false}\n{\"response\":\".\","),
+                chunk("\"done\":true}\n"),
+                "0\r\n\r\n".to_string(),
+            ]
+            .concat();
+            socket.write_all(rest.as_bytes()).await.unwrap();
+            body
+        });
+
+        let (tx, mut rx) = mpsc::channel(8);
+        let generation =
+            tokio::spawn(async move { client.generate_stream(request("When?"), tx).await });
+
+        let first = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("first chunk before the server finished");
+        assert_eq!(first.as_deref(), Some("The launch"));
+        assert!(!generation.is_finished());
+
+        release_tx.send(()).unwrap();
+        let answer = generation.await.unwrap().unwrap();
+        assert_eq!(answer.as_deref(), Some("The launch is on Monday."));
+        let mut rest = Vec::new();
+        while let Some(text) = rx.recv().await {
+            rest.push(text);
+        }
+        assert_eq!(rest, vec![" is on Monday", "."]);
+
+        let body = server.await.unwrap();
+        assert_eq!(body["model"], "gemma3:12b");
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["options"]["num_predict"], 64);
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_are_returned() {
+        let (listener, client) = local_server().await;
+        tokio::spawn(async move {
+            let (mut socket, _) = accept_request(&listener).await;
+            let body = "{\"error\":\"model 'gemma3:12b' not found\"}";
+            let response = format!(
+                "HTTP/1.1 404 Not Found\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let (tx, _rx) = mpsc::channel(8);
+        match client.generate_stream(request("When?"), tx).await {
+            Err(NodeSpaceError::InternalError { message, .. }) => {
+                assert!(message.contains("HTTP 404") && message.contains("not found"));
+            }
+            other => panic!("expected an Ollama error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_base_url_must_be_http() {
+        let client = OllamaClient::new("http://localhost:11434/", "m").unwrap();
+        assert_eq!(
+            client.endpoint("api/generate"),
+            "http://localhost:11434/api/generate"
+        );
+        let client = OllamaClient::new("https://gpu-box/ollama", "m").unwrap();
+        assert_eq!(
+            client.endpoint("api/generate"),
+            "https://gpu-box/ollama/api/generate"
+        );
+        assert!(OllamaClient::new("localhost:11434", "m").is_err());
+        assert!(OllamaClient::new("ftp://localhost", "m").is_err());
+    }
+}
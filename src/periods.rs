@@ -0,0 +1,437 @@
+//! Week, month and year period nodes above date nodes
+//!
+//! Period nodes are optional containers with predictable IDs: `2026-W42` for
+//! an ISO week, `2026-10` for a month and `2026` for a year. Like date nodes
+//! they are roots (`root_id = None`) that hold their own notes. Date nodes are
+//! not moved under them; a period rolls up the date nodes whose dates it
+//! covers, which are derived from the period itself.
+
+use crate::date_range::{date_node_id, CalendarSummary};
+use crate::{
+    CoreLogic, DataStore, HierarchicalNode, HierarchyComputation, NLPEngine, NodeSpaceService,
+};
+use chrono::{Datelike, NaiveDate, Weekday};
+use nodespace_core_types::{Node, NodeId, NodeSpaceError, NodeSpaceResult, ValidationError};
+use nodespace_data_store::NodeType;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Granularity of a period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeriodKind {
+    /// ISO 8601 week, Monday to Sunday
+    Week,
+    Month,
+    Year,
+}
+
+impl PeriodKind {
+    /// Node type of period nodes of this kind
+    pub fn node_type(&self) -> &'static str {
+        match self {
+            PeriodKind::Week => "week",
+            PeriodKind::Month => "month",
+            PeriodKind::Year => "year",
+        }
+    }
+}
+
+/// Calendar period covering a contiguous range of dates
+///
+/// Periods are valid by construction: build them with `containing`, `week`,
+/// `month`, `year` or `parse`. Deserialization applies the same checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "PeriodFields", into = "PeriodFields")]
+pub struct Period(PeriodFields);
+
+/// Serialized form of a period, checked when converted into one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum PeriodFields {
+    /// ISO week `week` of ISO week-numbering year `year`
+    Week {
+        year: i32,
+        week: u32,
+    },
+    Month {
+        year: i32,
+        month: u32,
+    },
+    Year {
+        year: i32,
+    },
+}
+
+impl TryFrom<PeriodFields> for Period {
+    type Error = String;
+
+    fn try_from(fields: PeriodFields) -> Result<Self, Self::Error> {
+        let period = match fields {
+            PeriodFields::Week { year, week } => Period::week(year, week),
+            PeriodFields::Month { year, month } => Period::month(year, month),
+            PeriodFields::Year { year } => Period::year(year),
+        };
+        period.ok_or_else(|| format!("invalid period {:?}", fields))
+    }
+}
+
+impl From<Period> for PeriodFields {
+    fn from(period: Period) -> Self {
+        period.0
+    }
+}
+
+impl Period {
+    /// Period of the given kind that contains `date`
+    pub fn containing(kind: PeriodKind, date: NaiveDate) -> Self {
+        match kind {
+            PeriodKind::Week => {
+                let week = date.iso_week();
+                Period(PeriodFields::Week {
+                    year: week.year(),
+                    week: week.week(),
+                })
+            }
+            PeriodKind::Month => Period(PeriodFields::Month {
+                year: date.year(),
+                month: date.month(),
+            }),
+            PeriodKind::Year => Period(PeriodFields::Year { year: date.year() }),
+        }
+    }
+
+    /// ISO week, or `None` if the year has no such week
+    pub fn week(year: i32, week: u32) -> Option<Self> {
+        NaiveDate::from_isoywd_opt(year, week, Weekday::Mon)?;
+        Some(Period(PeriodFields::Week { year, week }))
+    }
+
+    /// Month, or `None` for an invalid month
+    pub fn month(year: i32, month: u32) -> Option<Self> {
+        NaiveDate::from_ymd_opt(year, month, 1)?;
+        Some(Period(PeriodFields::Month { year, month }))
+    }
+
+    /// Year, or `None` outside the supported calendar range
+    pub fn year(year: i32) -> Option<Self> {
+        NaiveDate::from_ymd_opt(year, 12, 31)?;
+        Some(Period(PeriodFields::Year { year }))
+    }
+
+    /// Parse a period node ID such as `2026-W42`, `2026-10` or `2026`
+    pub fn parse(id: &str) -> Option<Self> {
+        fn digits(text: &str, len: usize) -> Option<&str> {
+            (text.len() == len && text.bytes().all(|b| b.is_ascii_digit())).then_some(text)
+        }
+        let mut parts = id.splitn(2, '-');
+        let year: i32 = digits(parts.next()?, 4)?.parse().ok()?;
+        match parts.next() {
+            None => Self::year(year),
+            Some(rest) => match rest.strip_prefix('W') {
+                Some(week) => Self::week(year, digits(week, 2)?.parse().ok()?),
+                None => Self::month(year, digits(rest, 2)?.parse().ok()?),
+            },
+        }
+    }
+
+    pub fn kind(&self) -> PeriodKind {
+        match self.0 {
+            PeriodFields::Week { .. } => PeriodKind::Week,
+            PeriodFields::Month { .. } => PeriodKind::Month,
+            PeriodFields::Year { .. } => PeriodKind::Year,
+        }
+    }
+
+    /// Predictable ID of the period node
+    pub fn node_id(&self) -> NodeId {
+        NodeId::from_string(self.to_string())
+    }
+
+    /// First day of the period
+    pub fn start(&self) -> NaiveDate {
+        match self.0 {
+            PeriodFields::Week { year, week } => {
+                NaiveDate::from_isoywd_opt(year, week, Weekday::Mon)
+            }
+            PeriodFields::Month { year, month } => NaiveDate::from_ymd_opt(year, month, 1),
+            PeriodFields::Year { year } => NaiveDate::from_ymd_opt(year, 1, 1),
+        }
+        .expect("periods are validated on construction")
+    }
+
+    /// Last day of the period
+    pub fn end(&self) -> NaiveDate {
+        match self.0 {
+            PeriodFields::Week { year, week } => {
+                NaiveDate::from_isoywd_opt(year, week, Weekday::Sun)
+            }
+            PeriodFields::Month { year, month } => {
+                crate::date_range::month_bounds(year, month).map(|(_, end)| end)
+            }
+            PeriodFields::Year { year } => NaiveDate::from_ymd_opt(year, 12, 31),
+        }
+        .expect("periods are validated on construction")
+    }
+
+    /// Dates in the period, in order
+    pub fn days(&self) -> impl Iterator<Item = NaiveDate> {
+        let end = self.end();
+        self.start()
+            .iter_days()
+            .take_while(move |date| *date <= end)
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        (self.start()..=self.end()).contains(&date)
+    }
+
+    /// Period of the same kind right after this one
+    pub fn next(&self) -> Option<Self> {
+        Some(Self::containing(self.kind(), self.end().succ_opt()?))
+    }
+
+    /// Period of the same kind right before this one
+    pub fn previous(&self) -> Option<Self> {
+        Some(Self::containing(self.kind(), self.start().pred_opt()?))
+    }
+
+    /// Enclosing year; ISO weeks belong to their week-numbering year
+    pub fn parent(&self) -> Option<Self> {
+        match self.0 {
+            PeriodFields::Week { year, .. } | PeriodFields::Month { year, .. } => Self::year(year),
+            PeriodFields::Year { .. } => None,
+        }
+    }
+
+    /// Human-readable name, e.g. "Week 42, 2026" or "October 2026"
+    pub fn label(&self) -> String {
+        match self.0 {
+            PeriodFields::Week { year, week } => format!("Week {}, {}", week, year),
+            PeriodFields::Month { .. } => self.start().format("%B %Y").to_string(),
+            PeriodFields::Year { year } => year.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            PeriodFields::Week { year, week } => write!(f, "{:04}-W{:02}", year, week),
+            PeriodFields::Month { year, month } => write!(f, "{:04}-{:02}", year, month),
+            PeriodFields::Year { year } => write!(f, "{:04}", year),
+        }
+    }
+}
+
+/// A period's own notes together with the days it rolls up
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeriodOverview {
+    pub period: Period,
+    pub label: String,
+    /// Period node, when it has been created
+    pub node_id: Option<NodeId>,
+    /// Notes attached directly to the period node
+    pub notes: Vec<HierarchicalNode>,
+    /// Node counts of the date nodes covered by the period
+    pub days: CalendarSummary,
+}
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Period node ID if a node of the right type exists under the predictable ID
+    pub(crate) async fn find_period_node_by_id(
+        &self,
+        period: Period,
+    ) -> NodeSpaceResult<Option<NodeId>> {
+        let node_id = period.node_id();
+        let found = self
+            .data_store
+            .get_node(&node_id)
+            .await?
+            .filter(|node| node.r#type == period.kind().node_type());
+        Ok(found.map(|node| node.id))
+    }
+
+    pub(crate) async fn create_period_node(&self, period: Period) -> NodeSpaceResult<NodeId> {
+        // Period nodes are organizational roots, like date nodes
+        let mut node = Node::new(
+            period.kind().node_type().to_string(),
+            serde_json::Value::Null,
+        );
+        node.id = period.node_id();
+        node.root_id = None;
+
+        let node_id = self.store_node_with_hierarchical_embedding(node).await?;
+        log::info!("🗓️ Created {} node {}", period.kind().node_type(), node_id);
+        Ok(node_id)
+    }
+
+    /// Create a note under a period node, creating the period node if needed
+    pub async fn create_node_for_period(
+        &self,
+        period: Period,
+        content: &str,
+        node_type: NodeType,
+        metadata: Option<serde_json::Value>,
+    ) -> NodeSpaceResult<NodeId> {
+        if !self.is_ready().await {
+            return Err(NodeSpaceError::InternalError {
+                message: format!("Service not ready: {:?}", self.get_state().await),
+                service: "core-logic".to_string(),
+            });
+        }
+
+        let period_node_id = self.ensure_period_node_exists(period).await?;
+
+        let mut node = Node::new(
+            format!("{:?}", node_type).to_lowercase(),
+            serde_json::Value::String(content.to_string()),
+        );
+        node.metadata = metadata;
+        node.parent_id = Some(period_node_id.clone());
+        node.root_id = Some(period_node_id);
+
+        let node_id = self.store_node_with_hierarchical_embedding(node).await?;
+        self.invalidate_hierarchy_cache().await;
+        Ok(node_id)
+    }
+
+    /// Period notes and the node counts of the days it covers
+    pub async fn get_period_overview(&self, period: Period) -> NodeSpaceResult<PeriodOverview> {
+        let node_id = self.find_period_node(period).await?;
+        let notes = match &node_id {
+            Some(id) => {
+                let tree_nodes = self.data_store.get_nodes_by_root(id).await?;
+                self.build_hierarchical_tree_from_flat_list(tree_nodes, id, 0)?
+            }
+            None => vec![],
+        };
+        let days = self
+            .get_calendar_summary(period.start(), period.end())
+            .await?;
+
+        Ok(PeriodOverview {
+            period,
+            label: period.label(),
+            node_id,
+            notes,
+            days,
+        })
+    }
+
+    /// Date node IDs covered by a period, in date order
+    pub fn period_date_node_ids(&self, period: Period) -> Vec<NodeId> {
+        period.days().map(date_node_id).collect()
+    }
+}
+
+impl FromStr for Period {
+    type Err = NodeSpaceError;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        Period::parse(id).ok_or_else(|| {
+            NodeSpaceError::Validation(ValidationError::InvalidFormat {
+                field: "period".to_string(),
+                expected: "an ISO week, month or year ID".to_string(),
+                actual: id.to_string(),
+                examples: vec![
+                    "2026-W42".to_string(),
+                    "2026-10".to_string(),
+                    "2026".to_string(),
+                ],
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_period_ids_round_trip_and_reject_dates() {
+        let day = date(2026, 10, 18);
+        let week = Period::containing(PeriodKind::Week, day);
+        let month = Period::containing(PeriodKind::Month, day);
+        let year = Period::containing(PeriodKind::Year, day);
+
+        assert_eq!(week.to_string(), "2026-W42");
+        assert_eq!(month.to_string(), "2026-10");
+        assert_eq!(year.to_string(), "2026");
+        for period in [week, month, year] {
+            assert_eq!(Period::parse(&period.to_string()), Some(period));
+        }
+
+        assert_eq!(Period::parse("2026-10-18"), None);
+        assert_eq!(Period::parse("2026-13"), None);
+        assert_eq!(Period::parse("2025-W53"), None);
+        assert_eq!(Period::parse("26-W01"), None);
+        assert!("2026-W54".parse::<Period>().is_err());
+    }
+
+    #[test]
+    fn test_deserialized_periods_are_validated() {
+        let week: Period =
+            serde_json::from_str(r#"{"kind":"week","year":2026,"week":42}"#).unwrap();
+        assert_eq!(week, Period::week(2026, 42).unwrap());
+        assert_eq!(
+            serde_json::to_value(week).unwrap(),
+            serde_json::json!({"kind": "week", "year": 2026, "week": 42})
+        );
+
+        for invalid in [
+            r#"{"kind":"week","year":2026,"week":60}"#,
+            r#"{"kind":"week","year":2025,"week":53}"#,
+            r#"{"kind":"month","year":2026,"month":13}"#,
+            r#"{"kind":"year","year":999999}"#,
+        ] {
+            assert!(
+                serde_json::from_str::<Period>(invalid).is_err(),
+                "{} should be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_period_bounds_follow_iso_weeks_and_month_lengths() {
+        let week = Period::parse("2026-W42").unwrap();
+        assert_eq!(
+            (week.start(), week.end()),
+            (date(2026, 10, 12), date(2026, 10, 18))
+        );
+        assert_eq!(week.days().count(), 7);
+
+        // 2027-01-01 is a Friday, so it belongs to the last ISO week of 2026
+        let new_year = Period::containing(PeriodKind::Week, date(2027, 1, 1));
+        assert_eq!(new_year.to_string(), "2026-W53");
+        assert_eq!(new_year.parent(), Period::year(2026));
+
+        let february = Period::month(2028, 2).unwrap();
+        assert_eq!(february.days().count(), 29);
+        assert!(february.contains(date(2028, 2, 29)));
+        assert!(!february.contains(date(2028, 3, 1)));
+        assert_eq!(Period::year(2028).unwrap().days().count(), 366);
+    }
+
+    #[test]
+    fn test_navigation_crosses_year_boundaries() {
+        let last_week = Period::parse("2026-W53").unwrap();
+        assert_eq!(last_week.next().unwrap().to_string(), "2027-W01");
+        assert_eq!(
+            Period::parse("2027-W01").unwrap().previous(),
+            Some(last_week)
+        );
+
+        let december = Period::month(2026, 12).unwrap();
+        assert_eq!(december.next(), Period::month(2027, 1));
+        assert_eq!(december.label(), "December 2026");
+        assert_eq!(Period::year(2026).unwrap().previous(), Period::year(2025));
+        assert_eq!(last_week.kind().node_type(), "week");
+    }
+}
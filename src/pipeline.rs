@@ -0,0 +1,968 @@
+//! Unified RAG pipeline
+//!
+//! Every answer path runs the same stages:
+//! retrieve → expand → rerank → pack → generate → verify (→ suggest).
+//! `RagPipeline` holds the stage configuration; the public query methods are
+//! presets that only differ in retrieval limit, prompt style, answer length and
+//! which optional stages run. Each stage is timed and reported with the answer.
+
+use crate::citations;
+use crate::context_budget::{
+    self, ContextBudgetReport, ContextCandidate, ContextPlan, TokenBudget, CONTEXT_SEPARATOR,
+};
+use crate::context_expansion::{self, HierarchyContextOptions};
+use crate::faithfulness::{self, FaithfulnessMode, FaithfulnessOptions, FaithfulnessReport};
+use crate::generation_options::{self, GenerationOptions, ResolvedGeneration};
+use crate::prompts::{names, PromptRegistry, PromptVersion, RenderedPrompt};
+use crate::reranker::lexical_overlap_score;
+use crate::{
+    constants, log_invalid_citations, AnswerCitation, ConfidenceBreakdown, CoreLogic, DataStore,
+    NLPEngine, NodeSpaceService, QueryResponse, Reranker, SearchResult, TextGenerator,
+    TokenCounter,
+};
+use futures::stream::{self, StreamExt};
+use nodespace_core_types::{Node, NodeId, NodeSpaceError, NodeSpaceResult};
+use nodespace_nlp_engine::{RAGContext, TextGenerationRequest};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Retrieved hits expanded with their hierarchy at once
+const MAX_CONCURRENT_EXPANSIONS: usize = 8;
+
+/// Pipeline stages in execution order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineStage {
+    Retrieve,
+    Expand,
+    Rerank,
+    Pack,
+    Generate,
+    Verify,
+    Suggest,
+}
+
+/// Wall-clock time spent in one stage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageTiming {
+    pub stage: PipelineStage,
+    pub duration_ms: u64,
+}
+
+/// Collects stage timings for one pipeline run
+#[derive(Debug, Clone)]
+pub struct StageTimer {
+    started: Instant,
+    stages: Vec<StageTiming>,
+}
+
+impl Default for StageTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StageTimer {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            stages: Vec::new(),
+        }
+    }
+
+    /// Record a stage that started at `since`
+    pub fn record(&mut self, stage: PipelineStage, since: Instant) {
+        self.stages.push(StageTiming {
+            stage,
+            duration_ms: since.elapsed().as_millis() as u64,
+        });
+    }
+
+    /// Milliseconds since the run started
+    pub fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    pub fn stages(&self) -> &[StageTiming] {
+        &self.stages
+    }
+
+    pub fn into_stages(self) -> Vec<StageTiming> {
+        self.stages
+    }
+}
+
+/// Where the retrieve stage gets its candidates
+#[derive(Debug, Clone)]
+pub enum Retrieval {
+    /// Semantic search for the query
+    Search { limit: usize },
+    /// Caller-chosen nodes, ranked in the given order
+    Nodes(Vec<NodeId>),
+    /// A fixed set of nodes (a date, a subtree, a selection)
+    ///
+    /// The whole scope is packed in order when it fits the context budget;
+    /// otherwise the `limit` best matches within the scope are retrieved.
+    Scope { nodes: Vec<Node>, limit: usize },
+}
+
+/// Prompt shape rendered around the packed context
+#[derive(Debug, Clone, PartialEq)]
+pub enum PromptStyle {
+    /// Conversational answer for `process_query`
+    Contextual,
+    /// Document-style answer for desktop AI responses
+    Document,
+    /// Follow-up in a multi-turn conversation
+    Conversation { history: String },
+    /// Question about a described set of notes, such as a page or a day
+    Scoped { scope: String },
+}
+
+impl PromptStyle {
+    /// Render the prompt for `query` around the packed context
+    pub fn render(
+        &self,
+        prompts: &PromptRegistry,
+        query: &str,
+        plan: &ContextPlan,
+    ) -> NodeSpaceResult<RenderedPrompt> {
+        if plan.is_empty() {
+            return self.render_without_context(prompts, query);
+        }
+        self.render_with_context(
+            prompts,
+            query,
+            &citations::number_sources(&plan.context_texts()),
+        )
+    }
+
+    /// Prompt with empty context, used to charge the scaffold against the budget
+    fn scaffold(&self, prompts: &PromptRegistry, query: &str) -> NodeSpaceResult<RenderedPrompt> {
+        self.render_with_context(prompts, query, "")
+    }
+
+    fn render_with_context(
+        &self,
+        prompts: &PromptRegistry,
+        query: &str,
+        context_text: &str,
+    ) -> NodeSpaceResult<RenderedPrompt> {
+        let vars = [("context", context_text), ("question", query)];
+        match self {
+            PromptStyle::Contextual => prompts.render_rag(names::RAG_CONTEXTUAL, &vars),
+            PromptStyle::Document => prompts.render_rag(names::RAG_DOCUMENT, &vars),
+            PromptStyle::Conversation { history } => prompts.render_rag(
+                names::RAG_CONVERSATION,
+                &[
+                    ("history", history),
+                    ("context", context_text),
+                    ("question", query),
+                ],
+            ),
+            PromptStyle::Scoped { scope } => prompts.render_rag(
+                names::RAG_SCOPED,
+                &[
+                    ("scope", scope),
+                    ("context", context_text),
+                    ("question", query),
+                ],
+            ),
+        }
+    }
+
+    fn render_without_context(
+        &self,
+        prompts: &PromptRegistry,
+        query: &str,
+    ) -> NodeSpaceResult<RenderedPrompt> {
+        let vars = [("question", query)];
+        match self {
+            PromptStyle::Contextual => prompts.render_rag(names::RAG_CONTEXTUAL_GENERAL, &vars),
+            PromptStyle::Document => prompts.render_rag(names::RAG_DOCUMENT_GENERAL, &vars),
+            PromptStyle::Conversation { .. } => self.render_with_context(prompts, query, ""),
+            PromptStyle::Scoped { scope } => prompts.render_rag(
+                names::RAG_SCOPED_EMPTY,
+                &[("scope", scope), ("question", query)],
+            ),
+        }
+    }
+}
+
+/// Output of the retrieve → pack stages, ready for generation
+#[derive(Debug, Clone)]
+pub struct PreparedQuery {
+    pub request: TextGenerationRequest,
+    /// Everything retrieved (after reranking), including nodes dropped by the budget
+    pub retrieved: Vec<SearchResult>,
+    pub plan: ContextPlan,
+    /// Template the prompt was rendered from
+    pub prompt_template: PromptVersion,
+    pub generation: ResolvedGeneration,
+    pub timer: StageTimer,
+}
+
+/// Answer produced by a full pipeline run
+#[derive(Debug, Clone)]
+pub struct PipelineOutput {
+    pub answer: String,
+    /// Sources in the order they were numbered in the prompt
+    pub sources: Vec<NodeId>,
+    pub retrieved: Vec<SearchResult>,
+    pub citations: Vec<AnswerCitation>,
+    pub confidence: ConfidenceBreakdown,
+    pub faithfulness: Option<FaithfulnessReport>,
+    pub related_queries: Vec<String>,
+    pub context_budget: ContextBudgetReport,
+    pub prompt_template: PromptVersion,
+    pub generation: ResolvedGeneration,
+    pub stage_timings: Vec<StageTiming>,
+    pub total_time_ms: u64,
+}
+
+impl From<PipelineOutput> for QueryResponse {
+    fn from(output: PipelineOutput) -> Self {
+        QueryResponse {
+            answer: output.answer,
+            sources: output.sources,
+            confidence: output.confidence.overall,
+            related_queries: output.related_queries,
+            confidence_breakdown: output.confidence,
+            context_budget: output.context_budget,
+            citations: output.citations,
+            faithfulness: output.faithfulness,
+            prompt_template: Some(output.prompt_template),
+            generation: Some(output.generation),
+            stage_timings: output.stage_timings,
+        }
+    }
+}
+
+/// Configurable RAG pipeline over a service's data store and NLP engine
+pub struct RagPipeline<'a, D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> {
+    service: &'a NodeSpaceService<D, N>,
+    retrieval: Retrieval,
+    retrieval_query: Option<String>,
+    reranker: Option<Arc<dyn Reranker>>,
+    token_counter: Arc<dyn TokenCounter>,
+    hierarchy_context: HierarchyContextOptions,
+    prompt: PromptStyle,
+    max_tokens: usize,
+    generation: GenerationOptions,
+    conversation_mode: bool,
+    enable_link_generation: bool,
+    faithfulness: FaithfulnessOptions,
+    related_queries: bool,
+    offline_fallback: bool,
+}
+
+impl<'a, D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> RagPipeline<'a, D, N> {
+    /// Pipeline with the service's stages and `process_query` defaults
+    pub fn new(service: &'a NodeSpaceService<D, N>) -> Self {
+        Self {
+            service,
+            retrieval: Retrieval::Search {
+                limit: constants::DEFAULT_SEARCH_LIMIT,
+            },
+            retrieval_query: None,
+            reranker: service.reranker.clone(),
+            token_counter: service.token_counter.clone(),
+            hierarchy_context: service.hierarchy_context.clone(),
+            prompt: PromptStyle::Contextual,
+            max_tokens: constants::DEFAULT_RAG_MAX_TOKENS,
+            generation: GenerationOptions::default(),
+            conversation_mode: false,
+            enable_link_generation: false,
+            faithfulness: FaithfulnessOptions::default(),
+            related_queries: false,
+            offline_fallback: false,
+        }
+    }
+
+    pub fn with_retrieval(mut self, retrieval: Retrieval) -> Self {
+        self.retrieval = retrieval;
+        self
+    }
+
+    /// Retrieve with a different query than the one answered (e.g. a rewritten follow-up)
+    pub fn with_retrieval_query(mut self, query: impl Into<String>) -> Self {
+        self.retrieval_query = Some(query.into());
+        self
+    }
+
+    /// Override the service reranker; `None` disables the rerank stage
+    pub fn with_reranker(mut self, reranker: Option<Arc<dyn Reranker>>) -> Self {
+        self.reranker = reranker;
+        self
+    }
+
+    pub fn with_token_counter(mut self, token_counter: Arc<dyn TokenCounter>) -> Self {
+        self.token_counter = token_counter;
+        self
+    }
+
+    pub fn with_hierarchy_context(mut self, options: HierarchyContextOptions) -> Self {
+        self.hierarchy_context = options;
+        self
+    }
+
+    pub fn with_prompt(mut self, prompt: PromptStyle) -> Self {
+        self.prompt = prompt;
+        self
+    }
+
+    /// Default answer length of this preset, reserved in the context budget
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Per-call overrides of the configured generation parameters
+    pub fn with_generation_options(mut self, options: GenerationOptions) -> Self {
+        self.generation = options;
+        self
+    }
+
+    pub fn with_conversation_mode(mut self, enabled: bool) -> Self {
+        self.conversation_mode = enabled;
+        self
+    }
+
+    pub fn with_link_generation(mut self, enabled: bool) -> Self {
+        self.enable_link_generation = enabled;
+        self
+    }
+
+    pub fn with_faithfulness(mut self, options: FaithfulnessOptions) -> Self {
+        self.faithfulness = options;
+        self
+    }
+
+    /// Run the suggest stage for grounded follow-up questions
+    pub fn with_related_queries(mut self, enabled: bool) -> Self {
+        self.related_queries = enabled;
+        self
+    }
+
+    /// Answer generation failures with the configured offline fallback
+    ///
+    /// Off by default: a failed generation is returned as an error. The
+    /// `process_query` preset enables it. When the policy produces a canned
+    /// answer, the verify and suggest stages are skipped for it.
+    pub fn with_offline_fallback(mut self, enabled: bool) -> Self {
+        self.offline_fallback = enabled;
+        self
+    }
+
+    /// Run every stage and assemble the answer
+    pub async fn run(&self, query: &str) -> NodeSpaceResult<PipelineOutput> {
+        let PreparedQuery {
+            request,
+            retrieved,
+            plan,
+            prompt_template,
+            generation,
+            mut timer,
+        } = self.prepare(query).await?;
+        let sources = plan.source_ids();
+
+        let started = Instant::now();
+        let generated = self.service.generate_answer(request, &sources).await;
+        timer.record(PipelineStage::Generate, started);
+        let answer = match answer_or_fallback(generated, || {
+            self.offline_fallback
+                .then(|| self.service.offline_fallback_answer(&sources))
+                .flatten()
+        })? {
+            Answer::Generated(answer) => answer,
+            Answer::Fallback(answer) => {
+                return Ok(PipelineOutput {
+                    answer,
+                    sources,
+                    retrieved,
+                    citations: Vec::new(),
+                    confidence: fallback_confidence(),
+                    faithfulness: None,
+                    related_queries: Vec::new(),
+                    context_budget: plan.report,
+                    prompt_template,
+                    generation,
+                    total_time_ms: timer.elapsed_ms(),
+                    stage_timings: timer.into_stages(),
+                });
+            }
+        };
+
+        let started = Instant::now();
+        let (answer, faithfulness) = self.verify(answer, &plan).await;
+        let citations = citations::extract_citations(&answer, &sources);
+        log_invalid_citations(&citations);
+        let confidence = self
+            .service
+            .assess_answer_confidence(&answer, &plan, &citations)
+            .await;
+        timer.record(PipelineStage::Verify, started);
+
+        let related_queries = if self.related_queries {
+            let started = Instant::now();
+            let suggestions = self
+                .service
+                .generate_related_queries(self.retrieval_query(query), &answer, &plan)
+                .await;
+            timer.record(PipelineStage::Suggest, started);
+            suggestions
+        } else {
+            Vec::new()
+        };
+
+        log::info!(
+            "✅ RAG pipeline complete in {}ms: {} sources, confidence {:.2}",
+            timer.elapsed_ms(),
+            sources.len(),
+            confidence.overall
+        );
+
+        Ok(PipelineOutput {
+            answer,
+            sources,
+            retrieved,
+            citations,
+            confidence,
+            faithfulness,
+            related_queries,
+            context_budget: plan.report,
+            prompt_template,
+            generation,
+            total_time_ms: timer.elapsed_ms(),
+            stage_timings: timer.into_stages(),
+        })
+    }
+
+    /// Run retrieve → expand → rerank → pack and build the generation request
+    pub async fn prepare(&self, query: &str) -> NodeSpaceResult<PreparedQuery> {
+        if !self.service.is_ready().await {
+            let state = self.service.get_state().await;
+            return Err(NodeSpaceError::InternalError {
+                message: format!("Service not ready: {:?}", state),
+                service: "core-logic".to_string(),
+            });
+        }
+
+        // Invalid parameters are rejected before any retrieval work
+        let generation = generation_options::resolve_generation(
+            &self.generation,
+            &self.service.config.performance_config,
+            &self.service.model_limits,
+            self.max_tokens,
+        )?;
+
+        let budget = TokenBudget {
+            context_window: generation.context_window,
+            reserved_output_tokens: generation.max_tokens,
+            prompt_tokens: self
+                .token_counter
+                .count_tokens(&self.prompt.scaffold(&self.service.prompts, query)?.text),
+        };
+
+        let mut timer = StageTimer::new();
+        let retrieval_query = self.retrieval_query(query);
+        log::info!("🚀 RAG pipeline for '{}'", query);
+
+        // A scope that fits the budget is used whole, without retrieval stages
+        let whole_scope = self.whole_scope(&budget);
+        let whole_scope_used = whole_scope.is_some();
+        let retrieved = match whole_scope {
+            Some(results) => {
+                log::info!("   Using all {} scope nodes as context", results.len());
+                results
+            }
+            None => {
+                let started = Instant::now();
+                let retrieved = self.retrieve(retrieval_query).await?;
+                timer.record(PipelineStage::Retrieve, started);
+                retrieved
+            }
+        };
+        let expand = self.hierarchy_context.enabled && !whole_scope_used;
+
+        let started = Instant::now();
+        let mut expanded: Vec<_> = if expand {
+            // Hits are expanded concurrently; `buffered` keeps retrieval order
+            let expanded = stream::iter(&retrieved)
+                .map(|result| {
+                    self.service
+                        .expand_search_hit(result, &self.hierarchy_context)
+                })
+                .buffered(MAX_CONCURRENT_EXPANSIONS)
+                .collect()
+                .await;
+            timer.record(PipelineStage::Expand, started);
+            expanded
+        } else {
+            Vec::new()
+        };
+
+        // Caller-chosen nodes and whole scopes keep their order
+        let ranked = match self.retrieval {
+            Retrieval::Search { .. } => true,
+            Retrieval::Scope { .. } => !whole_scope_used,
+            Retrieval::Nodes(_) => false,
+        };
+        let retrieved = if ranked {
+            let started = Instant::now();
+            let reranked = rerank_with(
+                self.reranker.as_deref(),
+                &self.service.nlp_engine,
+                &self.service.prompts,
+                retrieval_query,
+                retrieved,
+            )
+            .await;
+            timer.record(PipelineStage::Rerank, started);
+            reranked
+        } else {
+            retrieved
+        };
+
+        let started = Instant::now();
+        let plan = if expand {
+            // Expanded blocks take the reranked scores of their hits
+            let scores: HashMap<&NodeId, f32> =
+                retrieved.iter().map(|r| (&r.node_id, r.score)).collect();
+            for hit in expanded.iter_mut() {
+                if let Some(score) = scores.get(&hit.node.id) {
+                    hit.score = *score;
+                }
+            }
+            context_expansion::plan_expanded_context(self.token_counter.as_ref(), expanded, budget)
+        } else {
+            let candidates = retrieved
+                .iter()
+                .filter_map(|result| {
+                    Some(ContextCandidate {
+                        node_id: result.node_id.clone(),
+                        content: result.node.content.as_str()?.to_string(),
+                        score: result.score,
+                    })
+                })
+                .collect();
+            context_budget::plan_context(self.token_counter.as_ref(), candidates, budget)
+        };
+        log::info!(
+            "   Context budget: {} of {} tokens used, {} sources included, {} dropped, {} deduplicated",
+            plan.report.used_tokens,
+            plan.report.budget_tokens,
+            plan.report.included.len(),
+            plan.report.dropped.len(),
+            plan.report.deduplicated.len()
+        );
+
+        let prompt = self.prompt.render(&self.service.prompts, query, &plan)?;
+        log::info!(
+            "   Prompt template: {} ({})",
+            prompt.template.name,
+            prompt.template.version
+        );
+        let request = self.build_request(prompt.text, &plan, &generation);
+        timer.record(PipelineStage::Pack, started);
+
+        Ok(PreparedQuery {
+            request,
+            retrieved,
+            plan,
+            prompt_template: prompt.template,
+            generation,
+            timer,
+        })
+    }
+
+    fn retrieval_query<'q>(&'q self, query: &'q str) -> &'q str {
+        self.retrieval_query.as_deref().unwrap_or(query)
+    }
+
+    /// All scope nodes in scope order, when their content fits the budget
+    fn whole_scope(&self, budget: &TokenBudget) -> Option<Vec<SearchResult>> {
+        let nodes = match &self.retrieval {
+            Retrieval::Scope { nodes, .. } => nodes,
+            _ => return None,
+        };
+
+        let counter = self.token_counter.as_ref();
+        let separator_tokens = counter.count_tokens(CONTEXT_SEPARATOR);
+        let tokens: usize = nodes
+            .iter()
+            .filter_map(|node| node.content.as_str())
+            .enumerate()
+            .map(|(packed, content)| {
+                let tokens = counter.count_tokens(content);
+                context_budget::packing_cost(counter, tokens, packed, separator_tokens)
+            })
+            .sum();
+        if tokens > budget.available_context_tokens() {
+            log::info!(
+                "   Scope of {} nodes needs {} tokens, retrieving within it",
+                nodes.len(),
+                tokens
+            );
+            return None;
+        }
+
+        // Equal scores keep scope order through the stable budget sort
+        Some(
+            nodes
+                .iter()
+                .filter(|node| node.content.is_string())
+                .map(|node| SearchResult {
+                    node_id: node.id.clone(),
+                    node: node.clone(),
+                    score: 1.0,
+                })
+                .collect(),
+        )
+    }
+
+    async fn retrieve(&self, query: &str) -> NodeSpaceResult<Vec<SearchResult>> {
+        match &self.retrieval {
+            Retrieval::Scope { nodes, limit } => {
+                let in_scope: HashSet<&NodeId> = nodes.iter().map(|node| &node.id).collect();
+                let mut results: Vec<SearchResult> = self
+                    .service
+                    .semantic_search(query, constants::DEFAULT_SCOPED_SEARCH_CANDIDATES)
+                    .await?
+                    .into_iter()
+                    .filter(|result| {
+                        in_scope.contains(&result.node_id) && result.node.content.is_string()
+                    })
+                    .take(*limit)
+                    .collect();
+
+                // Scope nodes may be missing from the global top candidates
+                if results.is_empty() {
+                    results = lexical_scope_matches(query, nodes, *limit);
+                }
+                log::info!("   Retrieved {} results within scope", results.len());
+                Ok(results)
+            }
+            Retrieval::Search { limit } => {
+                let results = self.service.semantic_search(query, *limit).await?;
+                log::info!("   Retrieved {} search results", results.len());
+                Ok(results
+                    .into_iter()
+                    .filter(|result| result.node.content.is_string())
+                    .collect())
+            }
+            Retrieval::Nodes(node_ids) => {
+                // Preserve caller ordering through descending positional scores
+                let mut results = Vec::new();
+                for (index, node_id) in node_ids.iter().enumerate() {
+                    if let Some(node) = self.service.data_store.get_node(node_id).await? {
+                        results.push(SearchResult {
+                            node_id: node_id.clone(),
+                            node,
+                            score: 1.0 / (index + 1) as f32,
+                        });
+                    }
+                }
+                Ok(results)
+            }
+        }
+    }
+
+    fn build_request(
+        &self,
+        prompt: String,
+        plan: &ContextPlan,
+        generation: &ResolvedGeneration,
+    ) -> TextGenerationRequest {
+        let (retrieval_confidence, context_summary) = if plan.is_empty() {
+            (
+                constants::BASE_CONFIDENCE_NO_CONTEXT,
+                "General knowledge query",
+            )
+        } else {
+            (
+                constants::BASE_CONFIDENCE_WITH_CONTEXT,
+                "Relevant documents retrieved from knowledge base",
+            )
+        };
+
+        TextGenerationRequest {
+            prompt,
+            max_tokens: generation.max_tokens,
+            temperature: generation.temperature,
+            context_window: generation.context_window,
+            conversation_mode: self.conversation_mode,
+            rag_context: Some(RAGContext {
+                knowledge_sources: plan
+                    .source_ids()
+                    .iter()
+                    .map(|id| format!("node:{}", id.as_str()))
+                    .collect(),
+                retrieval_confidence,
+                context_summary: context_summary.to_string(),
+                suggested_links: vec![],
+            }),
+            enable_link_generation: self.enable_link_generation,
+            node_metadata: vec![],
+        }
+    }
+
+    /// Check answer sentences against the packed context and apply the faithfulness mode
+    async fn verify(
+        &self,
+        answer: String,
+        plan: &ContextPlan,
+    ) -> (String, Option<FaithfulnessReport>) {
+        if self.faithfulness.mode == FaithfulnessMode::Off {
+            return (answer, None);
+        }
+
+        let verifier: Option<&dyn TextGenerator> = if self.faithfulness.verify_with_llm {
+            Some(&self.service.nlp_engine)
+        } else {
+            None
+        };
+        let sentences = faithfulness::check_sentences(
+            verifier,
+            &self.service.prompts,
+            &answer,
+            &plan.context_texts(),
+        )
+        .await;
+        let (verified, report) =
+            faithfulness::apply_faithfulness(&self.faithfulness, &answer, sentences);
+        log::info!(
+            "   Faithfulness: {:?}, {:.0}% of sentences supported",
+            report.outcome,
+            report.supported_ratio * 100.0
+        );
+
+        (verified, Some(report))
+    }
+}
+
+/// Outcome of the generate stage
+#[derive(Debug, PartialEq)]
+enum Answer {
+    Generated(String),
+    /// Canned answer standing in for a failed generation
+    Fallback(String),
+}
+
+/// The generated answer, or the fallback when generation failed and one is available
+fn answer_or_fallback(
+    generated: NodeSpaceResult<String>,
+    fallback: impl FnOnce() -> Option<String>,
+) -> NodeSpaceResult<Answer> {
+    match generated {
+        Ok(answer) => Ok(Answer::Generated(answer)),
+        Err(e) => match fallback() {
+            Some(answer) => {
+                log::warn!("⚠️ Generation failed, using offline fallback: {}", e);
+                Ok(Answer::Fallback(answer))
+            }
+            None => Err(e),
+        },
+    }
+}
+
+/// Confidence of an offline fallback answer, which is not grounded in any source
+fn fallback_confidence() -> ConfidenceBreakdown {
+    ConfidenceBreakdown {
+        reasons: vec!["The answer is a fallback message, not a generated answer".to_string()],
+        ..Default::default()
+    }
+}
+
+/// Scope nodes ranked by term overlap with `query`
+fn lexical_scope_matches(query: &str, nodes: &[Node], limit: usize) -> Vec<SearchResult> {
+    let mut matches: Vec<SearchResult> = nodes
+        .iter()
+        .filter_map(|node| {
+            let score = lexical_overlap_score(query, node.content.as_str()?);
+            (score > 0.0).then(|| SearchResult {
+                node_id: node.id.clone(),
+                node: node.clone(),
+                score,
+            })
+        })
+        .collect();
+    matches.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    matches.truncate(limit);
+    matches
+}
+
+/// Apply `reranker` to the top-N candidates
+///
+/// Candidates beyond top-N keep their retrieval order after the reranked head;
+/// reranker failures fall back to the original ordering.
+pub(crate) async fn rerank_with(
+    reranker: Option<&dyn Reranker>,
+    generator: &dyn TextGenerator,
+    prompts: &PromptRegistry,
+    query: &str,
+    mut results: Vec<SearchResult>,
+) -> Vec<SearchResult> {
+    let reranker = match reranker {
+        Some(reranker) if !results.is_empty() => reranker,
+        _ => return results,
+    };
+
+    let head_len = reranker.top_n().min(results.len());
+    let tail = results.split_off(head_len);
+    let head = results;
+
+    log::info!(
+        "🔀 Reranking top {} of {} candidates",
+        head_len,
+        head_len + tail.len()
+    );
+
+    match reranker
+        .rerank(generator, prompts, query, head.clone())
+        .await
+    {
+        Ok(mut reranked) => {
+            reranked.extend(tail);
+            reranked
+        }
+        Err(e) => {
+            log::warn!("⚠️ Reranking failed, keeping retrieval order: {}", e);
+            let mut original = head;
+            original.extend(tail);
+            original
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{result, StubGenerator};
+    use crate::{DeterministicReranker, OfflineFallback};
+    use serde_json::json;
+
+    #[test]
+    fn test_prompt_styles_number_sources_and_fall_back_without_context() {
+        let plan = ContextPlan {
+            included: vec![ContextCandidate {
+                node_id: NodeId::from_string("a".to_string()),
+                content: "Budget approved".to_string(),
+                score: 1.0,
+            }],
+            ..Default::default()
+        };
+
+        let prompts = PromptRegistry::default();
+
+        let document = PromptStyle::Document
+            .render(&prompts, "What was approved?", &plan)
+            .unwrap();
+        assert!(document
+            .text
+            .starts_with("Context Information:\n[1] Budget approved"));
+        assert!(document.text.contains(citations::CITATION_INSTRUCTION));
+        assert_eq!(document.template.name, names::RAG_DOCUMENT);
+
+        let general = PromptStyle::Document
+            .render(&prompts, "What was approved?", &ContextPlan::default())
+            .unwrap();
+        assert_eq!(
+            general.text,
+            "Answer this question using your knowledge: What was approved?"
+        );
+        assert_eq!(general.template.name, names::RAG_DOCUMENT_GENERAL);
+
+        let conversation = PromptStyle::Conversation {
+            history: "User: When is the launch?".to_string(),
+        }
+        .render(&prompts, "And the budget?", &plan)
+        .unwrap();
+        assert!(conversation.text.contains("User: When is the launch?"));
+        assert!(conversation.text.contains("[1] Budget approved"));
+    }
+
+    #[tokio::test]
+    async fn test_rerank_stage_reorders_head_only() {
+        let reranker = DeterministicReranker::new().with_top_n(2);
+        let results = vec![
+            result("grocery list", 0.9),
+            result("marketing budget", 0.8),
+            result("marketing budget tail", 0.1),
+        ];
+
+        let reranked = rerank_with(
+            Some(&reranker),
+            &StubGenerator::replying(""),
+            &PromptRegistry::default(),
+            "marketing budget",
+            results.clone(),
+        )
+        .await;
+        let contents: Vec<_> = reranked
+            .iter()
+            .map(|r| r.node.content.as_str().unwrap())
+            .collect();
+        assert_eq!(
+            contents,
+            vec!["marketing budget", "grocery list", "marketing budget tail"]
+        );
+
+        // Without a reranker the stage is a pass-through
+        let untouched = rerank_with(
+            None,
+            &StubGenerator::replying(""),
+            &PromptRegistry::default(),
+            "marketing budget",
+            results,
+        )
+        .await;
+        assert_eq!(untouched[0].node.content.as_str(), Some("grocery list"));
+    }
+
+    #[tokio::test]
+    async fn test_offline_generation_answers_with_the_configured_fallback() {
+        let offline = StubGenerator::offline();
+
+        let answer = answer_or_fallback(offline.generate("What was approved?").await, || {
+            OfflineFallback::Cache.answer(2)
+        })
+        .unwrap();
+        assert_eq!(
+            answer,
+            Answer::Fallback("I found 2 related documents but cannot generate a detailed response at this time. Please review the source materials directly.".to_string())
+        );
+
+        // Without a fallback the generation error is returned
+        let failed = answer_or_fallback(offline.generate("What was approved?").await, || {
+            OfflineFallback::Error.answer(2)
+        });
+        assert!(failed.is_err());
+
+        let generated = answer_or_fallback(Ok("Approved".to_string()), || {
+            OfflineFallback::Cache.answer(2)
+        })
+        .unwrap();
+        assert_eq!(generated, Answer::Generated("Approved".to_string()));
+    }
+
+    #[test]
+    fn test_stage_timer_records_in_order() {
+        let mut timer = StageTimer::new();
+        timer.record(PipelineStage::Retrieve, Instant::now());
+        timer.record(PipelineStage::Pack, Instant::now());
+
+        let stages: Vec<PipelineStage> = timer.stages().iter().map(|s| s.stage).collect();
+        assert_eq!(stages, vec![PipelineStage::Retrieve, PipelineStage::Pack]);
+        assert_eq!(
+            serde_json::to_value(&timer.into_stages()[0]).unwrap(),
+            json!({ "stage": "retrieve", "duration_ms": 0 })
+        );
+    }
+}
@@ -0,0 +1,550 @@
+//! Prompt template registry
+//!
+//! Every LLM prompt built by the service is a named template with `{{variable}}`
+//! placeholders and a version. The built-in templates can be overridden per
+//! workspace from `.prompt` files or `NodeSpaceConfig::prompt_config`; the name
+//! and version of the template behind an answer are returned with it, so prompt
+//! changes can be traced in responses.
+//!
+//! A `.prompt` file is named after its template (`rag.document.prompt`) and may
+//! start with a header declaring its version, separated from the body by `---`:
+//!
+//! ```text
+//! version: 2024-06-tuned
+//! ---
+//! Context:
+//! {{context}}
+//! ...
+//! ```
+
+use crate::citations;
+use nodespace_core_types::{NodeSpaceError, NodeSpaceResult, ValidationError};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+/// Names of the templates used by the service
+pub mod names {
+    /// `process_query` answer over retrieved context
+    pub const RAG_CONTEXTUAL: &str = "rag.contextual";
+    /// `process_query` answer when nothing relevant was retrieved
+    pub const RAG_CONTEXTUAL_GENERAL: &str = "rag.contextual.general";
+    /// Desktop AI response over retrieved context
+    pub const RAG_DOCUMENT: &str = "rag.document";
+    /// Desktop AI response when nothing relevant was retrieved
+    pub const RAG_DOCUMENT_GENERAL: &str = "rag.document.general";
+    /// Follow-up question in a conversation session
+    pub const RAG_CONVERSATION: &str = "rag.conversation";
+    /// Question about a date, subtree or selected nodes
+    pub const RAG_SCOPED: &str = "rag.scoped";
+    /// Question about a scope that contains no notes
+    pub const RAG_SCOPED_EMPTY: &str = "rag.scoped.empty";
+    /// Map step of hierarchical summarization: one node or chunk
+    pub const SUMMARIZE_NODE: &str = "summarize.node";
+    /// Reduce step of hierarchical summarization: merge summaries
+    pub const SUMMARIZE_MERGE: &str = "summarize.merge";
+    /// `generate_insights` over a set of nodes
+    pub const INSIGHTS: &str = "insights";
+    /// Entity extraction for cross-modal search
+    pub const EXTRACT_ENTITIES: &str = "extract.entities";
+    /// Entity extraction from node content at write time
+    pub const EXTRACT_NODE_ENTITIES: &str = "extract.node_entities";
+    /// Temporal reference extraction for cross-modal search
+    pub const EXTRACT_TEMPORAL: &str = "extract.temporal";
+    /// Visual attribute extraction for image queries
+    pub const EXTRACT_VISUAL: &str = "extract.visual";
+    /// Caption and visual attributes of an ingested image
+    pub const DESCRIBE_IMAGE: &str = "describe.image";
+    /// Second attempt after a response that did not parse as the requested JSON
+    pub const REPAIR_JSON: &str = "repair.json";
+    /// Follow-up questions suggested after an answer
+    pub const SUGGEST_RELATED: &str = "suggest.related";
+    /// Standalone retrieval query for a follow-up in a conversation
+    pub const REWRITE_FOLLOW_UP: &str = "rewrite.follow_up";
+    /// Entailment grade of an answer against its sources, for confidence
+    pub const GRADE_ENTAILMENT: &str = "grade.entailment";
+    /// Yes/no check of one answer sentence, for faithfulness
+    pub const VERIFY_STATEMENT: &str = "verify.statement";
+    /// Relevance grade of one candidate, for the LLM reranker
+    pub const RERANK_RELEVANCE: &str = "rerank.relevance";
+}
+
+/// File extension of template files loaded by `PromptRegistry::load_dir`
+pub const PROMPT_FILE_EXTENSION: &str = "prompt";
+
+/// Version of the templates shipped with the service
+pub const BUILTIN_VERSION: &str = "builtin-1";
+
+/// Version given to file templates without a `version:` header
+pub const UNVERSIONED: &str = "unversioned";
+
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    (
+        names::RAG_CONTEXTUAL,
+        "Using the context below, provide a helpful answer that's both informative and conversational:\n\nContext:\n{{context}}\n\nQuestion: {{question}}\n\nAnswer directly but include relevant context that helps explain the 'why' behind the information. Keep it engaging and professional. {{citation_instruction}}\n\nAnswer:",
+    ),
+    (
+        names::RAG_CONTEXTUAL_GENERAL,
+        "Please provide a detailed and helpful answer to this question: {{question}}\n\nProvide a comprehensive response with explanations and context where appropriate.",
+    ),
+    (
+        names::RAG_DOCUMENT,
+        "Context Information:\n{{context}}\n\nBased on the context above, answer this question: {{question}}\n\nProvide a helpful and accurate response. {{citation_instruction}}",
+    ),
+    (
+        names::RAG_DOCUMENT_GENERAL,
+        "Answer this question using your knowledge: {{question}}",
+    ),
+    (
+        names::RAG_CONVERSATION,
+        "You are continuing a conversation about the user's notes.\n\nConversation so far:\n{{history}}\n\nContext:\n{{context}}\n\nQuestion: {{question}}\n\nAnswer the latest question using the context and the conversation. {{citation_instruction}}\n\nAnswer:",
+    ),
+    (
+        names::RAG_SCOPED,
+        "The notes below are from {{scope}}.\n\nContext:\n{{context}}\n\nQuestion: {{question}}\n\nAnswer using only these notes. If they do not contain the answer, say so. {{citation_instruction}}\n\nAnswer:",
+    ),
+    (
+        names::RAG_SCOPED_EMPTY,
+        "The user asked about {{scope}}, which contains no notes.\n\nQuestion: {{question}}\n\nBriefly explain that there are no notes there to answer from.",
+    ),
+    (
+        names::SUMMARIZE_NODE,
+        "Summarize the following notes in at most {{max_words}} words. Keep names, dates, decisions and open questions.\n\nNotes:\n{{content}}\n\nSummary:",
+    ),
+    (
+        names::SUMMARIZE_MERGE,
+        "Combine these summaries of related notes into one summary of at most {{max_words}} words. Keep names, dates, decisions and open questions, and drop repetition.\n\nSummaries:\n{{summaries}}\n\nCombined summary:",
+    ),
+    (
+        names::INSIGHTS,
+        "Analyze the following content and provide key insights, patterns, and connections:\n\n{{content}}\n\nProvide a concise summary with 3-5 key insights:",
+    ),
+    (
+        names::EXTRACT_ENTITIES,
+        "Extract entities from this query and respond in JSON format:\nQuery: '{{query}}'\n\nExtract:\n- people: names of people mentioned\n- events: events or occasions mentioned\n- objects: physical objects or items mentioned\n- locations: places or locations mentioned\n\nRespond with JSON: {\"people\": [...], \"events\": [...], \"objects\": [...], \"locations\": [...]}",
+    ),
+    (
+        names::EXTRACT_NODE_ENTITIES,
+        "Extract the named entities mentioned in this note and respond in JSON format:\nNote: '{{content}}'\n\nExtract:\n- people: names of people\n- events: events or occasions, such as \"Claire's birthday\"\n- objects: physical objects or items\n- locations: places, venues or organizations\n\nUse the names as written in the note. Respond with JSON only: {\"people\": [...], \"events\": [...], \"objects\": [...], \"locations\": [...]}",
+    ),
+    (
+        names::EXTRACT_TEMPORAL,
+        "Extract temporal references from this query. Look for dates, times, or event-based temporal references. Today is {{today}}.\nQuery: '{{query}}'\n\nFind references like:\n- Specific dates (June 15, 2023-06-15)\n- Relative times (yesterday, last week)\n- Event-based times (during birthday, at the meeting)\n- Fuzzy times (around that time, recently)\n\nFor each reference give the text as written, its type (exact, relative, event or fuzzy) and the first and last day it covers as YYYY-MM-DD, or null when unknown.\n\nRespond with a JSON array only: [{\"text\": \"...\", \"type\": \"relative\", \"start\": \"YYYY-MM-DD\", \"end\": \"YYYY-MM-DD\"}]",
+    ),
+    (
+        names::EXTRACT_VISUAL,
+        "Extract what this query is looking for in images and respond in JSON format:\nQuery: '{{query}}'\n\nExtract:\n- colors: colors mentioned\n- objects: visible objects or clothing\n- scene_types: settings such as indoor, outdoor, office or restaurant\n- people_descriptions: how people look or what they are doing\n\nRespond with JSON only: {\"colors\": [...], \"objects\": [...], \"scene_types\": [...], \"people_descriptions\": [...]}",
+    ),
+    (
+        names::DESCRIBE_IMAGE,
+        "Describe this image for search and respond in JSON format.\n\nInclude:\n- caption: one sentence describing what the image shows\n- colors: prominent colors\n- objects: visible objects or clothing, with their color when notable (\"red shirt\")\n- scene_types: settings such as indoor, outdoor, office or restaurant\n- people_descriptions: how people look or what they are doing\n\nRespond with JSON only: {\"caption\": \"...\", \"colors\": [...], \"objects\": [...], \"scene_types\": [...], \"people_descriptions\": [...]}",
+    ),
+    (
+        names::REPAIR_JSON,
+        "Your previous response could not be read as the requested JSON ({{error}}).\n\nRequest:\n{{prompt}}\n\nPrevious response:\n{{response}}\n\nRespond with only the corrected JSON, without explanation or code fences.",
+    ),
+    (
+        names::SUGGEST_RELATED,
+        "Suggest {{count}} short follow-up questions the user could ask about their notes. Each question must be answerable from the notes below and must not repeat the original question. Respond with one question per line and nothing else.\n\nOriginal question: {{question}}\n\nAnswer: {{answer}}\n\nNotes:\n{{notes}}\n\nFollow-up questions:",
+    ),
+    (
+        names::REWRITE_FOLLOW_UP,
+        "Rewrite the follow-up question as a standalone search query that can be understood without the conversation. Resolve pronouns and references to earlier topics. Respond with only the rewritten query.\n\nConversation:\n{{history}}\n\nFollow-up question: {{question}}\n\nStandalone query:",
+    ),
+    (
+        names::GRADE_ENTAILMENT,
+        "Check whether the answer below is supported by the sources.\n\nSources:\n{{sources}}\n\nAnswer:\n{{answer}}\n\nRespond with a single number from 0 (unsupported or contradicted) to 10 (every statement is supported by the sources).\n\nScore:",
+    ),
+    (
+        names::VERIFY_STATEMENT,
+        "Context:\n{{context}}\n\nStatement: {{statement}}\n\nIs the statement supported by the context? Answer yes or no.\n\nAnswer:",
+    ),
+    (
+        names::RERANK_RELEVANCE,
+        "Rate how relevant the note below is for answering the question.\n\nQuestion: {{question}}\n\nNote:\n{{note}}\n\nRespond with a single number from 0 (irrelevant) to 10 (directly answers the question).\n\nScore:",
+    ),
+];
+
+/// Named prompt template with `{{variable}}` placeholders
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub version: String,
+    pub template: String,
+}
+
+/// Name and version of the template a prompt was rendered from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptVersion {
+    pub name: String,
+    pub version: String,
+}
+
+/// Prompt text together with the template it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedPrompt {
+    pub text: String,
+    pub template: PromptVersion,
+}
+
+/// Workspace prompt overrides in `NodeSpaceConfig`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptConfig {
+    /// Directory of `.prompt` files, loaded over the built-in templates
+    #[serde(default)]
+    pub template_dir: Option<String>,
+    /// Templates applied last, over built-ins and files
+    #[serde(default)]
+    pub templates: Vec<PromptTemplate>,
+}
+
+impl PromptTemplate {
+    pub fn new(
+        name: impl Into<String>,
+        version: impl Into<String>,
+        template: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            template: template.into(),
+        }
+    }
+
+    /// Parse a template file body, with an optional `version:` header before `---`
+    pub fn parse(name: &str, source: &str) -> Self {
+        let mut version = UNVERSIONED.to_string();
+        let mut body = source;
+
+        if let Some((header, rest)) = split_header(source) {
+            if let Some(declared) = header
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix("version:"))
+                .map(str::trim)
+                .find(|v| !v.is_empty())
+            {
+                version = declared.to_string();
+                body = rest;
+            }
+        }
+
+        Self::new(name, version, body.trim_end_matches(['\n', '\r']))
+    }
+
+    /// Placeholder names used by the template
+    pub fn variables(&self) -> BTreeSet<&str> {
+        let mut variables = BTreeSet::new();
+        let mut rest = self.template.as_str();
+        while let Some(open) = rest.find("{{") {
+            let after = &rest[open + 2..];
+            match after.find("}}") {
+                Some(close) => {
+                    variables.insert(after[..close].trim());
+                    rest = &after[close + 2..];
+                }
+                None => break,
+            }
+        }
+        variables
+    }
+
+    /// Substitute `vars` into the placeholders
+    ///
+    /// Substituted values are not scanned again, so user content containing
+    /// `{{` is inserted verbatim. A placeholder without a value is an error.
+    pub fn render(&self, vars: &[(&str, &str)]) -> NodeSpaceResult<RenderedPrompt> {
+        let mut text = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+
+        while let Some(open) = rest.find("{{") {
+            let after = &rest[open + 2..];
+            let close = match after.find("}}") {
+                Some(close) => close,
+                None => break,
+            };
+            let variable = after[..close].trim();
+            let value = vars
+                .iter()
+                .find(|(name, _)| *name == variable)
+                .map(|(_, value)| *value)
+                .ok_or_else(|| {
+                    invalid_template(
+                        &self.name,
+                        "a value for every placeholder",
+                        &format!("no value for '{{{{{}}}}}'", variable),
+                        vars.iter().map(|(name, _)| name.to_string()).collect(),
+                    )
+                })?;
+            text.push_str(&rest[..open]);
+            text.push_str(value);
+            rest = &after[close + 2..];
+        }
+        text.push_str(rest);
+
+        Ok(RenderedPrompt {
+            text,
+            template: self.version_info(),
+        })
+    }
+
+    pub fn version_info(&self) -> PromptVersion {
+        PromptVersion {
+            name: self.name.clone(),
+            version: self.version.clone(),
+        }
+    }
+}
+
+/// Registry of prompt templates, starting from the built-in set
+#[derive(Debug, Clone)]
+pub struct PromptRegistry {
+    templates: HashMap<String, PromptTemplate>,
+}
+
+impl Default for PromptRegistry {
+    fn default() -> Self {
+        Self {
+            templates: BUILTIN_TEMPLATES
+                .iter()
+                .map(|(name, template)| {
+                    (
+                        name.to_string(),
+                        PromptTemplate::new(*name, BUILTIN_VERSION, *template),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl PromptRegistry {
+    /// Built-in templates with the overrides from `config` applied
+    pub fn from_config(config: &PromptConfig) -> NodeSpaceResult<Self> {
+        let mut registry = Self::default();
+        if let Some(dir) = &config.template_dir {
+            registry.load_dir(dir)?;
+        }
+        for template in &config.templates {
+            registry.register(template.clone())?;
+        }
+        Ok(registry)
+    }
+
+    /// Add or replace a template
+    ///
+    /// Overrides of built-in templates may only use the variables the service
+    /// provides for them.
+    pub fn register(&mut self, template: PromptTemplate) -> NodeSpaceResult<()> {
+        if let Some((_, builtin)) = BUILTIN_TEMPLATES
+            .iter()
+            .find(|(name, _)| *name == template.name)
+        {
+            let builtin = PromptTemplate::new(template.name.as_str(), BUILTIN_VERSION, *builtin);
+            let provided = builtin.variables();
+            let unknown: Vec<&str> = template
+                .variables()
+                .into_iter()
+                .filter(|v| !provided.contains(v))
+                .collect();
+            if !unknown.is_empty() {
+                return Err(invalid_template(
+                    &template.name,
+                    "only the variables provided for this template",
+                    &format!("unknown variables: {}", unknown.join(", ")),
+                    provided.iter().map(|v| v.to_string()).collect(),
+                ));
+            }
+        }
+
+        log::info!(
+            "📝 Registered prompt template '{}' ({})",
+            template.name,
+            template.version
+        );
+        self.templates.insert(template.name.clone(), template);
+        Ok(())
+    }
+
+    pub fn with_template(mut self, template: PromptTemplate) -> NodeSpaceResult<Self> {
+        self.register(template)?;
+        Ok(self)
+    }
+
+    /// Load every `.prompt` file in `dir`, named after the file stem
+    ///
+    /// Returns the number of templates loaded.
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> NodeSpaceResult<usize> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir).map_err(|e| NodeSpaceError::InternalError {
+            message: format!("Cannot read prompt directory {}: {}", dir.display(), e),
+            service: "core-logic".to_string(),
+        })?;
+
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext == PROMPT_FILE_EXTENSION)
+            })
+            .collect();
+        paths.sort();
+
+        for path in &paths {
+            self.load_file(path)?;
+        }
+        Ok(paths.len())
+    }
+
+    /// Load one template file, named after its file stem
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> NodeSpaceResult<()> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| NodeSpaceError::InternalError {
+                message: format!("Invalid prompt file name: {}", path.display()),
+                service: "core-logic".to_string(),
+            })?;
+        let source = std::fs::read_to_string(path).map_err(|e| NodeSpaceError::InternalError {
+            message: format!("Cannot read prompt file {}: {}", path.display(), e),
+            service: "core-logic".to_string(),
+        })?;
+
+        self.register(PromptTemplate::parse(name, &source))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PromptTemplate> {
+        self.templates.get(name)
+    }
+
+    /// Render the named template
+    pub fn render(&self, name: &str, vars: &[(&str, &str)]) -> NodeSpaceResult<RenderedPrompt> {
+        self.get(name)
+            .ok_or_else(|| NodeSpaceError::InternalError {
+                message: format!("Unknown prompt template: {}", name),
+                service: "core-logic".to_string(),
+            })?
+            .render(vars)
+    }
+
+    /// Render a RAG template with the standard citation instruction available
+    pub fn render_rag(&self, name: &str, vars: &[(&str, &str)]) -> NodeSpaceResult<RenderedPrompt> {
+        let mut all = vars.to_vec();
+        all.push(("citation_instruction", citations::CITATION_INSTRUCTION));
+        self.render(name, &all)
+    }
+
+    /// Names and versions of all registered templates
+    pub fn versions(&self) -> Vec<PromptVersion> {
+        let mut versions: Vec<PromptVersion> = self
+            .templates
+            .values()
+            .map(PromptTemplate::version_info)
+            .collect();
+        versions.sort_by(|a, b| a.name.cmp(&b.name));
+        versions
+    }
+}
+
+/// Split `header\n---\nbody`, if the source has a header separator line
+fn split_header(source: &str) -> Option<(&str, &str)> {
+    let mut offset = 0;
+    for line in source.split_inclusive('\n') {
+        if line.trim() == "---" {
+            return Some((&source[..offset], &source[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn invalid_template(
+    name: &str,
+    expected: &str,
+    actual: &str,
+    examples: Vec<String>,
+) -> NodeSpaceError {
+    NodeSpaceError::Validation(ValidationError::InvalidFormat {
+        field: format!("prompt template '{}'", name),
+        expected: expected.to_string(),
+        actual: actual.to_string(),
+        examples,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_substitutes_once_and_reports_version() {
+        let template = PromptTemplate::new("greeting", "3", "Hi {{ name }}, re: {{topic}}");
+
+        let rendered = template
+            .render(&[("name", "{{topic}}"), ("topic", "budget")])
+            .unwrap();
+
+        // Values are inserted verbatim, never re-expanded
+        assert_eq!(rendered.text, "Hi {{topic}}, re: budget");
+        assert_eq!(
+            rendered.template,
+            PromptVersion {
+                name: "greeting".to_string(),
+                version: "3".to_string()
+            }
+        );
+        assert!(template.render(&[("name", "Claire")]).is_err());
+    }
+
+    #[test]
+    fn test_parse_file_with_version_header() {
+        let template = PromptTemplate::parse(
+            names::RAG_DOCUMENT,
+            "version: 2024-06-tuned\n---\nNotes:\n{{context}}\n\nQ: {{question}}\n",
+        );
+        assert_eq!(template.version, "2024-06-tuned");
+        assert_eq!(template.template, "Notes:\n{{context}}\n\nQ: {{question}}");
+
+        // Without a header the whole file is the body
+        let plain = PromptTemplate::parse("custom", "Summarize {{content}}");
+        assert_eq!(plain.version, UNVERSIONED);
+        assert_eq!(plain.template, "Summarize {{content}}");
+    }
+
+    #[test]
+    fn test_overrides_are_limited_to_provided_variables() {
+        let mut registry = PromptRegistry::default();
+
+        assert!(registry
+            .register(PromptTemplate::new(
+                names::RAG_DOCUMENT_GENERAL,
+                "2",
+                "Answer briefly: {{question}} for {{workspace}}",
+            ))
+            .is_err());
+
+        registry
+            .register(PromptTemplate::new(
+                names::RAG_DOCUMENT,
+                "2",
+                "{{context}}\n\nQ: {{question}}",
+            ))
+            .unwrap();
+        let rendered = registry
+            .render_rag(
+                names::RAG_DOCUMENT,
+                &[("context", "[1] Budget"), ("question", "Budget?")],
+            )
+            .unwrap();
+        assert_eq!(rendered.text, "[1] Budget\n\nQ: Budget?");
+        assert_eq!(rendered.template.version, "2");
+
+        // Built-ins render with every variable the service provides
+        for version in registry.versions() {
+            let template = registry.get(&version.name).unwrap();
+            let vars: Vec<(&str, &str)> =
+                template.variables().into_iter().map(|v| (v, "x")).collect();
+            assert!(template.render(&vars).is_ok());
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Follow-up question suggestions grounded in retrieved notes
+//!
+//! Suggestions are generated by the NLP engine from the question, the answer
+//! and the sources it used, with a deterministic fallback built from source
+//! headlines when generation is unavailable. Suggestions that merely restate
+//! the question or each other are removed; the service additionally keeps
+//! only those that have matching notes.
+
+use crate::generation::TextGenerator;
+use crate::prompts::{names, PromptRegistry};
+use std::collections::HashSet;
+
+/// Candidates requested from the LLM (more than returned, since some are filtered)
+const LLM_CANDIDATE_COUNT: usize = 5;
+
+/// Maximum characters of each source included in the suggestion prompt
+const MAX_SOURCE_CHARS: usize = 300;
+
+/// Maximum characters of a source headline used in fallback suggestions
+const MAX_TOPIC_CHARS: usize = 60;
+
+/// Term overlap (Jaccard) at which two questions count as duplicates
+const DUPLICATE_SIMILARITY: f32 = 0.7;
+
+/// Ask the NLP engine for follow-up questions, `None` when generation fails
+pub async fn generate_with_llm(
+    generator: &dyn TextGenerator,
+    prompts: &PromptRegistry,
+    query: &str,
+    answer: &str,
+    context: &[String],
+) -> Option<Vec<String>> {
+    let sources = context
+        .iter()
+        .map(|source| {
+            let snippet: String = source.chars().take(MAX_SOURCE_CHARS).collect();
+            format!("- {}", snippet.replace('\n', " "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let count = LLM_CANDIDATE_COUNT.to_string();
+    let generated = match prompts.render(
+        names::SUGGEST_RELATED,
+        &[
+            ("count", &count),
+            ("question", query),
+            ("answer", answer),
+            ("notes", &sources),
+        ],
+    ) {
+        Ok(prompt) => generator.generate(&prompt.text).await,
+        Err(e) => Err(e),
+    };
+
+    match generated {
+        Ok(response) => Some(parse_suggestions(&response)),
+        Err(e) => {
+            log::warn!("⚠️ Related query generation failed, using fallback: {}", e);
+            None
+        }
+    }
+}
+
+/// Parse one question per line, stripping numbering, bullets and quotes
+pub fn parse_suggestions(response: &str) -> Vec<String> {
+    response
+        .lines()
+        .map(|line| {
+            line.trim()
+                .trim_start_matches(|c: char| {
+                    c.is_ascii_digit() || matches!(c, '.' | ')' | '-' | '*' | '•')
+                })
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'')
+                .trim()
+        })
+        .filter(|line| line.chars().filter(|c| c.is_alphabetic()).count() >= 3)
+        .map(|line| {
+            if line.ends_with('?') {
+                line.to_string()
+            } else {
+                format!("{}?", line.trim_end_matches('.'))
+            }
+        })
+        .collect()
+}
+
+/// Deterministic suggestions built from the headline of each source
+pub fn fallback_suggestions(context: &[String]) -> Vec<String> {
+    context
+        .iter()
+        .filter_map(|source| source_topic(source))
+        .map(|topic| format!("What else do my notes say about {}?", topic))
+        .collect()
+}
+
+/// Remove suggestions that restate the question or an earlier suggestion
+pub fn dedupe_suggestions(query: &str, candidates: Vec<String>) -> Vec<String> {
+    let mut kept_terms = vec![question_terms(query)];
+    let mut kept = Vec::new();
+
+    for candidate in candidates {
+        let terms = question_terms(&candidate);
+        if terms.is_empty()
+            || kept_terms
+                .iter()
+                .any(|existing| jaccard(existing, &terms) >= DUPLICATE_SIMILARITY)
+        {
+            continue;
+        }
+        kept_terms.push(terms);
+        kept.push(candidate);
+    }
+
+    kept
+}
+
+/// First line of a source without outline bullets, shortened at a word boundary
+fn source_topic(source: &str) -> Option<String> {
+    let line = source
+        .lines()
+        .map(|line| line.trim().trim_start_matches(['-', '*', '#', ' ']).trim())
+        .find(|line| !line.is_empty())?;
+    let line = line.trim_end_matches(['.', ':', '?', '!']);
+
+    if line.chars().count() <= MAX_TOPIC_CHARS {
+        return Some(format!("\"{}\"", line));
+    }
+    let mut topic = String::new();
+    for word in line.split_whitespace() {
+        if topic.chars().count() + word.chars().count() + 1 > MAX_TOPIC_CHARS {
+            break;
+        }
+        if !topic.is_empty() {
+            topic.push(' ');
+        }
+        topic.push_str(word);
+    }
+    (!topic.is_empty()).then(|| format!("\"{}…\"", topic))
+}
+
+fn question_terms(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| term.chars().count() >= 3)
+        .map(|term| term.to_lowercase())
+        .collect()
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use nodespace_core_types::{NodeSpaceError, NodeSpaceResult};
+
+    struct Offline;
+
+    #[async_trait]
+    impl TextGenerator for Offline {
+        async fn generate(&self, _prompt: &str) -> NodeSpaceResult<String> {
+            Err(NodeSpaceError::InternalError {
+                message: "model offline".to_string(),
+                service: "test".to_string(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_parse_suggestions_strips_list_formatting() {
+        let response = "1. Who approved the Q3 budget?\n- \"When does the launch start\"\n\n2) ok";
+
+        assert_eq!(
+            parse_suggestions(response),
+            vec![
+                "Who approved the Q3 budget?".to_string(),
+                "When does the launch start?".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicates_of_question_and_each_other_are_removed() {
+        let kept = dedupe_suggestions(
+            "What is the Q3 marketing budget?",
+            vec![
+                "What's the marketing budget for Q3?".to_string(),
+                "Who approved the marketing budget?".to_string(),
+                "Who approved the marketing budget?".to_string(),
+                "When is the product launch?".to_string(),
+            ],
+        );
+
+        assert_eq!(
+            kept,
+            vec![
+                "Who approved the marketing budget?".to_string(),
+                "When is the product launch?".to_string()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fallback_uses_source_headlines() {
+        let context = vec![
+            "- Q3 marketing budget\n  - Approved".to_string(),
+            "Claire will coordinate the product launch with the regional sales teams across Europe and Asia.".to_string(),
+        ];
+
+        assert!(generate_with_llm(
+            &Offline,
+            &PromptRegistry::default(),
+            "budget?",
+            "",
+            &context
+        )
+        .await
+        .is_none());
+        assert_eq!(
+            fallback_suggestions(&context),
+            vec![
+                "What else do my notes say about \"Q3 marketing budget\"?".to_string(),
+                "What else do my notes say about \"Claire will coordinate the product launch with the regional…\"?".to_string()
+            ]
+        );
+    }
+}
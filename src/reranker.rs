@@ -0,0 +1,428 @@
+//! Second-stage reranking for retrieval results
+//!
+//! Vector retrieval is fast but coarse. A `Reranker` rescores the top-N
+//! candidates against the query before they are handed to answer generation:
+//! - `LlmPointwiseReranker` asks the NLP engine to grade each candidate
+//! - `DeterministicReranker` scores by lexical overlap (useful for tests)
+
+use crate::generation::TextGenerator;
+use crate::prompts::{names, PromptRegistry};
+use crate::{constants, SearchResult};
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use nodespace_core_types::NodeSpaceResult;
+use std::collections::HashSet;
+
+/// Pluggable second-stage reranker applied to the top retrieval candidates
+#[async_trait]
+pub trait Reranker: Send + Sync {
+    /// Number of top retrieval candidates handed to the reranker
+    fn top_n(&self) -> usize {
+        constants::DEFAULT_RERANK_TOP_N
+    }
+
+    /// Rescore candidates for the query, returning them sorted by descending score
+    ///
+    /// LLM-based rerankers render their grading prompt from `prompts`.
+    async fn rerank(
+        &self,
+        generator: &dyn TextGenerator,
+        prompts: &PromptRegistry,
+        query: &str,
+        candidates: Vec<SearchResult>,
+    ) -> NodeSpaceResult<Vec<SearchResult>>;
+}
+
+/// LLM-based pointwise scorer: each candidate is graded 0-10 independently
+/// and blended with its original retrieval score
+///
+/// Candidates are graded concurrently, a few generation calls at a time.
+#[derive(Debug, Clone)]
+pub struct LlmPointwiseReranker {
+    top_n: usize,
+    retrieval_weight: f32,
+    max_candidate_chars: usize,
+    concurrency: usize,
+}
+
+impl Default for LlmPointwiseReranker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LlmPointwiseReranker {
+    pub fn new() -> Self {
+        Self {
+            top_n: constants::DEFAULT_RERANK_TOP_N,
+            retrieval_weight: constants::DEFAULT_RERANK_RETRIEVAL_WEIGHT,
+            max_candidate_chars: constants::DEFAULT_RERANK_MAX_CANDIDATE_CHARS,
+            concurrency: constants::DEFAULT_RERANK_CONCURRENCY,
+        }
+    }
+
+    /// Number of candidates to rescore
+    pub fn with_top_n(mut self, top_n: usize) -> Self {
+        self.top_n = top_n;
+        self
+    }
+
+    /// Weight (0.0-1.0) kept for the original retrieval score in the final blend
+    pub fn with_retrieval_weight(mut self, weight: f32) -> Self {
+        self.retrieval_weight = weight.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Maximum characters of candidate content included in the scoring prompt
+    pub fn with_max_candidate_chars(mut self, max_chars: usize) -> Self {
+        self.max_candidate_chars = max_chars;
+        self
+    }
+
+    /// Maximum scoring calls in flight at once (at least one)
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    fn build_scoring_prompt(
+        &self,
+        prompts: &PromptRegistry,
+        query: &str,
+        candidate: &SearchResult,
+    ) -> NodeSpaceResult<String> {
+        let content = candidate.node.content.as_str().unwrap_or("");
+        let snippet: String = content.chars().take(self.max_candidate_chars).collect();
+        prompts
+            .render(
+                names::RERANK_RELEVANCE,
+                &[("question", query), ("note", &snippet)],
+            )
+            .map(|prompt| prompt.text)
+    }
+}
+
+#[async_trait]
+impl Reranker for LlmPointwiseReranker {
+    fn top_n(&self) -> usize {
+        self.top_n
+    }
+
+    async fn rerank(
+        &self,
+        generator: &dyn TextGenerator,
+        prompts: &PromptRegistry,
+        query: &str,
+        mut candidates: Vec<SearchResult>,
+    ) -> NodeSpaceResult<Vec<SearchResult>> {
+        let responses: Vec<NodeSpaceResult<String>> = stream::iter(&candidates)
+            .map(|candidate| {
+                let prompt = self.build_scoring_prompt(prompts, query, candidate);
+                async move {
+                    match prompt {
+                        Ok(prompt) => generator.generate(&prompt).await,
+                        Err(e) => Err(e),
+                    }
+                }
+            })
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+
+        for (candidate, response) in candidates.iter_mut().zip(responses) {
+            match response {
+                Ok(response) => match parse_relevance_score(&response) {
+                    Some(llm_score) => {
+                        candidate.score = self.retrieval_weight * candidate.score
+                            + (1.0 - self.retrieval_weight) * llm_score;
+                    }
+                    None => {
+                        log::warn!(
+                            "⚠️ Reranker could not parse score for node {}: '{}'",
+                            candidate.node_id,
+                            response.chars().take(50).collect::<String>()
+                        );
+                    }
+                },
+                Err(e) => {
+                    log::warn!(
+                        "⚠️ Reranker scoring failed for node {}, keeping retrieval score: {}",
+                        candidate.node_id,
+                        e
+                    );
+                }
+            }
+        }
+
+        sort_by_score_desc(&mut candidates);
+        Ok(candidates)
+    }
+}
+
+/// Deterministic reranker scoring candidates by query term overlap
+///
+/// Never calls the text generator, which makes it a stable test double and a
+/// cheap option when no LLM is available.
+#[derive(Debug, Clone)]
+pub struct DeterministicReranker {
+    top_n: usize,
+}
+
+impl Default for DeterministicReranker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeterministicReranker {
+    pub fn new() -> Self {
+        Self {
+            top_n: constants::DEFAULT_RERANK_TOP_N,
+        }
+    }
+
+    pub fn with_top_n(mut self, top_n: usize) -> Self {
+        self.top_n = top_n;
+        self
+    }
+}
+
+#[async_trait]
+impl Reranker for DeterministicReranker {
+    fn top_n(&self) -> usize {
+        self.top_n
+    }
+
+    async fn rerank(
+        &self,
+        _generator: &dyn TextGenerator,
+        _prompts: &PromptRegistry,
+        query: &str,
+        mut candidates: Vec<SearchResult>,
+    ) -> NodeSpaceResult<Vec<SearchResult>> {
+        for candidate in candidates.iter_mut() {
+            let content = candidate.node.content.as_str().unwrap_or("");
+            candidate.score = lexical_overlap_score(query, content);
+        }
+
+        sort_by_score_desc(&mut candidates);
+        Ok(candidates)
+    }
+}
+
+/// Parse the first number in an LLM response as a 0-10 grade, normalized to 0.0-1.0
+pub fn parse_relevance_score(response: &str) -> Option<f32> {
+    let number: String = response
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+
+    number
+        .trim_end_matches('.')
+        .parse::<f32>()
+        .ok()
+        .map(|score| score.clamp(0.0, 10.0) / 10.0)
+}
+
+/// Fraction of distinct query terms (3+ chars) that appear in the content
+pub fn lexical_overlap_score(query: &str, content: &str) -> f32 {
+    let query_terms: HashSet<String> = tokenize(query)
+        .into_iter()
+        .filter(|term| term.len() >= 3)
+        .collect();
+    if query_terms.is_empty() {
+        return 0.0;
+    }
+
+    let content_terms: HashSet<String> = tokenize(content).into_iter().collect();
+    let matched = query_terms
+        .iter()
+        .filter(|term| content_terms.contains(*term))
+        .count();
+
+    matched as f32 / query_terms.len() as f32
+}
+
+pub(crate) fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| term.to_lowercase())
+        .collect()
+}
+
+fn sort_by_score_desc(results: &mut [SearchResult]) {
+    results.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PromptTemplate;
+    use nodespace_core_types::{Node, NodeSpaceError};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Generator double that grades candidates by looking up a keyword in the prompt
+    struct KeywordGrader;
+
+    #[async_trait]
+    impl TextGenerator for KeywordGrader {
+        async fn generate(&self, prompt: &str) -> NodeSpaceResult<String> {
+            if prompt.contains("budget approved") {
+                Ok("9".to_string())
+            } else if prompt.contains("offline") {
+                Err(NodeSpaceError::InternalError {
+                    message: "model offline".to_string(),
+                    service: "test".to_string(),
+                })
+            } else {
+                Ok("Score: 2/10".to_string())
+            }
+        }
+    }
+
+    fn result(content: &str, score: f32) -> SearchResult {
+        let node = Node::new("text".to_string(), json!(content));
+        SearchResult {
+            node_id: node.id.clone(),
+            node,
+            score,
+        }
+    }
+
+    #[test]
+    fn test_parse_relevance_score() {
+        assert_eq!(parse_relevance_score("7"), Some(0.7));
+        assert_eq!(parse_relevance_score("Score: 8.5/10"), Some(0.85));
+        assert_eq!(parse_relevance_score("15"), Some(1.0));
+        assert_eq!(parse_relevance_score("not relevant"), None);
+    }
+
+    #[tokio::test]
+    async fn test_llm_reranker_promotes_graded_candidate() {
+        let reranker = LlmPointwiseReranker::new().with_retrieval_weight(0.0);
+        let candidates = vec![
+            result("Team lunch notes", 0.9),
+            result("The budget approved for Q3 is $50k", 0.4),
+        ];
+
+        let reranked = reranker
+            .rerank(
+                &KeywordGrader,
+                &PromptRegistry::default(),
+                "What budget was approved?",
+                candidates,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            reranked[0].node.content.as_str(),
+            Some("The budget approved for Q3 is $50k")
+        );
+        assert!((reranked[0].score - 0.9).abs() < f32::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_llm_reranker_keeps_retrieval_score_on_failure() {
+        let reranker = LlmPointwiseReranker::new();
+        let candidates = vec![result("offline note", 0.6)];
+
+        let reranked = reranker
+            .rerank(
+                &KeywordGrader,
+                &PromptRegistry::default(),
+                "anything",
+                candidates,
+            )
+            .await
+            .unwrap();
+
+        assert!((reranked[0].score - 0.6).abs() < f32::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_llm_reranker_renders_overridden_prompt() {
+        let prompts = PromptRegistry::default()
+            .with_template(PromptTemplate::new(
+                names::RERANK_RELEVANCE,
+                "2",
+                "Grade this for {{question}}",
+            ))
+            .unwrap();
+        let candidates = vec![result("Team lunch notes", 0.9)];
+
+        let reranked = LlmPointwiseReranker::new()
+            .with_retrieval_weight(0.0)
+            .rerank(&KeywordGrader, &prompts, "the budget approved", candidates)
+            .await
+            .unwrap();
+
+        assert!((reranked[0].score - 0.9).abs() < f32::EPSILON);
+    }
+
+    /// Generator double that records how many calls run at once
+    #[derive(Default)]
+    struct SlowGrader {
+        running: AtomicUsize,
+        most_running: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TextGenerator for SlowGrader {
+        async fn generate(&self, _prompt: &str) -> NodeSpaceResult<String> {
+            let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most_running.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok("5".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_llm_reranker_scores_candidates_concurrently() {
+        let grader = SlowGrader::default();
+        let candidates = (0..10)
+            .map(|i| result(&format!("note {}", i), 0.5))
+            .collect();
+
+        let reranked = LlmPointwiseReranker::new()
+            .with_concurrency(3)
+            .rerank(&grader, &PromptRegistry::default(), "anything", candidates)
+            .await
+            .unwrap();
+
+        assert_eq!(reranked.len(), 10);
+        assert_eq!(grader.most_running.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_reranker_orders_by_overlap() {
+        let candidates = vec![
+            result("Unrelated grocery list", 0.95),
+            result("Marketing budget review with Claire", 0.5),
+        ];
+
+        let reranked = DeterministicReranker::new()
+            .rerank(
+                &KeywordGrader,
+                &PromptRegistry::default(),
+                "marketing budget",
+                candidates,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            reranked[0].node.content.as_str(),
+            Some("Marketing budget review with Claire")
+        );
+        assert_eq!(reranked[0].score, 1.0);
+        assert_eq!(reranked[1].score, 0.0);
+    }
+}
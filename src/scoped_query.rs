@@ -0,0 +1,200 @@
+//! Questions scoped to part of the knowledge base
+//!
+//! `process_query_scoped` answers from a date's notes, a subtree or a
+//! user-selected set of nodes instead of the whole store. A scope whose content
+//! fits the context budget is passed to the model whole, in outline order;
+//! larger scopes go through retrieval restricted to the scope.
+
+use crate::{
+    constants, CoreLogic, DataStore, HierarchicalNode, HierarchyComputation, NLPEngine,
+    NodeSpaceService, PromptStyle, QueryOptions, QueryResponse, Retrieval,
+};
+use chrono::NaiveDate;
+use nodespace_core_types::{Node, NodeId, NodeSpaceResult};
+use serde::{Deserialize, Serialize};
+
+/// Maximum characters of a node's first line used to describe its subtree
+const MAX_SCOPE_TITLE_CHARS: usize = 60;
+
+/// Part of the knowledge base a question is about
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QueryScope {
+    /// Every node under a date node
+    Date { date: NaiveDate },
+    /// A node and all of its descendants, such as a page
+    Subtree { root_id: NodeId },
+    /// Nodes selected by the user, in the given order
+    Nodes { node_ids: Vec<NodeId> },
+}
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Answer a question using only the nodes in `scope`
+    pub async fn process_query_scoped(
+        &self,
+        query: &str,
+        scope: &QueryScope,
+    ) -> NodeSpaceResult<QueryResponse> {
+        self.process_query_scoped_with_options(query, scope, &QueryOptions::default())
+            .await
+    }
+
+    /// Scoped query with per-call options
+    pub async fn process_query_scoped_with_options(
+        &self,
+        query: &str,
+        scope: &QueryScope,
+        options: &QueryOptions,
+    ) -> NodeSpaceResult<QueryResponse> {
+        let (description, nodes) = self.resolve_query_scope(scope).await?;
+        log::info!(
+            "🎯 Scoped query over {} ({} nodes): '{}'",
+            description,
+            nodes.len(),
+            query
+        );
+
+        let output = self
+            .rag_pipeline()
+            .with_retrieval(Retrieval::Scope {
+                nodes,
+                limit: constants::DEFAULT_SEARCH_LIMIT,
+            })
+            .with_prompt(PromptStyle::Scoped { scope: description })
+            .with_faithfulness(options.faithfulness.clone())
+            .with_generation_options(options.generation)
+            .with_related_queries(true)
+            .run(query)
+            .await?;
+
+        Ok(QueryResponse::from(output))
+    }
+
+    /// Description for the prompt and the scope's nodes in outline order
+    async fn resolve_query_scope(
+        &self,
+        scope: &QueryScope,
+    ) -> NodeSpaceResult<(String, Vec<Node>)> {
+        match scope {
+            QueryScope::Date { date } => {
+                let hierarchy = self.get_hierarchical_nodes_for_date(*date).await?;
+                Ok((
+                    date.format("%A, %B %-d, %Y").to_string(),
+                    flatten_hierarchy(&hierarchy.children),
+                ))
+            }
+            QueryScope::Subtree { root_id } => {
+                let nodes: Vec<Node> = self
+                    .get_subtree_with_depths(root_id)
+                    .await?
+                    .into_iter()
+                    .map(|(node, _)| node)
+                    .collect();
+                let description = nodes
+                    .first()
+                    .and_then(|root| root.content.as_str())
+                    .and_then(scope_title)
+                    .map(|title| format!("the page \"{}\"", title))
+                    .unwrap_or_else(|| "the selected page".to_string());
+                Ok((description, nodes))
+            }
+            QueryScope::Nodes { node_ids } => {
+                let mut nodes = Vec::new();
+                for node_id in node_ids {
+                    match self.data_store.get_node(node_id).await? {
+                        Some(node) => nodes.push(node),
+                        None => log::warn!("⚠️ Scoped node {} not found, skipping", node_id),
+                    }
+                }
+                Ok((format!("a selection of {} notes", nodes.len()), nodes))
+            }
+        }
+    }
+}
+
+/// Nodes of a hierarchy in outline (depth-first) order
+pub fn flatten_hierarchy(roots: &[HierarchicalNode]) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    for root in roots {
+        nodes.push(root.node.clone());
+        nodes.extend(flatten_hierarchy(&root.children));
+    }
+    nodes
+}
+
+/// First non-empty line of a node, shortened at a character boundary
+fn scope_title(content: &str) -> Option<String> {
+    let line = content
+        .lines()
+        .map(|line| line.trim().trim_start_matches(['-', '*', '#', ' ']).trim())
+        .find(|line| !line.is_empty())?;
+    if line.chars().count() <= MAX_SCOPE_TITLE_CHARS {
+        return Some(line.to_string());
+    }
+    let shortened: String = line.chars().take(MAX_SCOPE_TITLE_CHARS).collect();
+    Some(format!("{}…", shortened.trim_end()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn hierarchical(
+        content: &str,
+        depth: u32,
+        children: Vec<HierarchicalNode>,
+    ) -> HierarchicalNode {
+        HierarchicalNode {
+            node: Node::new("text".to_string(), json!(content)),
+            children,
+            depth,
+            sibling_index: 0,
+            parent_id: None,
+        }
+    }
+
+    #[test]
+    fn test_flatten_hierarchy_keeps_outline_order() {
+        let roots = vec![
+            hierarchical(
+                "Standup",
+                1,
+                vec![
+                    hierarchical("Budget approved", 2, vec![]),
+                    hierarchical("Launch moved", 2, vec![]),
+                ],
+            ),
+            hierarchical("Groceries", 1, vec![]),
+        ];
+
+        let contents: Vec<_> = flatten_hierarchy(&roots)
+            .into_iter()
+            .map(|node| node.content.as_str().unwrap().to_string())
+            .collect();
+
+        assert_eq!(
+            contents,
+            vec!["Standup", "Budget approved", "Launch moved", "Groceries"]
+        );
+    }
+
+    #[test]
+    fn test_scope_serializes_with_type_tag_and_titles_are_shortened() {
+        let scope = QueryScope::Date {
+            date: NaiveDate::from_ymd_opt(2024, 6, 3).unwrap(),
+        };
+        assert_eq!(
+            serde_json::to_value(&scope).unwrap(),
+            json!({ "type": "date", "date": "2024-06-03" })
+        );
+
+        assert_eq!(
+            scope_title("# Q3 planning\n- budget").as_deref(),
+            Some("Q3 planning")
+        );
+        let long = "word ".repeat(20);
+        assert!(scope_title(&long).unwrap().ends_with('…'));
+        assert_eq!(scope_title("  \n"), None);
+    }
+}
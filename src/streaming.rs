@@ -0,0 +1,466 @@
+//! Streaming answers for the desktop chat
+//!
+//! Instead of blocking for the whole generation, streaming queries emit
+//! `QueryStreamEvent`s over a channel: the sources first, then incremental
+//! answer chunks, then the final confidence, timing and citations. Dropping
+//! the receiver cancels the query; generation stops at the next chunk.
+//! Tokens are streamed by the configured `StreamingTextGenerator`; without
+//! one the engine's whole answer arrives as a single chunk.
+
+use crate::confidence::assess_confidence;
+use crate::desktop_integration::build_node_sources;
+use crate::generation::TextGenerator;
+use crate::pipeline::{PipelineStage, PreparedQuery};
+use crate::prompts::PromptRegistry;
+use crate::{
+    citations, log_invalid_citations, AnswerCitation, ConfidenceBreakdown, DataStore, NLPEngine,
+    NodeSource, NodeSpaceService, PromptVersion, ResolvedGeneration, StageTiming,
+};
+use async_trait::async_trait;
+use nodespace_core_types::{NodeId, NodeSpaceResult};
+use nodespace_nlp_engine::TextGenerationRequest;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+/// Buffered chunks between the generator and the event stream
+const CHUNK_CHANNEL_CAPACITY: usize = 32;
+
+/// Event emitted by a streaming query, in order: `Sources`, `Chunk`*, `Complete`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QueryStreamEvent {
+    /// Sources in the order they are numbered in the prompt
+    Sources { sources: Vec<NodeSource> },
+    /// Next piece of the answer text
+    Chunk { text: String },
+    /// Final metadata once the full answer has been generated
+    Complete {
+        confidence: f64,
+        confidence_breakdown: ConfidenceBreakdown,
+        generation_time_ms: u64,
+        citations: Vec<AnswerCitation>,
+        #[serde(default)]
+        prompt_template: Option<PromptVersion>,
+        #[serde(default)]
+        generation: Option<ResolvedGeneration>,
+        #[serde(default)]
+        stage_timings: Vec<StageTiming>,
+    },
+}
+
+/// Text generation that delivers its output incrementally
+#[async_trait]
+pub trait StreamingTextGenerator: Send + Sync {
+    /// Generate a completion, sending chunks to `chunks` as they are produced
+    ///
+    /// Returns the full text, or `None` when the receiver was dropped before
+    /// generation finished (the query was cancelled).
+    async fn generate_stream(
+        &self,
+        request: TextGenerationRequest,
+        chunks: mpsc::Sender<String>,
+    ) -> NodeSpaceResult<Option<String>>;
+}
+
+/// Engines without token streaming send the whole answer as one chunk
+///
+/// Used when no `StreamingTextGenerator` is configured. The generation call
+/// is abandoned as soon as the receiver is dropped.
+pub(crate) struct SingleChunk<'a, N>(pub(crate) &'a N);
+
+#[async_trait]
+impl<N: NLPEngine + Send + Sync> StreamingTextGenerator for SingleChunk<'_, N> {
+    async fn generate_stream(
+        &self,
+        request: TextGenerationRequest,
+        chunks: mpsc::Sender<String>,
+    ) -> NodeSpaceResult<Option<String>> {
+        let text = tokio::select! {
+            _ = chunks.closed() => return Ok(None),
+            response = self.0.generate_text_enhanced(request) => response?.text,
+        };
+
+        if chunks.send(text.clone()).await.is_err() {
+            return Ok(None);
+        }
+        Ok(Some(text))
+    }
+}
+
+/// Emit sources, stream the answer and finish with metadata
+///
+/// Confidence is scored against the prepared context plan, with `entailment`
+/// grading support through the `prompts` template when given. Generation and verification are recorded after
+/// the preparation stages already on the prepared timer. Returns the full
+/// answer, or `None` when the receiver was dropped.
+pub(crate) async fn stream_grounded_answer(
+    generator: &dyn StreamingTextGenerator,
+    entailment: Option<&dyn TextGenerator>,
+    prompts: &PromptRegistry,
+    prepared: PreparedQuery,
+    sources: Vec<NodeSource>,
+    events: &mpsc::Sender<QueryStreamEvent>,
+) -> NodeSpaceResult<Option<String>> {
+    let PreparedQuery {
+        request,
+        plan,
+        prompt_template,
+        generation,
+        mut timer,
+        ..
+    } = prepared;
+
+    if events
+        .send(QueryStreamEvent::Sources { sources })
+        .await
+        .is_err()
+    {
+        return Ok(None);
+    }
+
+    let started = Instant::now();
+    let (chunk_tx, mut chunk_rx) = mpsc::channel::<String>(CHUNK_CHANNEL_CAPACITY);
+    let chunk_events = events.clone();
+    // Owns the chunk receiver, so a dropped event receiver cancels generation
+    let forward = async move {
+        while let Some(text) = chunk_rx.recv().await {
+            if chunk_events
+                .send(QueryStreamEvent::Chunk { text })
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    };
+
+    let (generated, _) = tokio::join!(generator.generate_stream(request, chunk_tx), forward);
+    let answer = match generated? {
+        Some(answer) => answer,
+        None => {
+            log::info!("   ⏹️ Streaming query cancelled by receiver");
+            return Ok(None);
+        }
+    };
+    timer.record(PipelineStage::Generate, started);
+
+    let started = Instant::now();
+    let citations = citations::extract_citations(&answer, &plan.source_ids());
+    log_invalid_citations(&citations);
+
+    let scores: Vec<f32> = plan.included.iter().map(|c| c.score).collect();
+    let confidence_breakdown = assess_confidence(
+        entailment,
+        prompts,
+        &answer,
+        &plan.context_texts(),
+        &scores,
+        &citations,
+    )
+    .await;
+    timer.record(PipelineStage::Verify, started);
+
+    let complete = QueryStreamEvent::Complete {
+        confidence: confidence_breakdown.overall as f64,
+        confidence_breakdown,
+        generation_time_ms: timer.elapsed_ms(),
+        citations,
+        prompt_template: Some(prompt_template),
+        generation: Some(generation),
+        stage_timings: timer.into_stages(),
+    };
+    if events.send(complete).await.is_err() {
+        return Ok(None);
+    }
+
+    Ok(Some(answer))
+}
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Streaming variant of `process_query_enhanced`
+    ///
+    /// Sends `Sources`, answer `Chunk`s and `Complete` to `events`. Dropping
+    /// the receiver stops generation and returns `Ok(())`.
+    pub async fn process_query_enhanced_stream(
+        &self,
+        query: &str,
+        events: mpsc::Sender<QueryStreamEvent>,
+    ) -> NodeSpaceResult<()> {
+        log::info!("🔍 Processing streaming enhanced query: '{}'", query);
+
+        let prepared = self.grounded_pipeline(&[]).prepare(query).await?;
+        if events.is_closed() {
+            return Ok(());
+        }
+
+        self.stream_prepared(prepared, &events).await.map(|_| ())
+    }
+
+    /// Streaming variant of `generate_ai_response`
+    ///
+    /// Returns the full answer, or `None` when the receiver was dropped.
+    pub async fn generate_ai_response_stream(
+        &self,
+        query: &str,
+        context_nodes: &[NodeId],
+        events: mpsc::Sender<QueryStreamEvent>,
+    ) -> NodeSpaceResult<Option<String>> {
+        let prepared = self.grounded_pipeline(context_nodes).prepare(query).await?;
+        self.stream_prepared(prepared, &events).await
+    }
+
+    async fn stream_prepared(
+        &self,
+        prepared: PreparedQuery,
+        events: &mpsc::Sender<QueryStreamEvent>,
+    ) -> NodeSpaceResult<Option<String>> {
+        let node_sources = build_node_sources(&prepared.plan.source_ids(), &prepared.retrieved);
+        let single_chunk = SingleChunk(&self.nlp_engine);
+        let generator: &dyn StreamingTextGenerator = match &self.streaming_generator {
+            Some(generator) => generator.as_ref(),
+            None => &single_chunk,
+        };
+
+        stream_grounded_answer(
+            generator,
+            self.entailment_generator(),
+            &self.prompts,
+            prepared,
+            node_sources,
+            events,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_budget::ContextCandidate;
+    use crate::pipeline::StageTimer;
+    use crate::ContextPlan;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Fake NLP engine that streams scripted chunks with a delay between them
+    struct FakeStreamingEngine {
+        chunks: Vec<&'static str>,
+        delay: Duration,
+        emitted: Arc<AtomicUsize>,
+    }
+
+    impl FakeStreamingEngine {
+        fn new(chunks: Vec<&'static str>) -> Self {
+            Self {
+                chunks,
+                delay: Duration::from_millis(0),
+                emitted: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl StreamingTextGenerator for FakeStreamingEngine {
+        async fn generate_stream(
+            &self,
+            _request: TextGenerationRequest,
+            chunks: mpsc::Sender<String>,
+        ) -> NodeSpaceResult<Option<String>> {
+            let mut text = String::new();
+            for chunk in &self.chunks {
+                tokio::time::sleep(self.delay).await;
+                if chunks.send(chunk.to_string()).await.is_err() {
+                    return Ok(None);
+                }
+                self.emitted.fetch_add(1, Ordering::SeqCst);
+                text.push_str(chunk);
+            }
+            Ok(Some(text))
+        }
+    }
+
+    fn prepared(plan: ContextPlan) -> PreparedQuery {
+        PreparedQuery {
+            request: TextGenerationRequest {
+                prompt: "question".to_string(),
+                max_tokens: 100,
+                temperature: 0.7,
+                context_window: 4096,
+                conversation_mode: false,
+                rag_context: None,
+                enable_link_generation: false,
+                node_metadata: vec![],
+            },
+            retrieved: vec![],
+            plan,
+            prompt_template: PromptVersion {
+                name: "rag.document".to_string(),
+                version: "2".to_string(),
+            },
+            generation: ResolvedGeneration {
+                temperature: 0.7,
+                max_tokens: 100,
+                context_window: 4096,
+                adjustments: vec![],
+            },
+            timer: StageTimer::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_stream_sources_then_chunks_then_metadata() {
+        let engine = FakeStreamingEngine::new(vec!["The launch", " is on", " Monday [1]."]);
+        let (tx, mut rx) = mpsc::channel(8);
+        let plan = ContextPlan {
+            included: vec![ContextCandidate {
+                node_id: NodeId::from_string("launch".to_string()),
+                content: "The launch is on Monday".to_string(),
+                score: 0.9,
+            }],
+            ..Default::default()
+        };
+
+        let answer = stream_grounded_answer(
+            &engine,
+            None,
+            &PromptRegistry::default(),
+            prepared(plan),
+            vec![],
+            &tx,
+        )
+        .await
+        .unwrap();
+        drop(tx);
+
+        let mut events = Vec::new();
+        while let Some(event) = rx.recv().await {
+            events.push(event);
+        }
+
+        assert!(matches!(
+            events.first(),
+            Some(QueryStreamEvent::Sources { .. })
+        ));
+        let streamed: String = events
+            .iter()
+            .filter_map(|event| match event {
+                QueryStreamEvent::Chunk { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(streamed, "The launch is on Monday [1].");
+        assert_eq!(answer.as_deref(), Some(streamed.as_str()));
+        match events.last() {
+            Some(QueryStreamEvent::Complete {
+                citations,
+                confidence_breakdown,
+                prompt_template,
+                stage_timings,
+                ..
+            }) => {
+                assert_eq!(citations[0].node_id.as_ref().unwrap().as_str(), "launch");
+                assert_eq!(confidence_breakdown.citation_coverage, 1.0);
+                let stages: Vec<_> = stage_timings.iter().map(|t| t.stage).collect();
+                assert_eq!(stages, vec![PipelineStage::Generate, PipelineStage::Verify]);
+                assert_eq!(prompt_template.as_ref().unwrap().version, "2");
+            }
+            other => panic!("expected Complete, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dropping_receiver_cancels_generation() {
+        let mut engine = FakeStreamingEngine::new(vec!["one", " two", " three", " four", " five"]);
+        engine.delay = Duration::from_millis(5);
+        let emitted = engine.emitted.clone();
+        let (tx, mut rx) = mpsc::channel(1);
+        let consumer = async move {
+            // Read the sources and the first chunk, then hang up
+            rx.recv().await;
+            rx.recv().await;
+        };
+        let (answer, _) = tokio::join!(
+            stream_grounded_answer(
+                &engine,
+                None,
+                &PromptRegistry::default(),
+                prepared(ContextPlan::default()),
+                vec![],
+                &tx
+            ),
+            consumer
+        );
+
+        assert_eq!(answer.unwrap(), None);
+        assert!(emitted.load(Ordering::SeqCst) < 5);
+    }
+
+    /// Generator that holds back the rest of its answer until released
+    struct GatedEngine {
+        release: tokio::sync::Mutex<Option<tokio::sync::oneshot::Receiver<()>>>,
+    }
+
+    #[async_trait]
+    impl StreamingTextGenerator for GatedEngine {
+        async fn generate_stream(
+            &self,
+            _request: TextGenerationRequest,
+            chunks: mpsc::Sender<String>,
+        ) -> NodeSpaceResult<Option<String>> {
+            let _ = chunks.send("The launch".to_string()).await;
+            if let Some(release) = self.release.lock().await.take() {
+                let _ = release.await;
+            }
+            let _ = chunks.send(" is on Monday.".to_string()).await;
+            Ok(Some("The launch is on Monday.".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_first_chunk_is_forwarded_before_generation_finishes() {
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel();
+        let engine = GatedEngine {
+            release: tokio::sync::Mutex::new(Some(release_rx)),
+        };
+        let (tx, mut rx) = mpsc::channel(8);
+        let consumer = async move {
+            let sources = rx.recv().await;
+            let first = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await;
+            release_tx.send(()).unwrap();
+            let mut rest = Vec::new();
+            while let Some(event) = rx.recv().await {
+                rest.push(event);
+            }
+            (sources, first, rest)
+        };
+        let (answer, (sources, first, rest)) = tokio::join!(
+            async {
+                let answer = stream_grounded_answer(
+                    &engine,
+                    None,
+                    &PromptRegistry::default(),
+                    prepared(ContextPlan::default()),
+                    vec![],
+                    &tx,
+                )
+                .await;
+                drop(tx);
+                answer
+            },
+            consumer
+        );
+
+        assert!(matches!(sources, Some(QueryStreamEvent::Sources { .. })));
+        match first.expect("first chunk while generation was still running") {
+            Some(QueryStreamEvent::Chunk { text }) => assert_eq!(text, "The launch"),
+            other => panic!("expected Chunk, got {:?}", other),
+        }
+        assert!(matches!(
+            rest.last(),
+            Some(QueryStreamEvent::Complete { .. })
+        ));
+        assert_eq!(answer.unwrap().as_deref(), Some("The launch is on Monday."));
+    }
+}
@@ -0,0 +1,368 @@
+//! Structured output from model responses
+//!
+//! Extraction prompts ask for JSON, but models wrap it in prose, code fences
+//! or trailing notes. `parse_json` finds the JSON values in a response and
+//! takes the first that deserializes into the requested type. When none does,
+//! `generate_structured` asks once more with a repair prompt carrying the
+//! parse error, and records the outcome in `StructuredOutputMetrics`.
+
+use crate::generation::TextGenerator;
+use crate::prompts::{names, PromptRegistry, RenderedPrompt};
+use crate::{DataStore, NLPEngine, NodeSpaceService};
+use nodespace_core_types::{NodeSpaceError, NodeSpaceResult, ValidationError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio::sync::RwLock;
+
+/// JSON values tried per response before giving up
+const MAX_JSON_CANDIDATES: usize = 16;
+
+/// Characters of an unparsable response quoted back in the repair prompt
+const MAX_REPAIR_RESPONSE_CHARS: usize = 2000;
+
+/// Outcomes of structured generation requests
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StructuredOutputMetrics {
+    pub requests: u64,
+    /// Parsed from the first response
+    pub parsed: u64,
+    /// Parsed after the repair prompt
+    pub repaired: u64,
+    /// Unparsable even after the repair prompt
+    pub failed: u64,
+    /// Unparsable responses per prompt template, repair attempts included
+    pub parse_failures: BTreeMap<String, u64>,
+}
+
+impl StructuredOutputMetrics {
+    /// Share of requests that produced the requested type
+    pub fn success_rate(&self) -> f64 {
+        if self.requests > 0 {
+            (self.parsed + self.repaired) as f64 / self.requests as f64
+        } else {
+            0.0
+        }
+    }
+
+    fn record_parse_failure(&mut self, prompt_name: &str) {
+        *self
+            .parse_failures
+            .entry(prompt_name.to_string())
+            .or_default() += 1;
+    }
+}
+
+/// Parse the first JSON value in `text` that deserializes into `T`
+///
+/// Tries the whole response, then fenced code blocks, then each balanced
+/// `{...}` or `[...]` span. The error describes the first candidate's failure.
+pub fn parse_json<T: DeserializeOwned>(text: &str) -> Result<T, String> {
+    let mut first_error = None;
+    for candidate in json_candidates(text) {
+        match serde_json::from_str::<T>(candidate) {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                first_error.get_or_insert_with(|| e.to_string());
+            }
+        }
+    }
+    Err(first_error.unwrap_or_else(|| "no JSON object or array in the response".to_string()))
+}
+
+/// JSON object of a model response
+pub(crate) type ResponseObject = serde_json::Map<String, serde_json::Value>;
+
+/// Fail unless the response object has at least one of `fields`
+///
+/// Types whose fields all default would otherwise take any object, such as
+/// `{"names": [...]}`, as an empty answer and never ask for a repair.
+pub(crate) fn require_any_field(object: &ResponseObject, fields: &[&str]) -> Result<(), String> {
+    if fields.iter().any(|field| object.contains_key(*field)) {
+        Ok(())
+    } else {
+        Err(format!(
+            "expected an object with any of {}",
+            fields.join(", ")
+        ))
+    }
+}
+
+/// List of strings under `field`, empty when missing or null
+pub(crate) fn string_list(object: &ResponseObject, field: &str) -> Result<Vec<String>, String> {
+    match object.get(field) {
+        None | Some(serde_json::Value::Null) => Ok(Vec::new()),
+        Some(value) => {
+            serde_json::from_value(value.clone()).map_err(|e| format!("{}: {}", field, e))
+        }
+    }
+}
+
+/// Possible JSON values in a response, most likely first
+pub fn json_candidates(text: &str) -> Vec<&str> {
+    let mut candidates = Vec::new();
+    let trimmed = text.trim();
+    if trimmed.starts_with(['{', '[']) {
+        candidates.push(trimmed);
+    }
+
+    let mut rest = text;
+    while let Some(open) = rest.find("```") {
+        let block = &rest[open + 3..];
+        let Some(close) = block.find("```") else {
+            break;
+        };
+        // Skip a language tag such as ```json
+        let body = match block[..close].find('\n') {
+            Some(newline) => &block[newline + 1..close],
+            None => &block[..close],
+        };
+        candidates.push(body.trim());
+        rest = &block[close + 3..];
+    }
+
+    for (start, c) in text.char_indices() {
+        if candidates.len() >= MAX_JSON_CANDIDATES {
+            break;
+        }
+        if c == '{' || c == '[' {
+            if let Some(end) = balanced_end(&text[start..]) {
+                candidates.push(&text[start..start + end]);
+            }
+        }
+    }
+
+    let mut unique = Vec::with_capacity(candidates.len());
+    for candidate in candidates {
+        if !candidate.is_empty() && !unique.contains(&candidate) {
+            unique.push(candidate);
+        }
+    }
+    unique.truncate(MAX_JSON_CANDIDATES);
+    unique
+}
+
+/// Byte length of the bracketed value `text` starts with, strings respected
+fn balanced_end(text: &str) -> Option<usize> {
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => stack.push('}'),
+            '[' => stack.push(']'),
+            '}' | ']' => {
+                if stack.pop() != Some(c) {
+                    return None;
+                }
+                if stack.is_empty() {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Generate and parse a structured response, with one repair attempt
+///
+/// Generation errors are returned as they are; a response that does not
+/// parse after the repair prompt is a validation error.
+pub async fn generate_structured<T: DeserializeOwned>(
+    generator: &dyn TextGenerator,
+    prompts: &PromptRegistry,
+    prompt: &RenderedPrompt,
+    metrics: &RwLock<StructuredOutputMetrics>,
+) -> NodeSpaceResult<T> {
+    let prompt_name = prompt.template.name.as_str();
+    metrics.write().await.requests += 1;
+
+    let response = generator.generate(&prompt.text).await?;
+    let error = match parse_json(&response) {
+        Ok(value) => {
+            metrics.write().await.parsed += 1;
+            return Ok(value);
+        }
+        Err(error) => error,
+    };
+    metrics.write().await.record_parse_failure(prompt_name);
+    log::warn!(
+        "⚠️ Unparsable {} response, retrying with repair prompt: {}",
+        prompt_name,
+        error
+    );
+
+    let quoted: String = response.chars().take(MAX_REPAIR_RESPONSE_CHARS).collect();
+    let repair = prompts.render(
+        names::REPAIR_JSON,
+        &[
+            ("error", &error),
+            ("prompt", &prompt.text),
+            ("response", &quoted),
+        ],
+    )?;
+    let repaired = match generator.generate(&repair.text).await {
+        Ok(response) => parse_json(&response),
+        Err(e) => {
+            metrics.write().await.failed += 1;
+            return Err(e);
+        }
+    };
+
+    let mut metrics = metrics.write().await;
+    match repaired {
+        Ok(value) => {
+            metrics.repaired += 1;
+            Ok(value)
+        }
+        Err(repair_error) => {
+            metrics.record_parse_failure(prompt_name);
+            metrics.failed += 1;
+            Err(NodeSpaceError::Validation(ValidationError::InvalidFormat {
+                field: prompt_name.to_string(),
+                expected: "a JSON response in the requested format".to_string(),
+                actual: repair_error,
+                examples: vec![],
+            }))
+        }
+    }
+}
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Generate a structured response from the NLP engine
+    pub(crate) async fn generate_structured<T: DeserializeOwned>(
+        &self,
+        prompt: &RenderedPrompt,
+    ) -> NodeSpaceResult<T> {
+        generate_structured(
+            &self.nlp_engine,
+            &self.prompts,
+            prompt,
+            &self.structured_output_metrics,
+        )
+        .await
+    }
+
+    /// Parse and repair outcomes of model extraction calls
+    pub async fn structured_output_metrics(&self) -> StructuredOutputMetrics {
+        self.structured_output_metrics.read().await.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtractedEntities;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Generator double replaying responses in order and recording prompts
+    struct Scripted {
+        responses: Mutex<Vec<&'static str>>,
+        prompts: Mutex<Vec<String>>,
+    }
+
+    impl Scripted {
+        fn new(responses: &[&'static str]) -> Self {
+            Self {
+                responses: Mutex::new(responses.iter().rev().copied().collect()),
+                prompts: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl TextGenerator for Scripted {
+        async fn generate(&self, prompt: &str) -> NodeSpaceResult<String> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            Ok(self
+                .responses
+                .lock()
+                .unwrap()
+                .pop()
+                .unwrap_or("")
+                .to_string())
+        }
+    }
+
+    fn entities_prompt(prompts: &PromptRegistry) -> RenderedPrompt {
+        prompts
+            .render(names::EXTRACT_ENTITIES, &[("query", "lunch with Claire")])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_json_is_found_in_fenced_and_noisy_responses() {
+        let fenced = "Sure! Here you go:\n```json\n{\"people\": [\"Claire\"]}\n```\nLet me know.";
+        let entities: ExtractedEntities = parse_json(fenced).unwrap();
+        assert_eq!(entities.people, vec!["Claire"]);
+
+        // Brackets inside strings and earlier non-matching values are skipped
+        let noisy = "Entities [people only]: {\"people\": [\"Ana {the} Silva]\"], \"events\": [\"lunch\"]} (done)";
+        let entities: ExtractedEntities = parse_json(noisy).unwrap();
+        assert_eq!(entities.people, vec!["Ana {the} Silva]"]);
+        assert_eq!(entities.events, vec!["lunch"]);
+
+        let list: Vec<u32> = parse_json("The days are [3, 14].").unwrap();
+        assert_eq!(list, vec![3, 14]);
+    }
+
+    #[test]
+    fn test_responses_of_the_wrong_shape_are_rejected() {
+        assert!(parse_json::<ExtractedEntities>("No entities found.").is_err());
+        assert!(parse_json::<ExtractedEntities>("{\"people\": \"Claire\"}").is_err());
+        assert!(parse_json::<ExtractedEntities>("{\"names\": [\"Claire\"]}").is_err());
+        assert!(parse_json::<ExtractedEntities>("{}").is_err());
+        let entities: ExtractedEntities = parse_json("{\"events\": [], \"people\": null}").unwrap();
+        assert_eq!(entities, ExtractedEntities::default());
+        assert!(parse_json::<Vec<u32>>("{\"days\": [3, 14").is_err());
+        assert!(json_candidates("Days: [1, 2").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_repair_prompt_retries_once_and_records_metrics() {
+        let prompts = PromptRegistry::default();
+        let metrics = RwLock::new(StructuredOutputMetrics::default());
+        let prompt = entities_prompt(&prompts);
+
+        let generator = Scripted::new(&["People: Claire", "{\"people\": [\"Claire\"]}"]);
+        let entities: ExtractedEntities =
+            generate_structured(&generator, &prompts, &prompt, &metrics)
+                .await
+                .unwrap();
+        assert_eq!(entities.people, vec!["Claire"]);
+        let sent = generator.prompts.lock().unwrap().clone();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[1].contains("People: Claire") && sent[1].contains(&prompt.text));
+
+        let generator = Scripted::new(&["nothing", "still nothing", "{}"]);
+        let result: NodeSpaceResult<ExtractedEntities> =
+            generate_structured(&generator, &prompts, &prompt, &metrics).await;
+        assert!(result.is_err());
+        assert_eq!(generator.prompts.lock().unwrap().len(), 2);
+
+        let metrics = metrics.read().await;
+        assert_eq!(
+            (
+                metrics.requests,
+                metrics.parsed,
+                metrics.repaired,
+                metrics.failed
+            ),
+            (2, 0, 1, 1)
+        );
+        assert_eq!(metrics.parse_failures[names::EXTRACT_ENTITIES], 3);
+        assert_eq!(metrics.success_rate(), 0.5);
+    }
+}
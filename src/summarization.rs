@@ -0,0 +1,793 @@
+//! Map-reduce summarization of large subtrees and date ranges
+//!
+//! Leaves are summarized first, then each parent from its own text and its
+//! children's summaries, up to the root. Every model call stays within the
+//! context window: text too long for one call is summarized in chunks, and
+//! summaries too long to merge at once are merged in batches until a single
+//! call fits. Nodes that are already short are used verbatim.
+//!
+//! Node summaries can be cached in node metadata under
+//! `SUMMARY_METADATA_KEY`, keyed by a hash of their inputs, so unchanged
+//! branches are not summarized again.
+
+use crate::context_budget::CONTEXT_SEPARATOR;
+use crate::date_range::validate_date_range;
+use crate::generation_options::{self, GenerationOptions, ResolvedGeneration};
+use crate::prompts::{names, PromptRegistry};
+use crate::scoped_query::flatten_hierarchy;
+use crate::{
+    constants, DataStore, HierarchicalNode, HierarchyComputation, NLPEngine, NodeSpaceService,
+    TextGenerator, TokenCounter,
+};
+use chrono::{NaiveDate, Utc};
+use nodespace_core_types::{Node, NodeId, NodeSpaceError, NodeSpaceResult, ValidationError};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Node metadata key under which summaries are cached
+pub const SUMMARY_METADATA_KEY: &str = "summary";
+
+/// Settings for `summarize_subtree` and `summarize_date_range`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SummarizationOptions {
+    /// Longest summary generated for a single node, in tokens
+    pub summary_max_tokens: usize,
+    /// Store generated node summaries in node metadata and reuse unchanged ones
+    pub cache_summaries: bool,
+}
+
+impl Default for SummarizationOptions {
+    fn default() -> Self {
+        Self {
+            summary_max_tokens: constants::DEFAULT_SUMMARY_MAX_TOKENS,
+            cache_summaries: false,
+        }
+    }
+}
+
+impl SummarizationOptions {
+    pub fn with_summary_max_tokens(mut self, summary_max_tokens: usize) -> Self {
+        self.summary_max_tokens = summary_max_tokens;
+        self
+    }
+
+    pub fn with_cache(mut self, cache_summaries: bool) -> Self {
+        self.cache_summaries = cache_summaries;
+        self
+    }
+}
+
+/// Summary of a subtree or date range
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HierarchicalSummary {
+    pub summary: String,
+    /// Nodes covered by the summary
+    pub node_count: usize,
+    /// Generation requests made, including chunk and merge steps
+    pub model_calls: usize,
+    /// Node summaries taken from the metadata cache
+    pub cached_nodes: usize,
+    pub generation: ResolvedGeneration,
+}
+
+/// Summary cached in a node's metadata
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedSummary {
+    pub text: String,
+    /// Hash of the node text, child summaries and templates the summary was built from
+    pub input_hash: String,
+    pub generated_at: String,
+}
+
+/// Summary of one node and its descendants
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeSummary {
+    pub node_id: NodeId,
+    pub text: String,
+    pub input_hash: String,
+    /// Produced by the model in this run, as opposed to verbatim or cached
+    pub generated: bool,
+}
+
+/// Budget-aware map-reduce summarizer over node hierarchies
+pub struct Summarizer<'a> {
+    generator: &'a dyn TextGenerator,
+    counter: &'a dyn TokenCounter,
+    prompts: &'a PromptRegistry,
+    summary_max_tokens: usize,
+    /// Tokens of input text that fit in one request next to the scaffold and answer
+    input_budget: usize,
+    model_calls: AtomicUsize,
+    cached_nodes: AtomicUsize,
+}
+
+impl<'a> Summarizer<'a> {
+    /// Summarizer producing summaries of `generation.max_tokens` tokens
+    ///
+    /// Fails when the context window cannot hold at least two summaries next
+    /// to the prompt scaffold, because merging would then never converge.
+    pub fn new(
+        generator: &'a dyn TextGenerator,
+        counter: &'a dyn TokenCounter,
+        prompts: &'a PromptRegistry,
+        generation: &ResolvedGeneration,
+    ) -> NodeSpaceResult<Self> {
+        let summary_max_tokens = generation.max_tokens;
+        let max_words = max_words(summary_max_tokens);
+        let scaffold_tokens = [
+            prompts.render(
+                names::SUMMARIZE_NODE,
+                &[("content", ""), ("max_words", &max_words)],
+            )?,
+            prompts.render(
+                names::SUMMARIZE_MERGE,
+                &[("summaries", ""), ("max_words", &max_words)],
+            )?,
+        ]
+        .iter()
+        .map(|prompt| counter.count_tokens(&prompt.text))
+        .max()
+        .unwrap_or(0);
+
+        let input_budget = generation
+            .context_window
+            .saturating_sub(summary_max_tokens)
+            .saturating_sub(scaffold_tokens);
+        let separator_tokens = counter.count_tokens(CONTEXT_SEPARATOR);
+        let required = 2 * (summary_max_tokens + separator_tokens);
+        if input_budget < required {
+            return Err(NodeSpaceError::Validation(ValidationError::InvalidFormat {
+                field: "summary_max_tokens".to_string(),
+                expected: format!(
+                    "a summary length leaving room to merge two summaries in a {} token context window",
+                    generation.context_window
+                ),
+                actual: summary_max_tokens.to_string(),
+                examples: vec![constants::DEFAULT_SUMMARY_MAX_TOKENS.to_string()],
+            }));
+        }
+
+        Ok(Self {
+            generator,
+            counter,
+            prompts,
+            summary_max_tokens,
+            input_budget,
+            model_calls: AtomicUsize::new(0),
+            cached_nodes: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn model_calls(&self) -> usize {
+        self.model_calls.load(Ordering::Relaxed)
+    }
+
+    pub fn cached_nodes(&self) -> usize {
+        self.cached_nodes.load(Ordering::Relaxed)
+    }
+
+    /// Summarize every node of the given trees, children before parents
+    ///
+    /// With `use_cache`, a summary cached in a node's metadata is reused when
+    /// its input hash still matches.
+    pub async fn summarize_forest(
+        &self,
+        roots: &[HierarchicalNode],
+        use_cache: bool,
+    ) -> NodeSpaceResult<Vec<NodeSummary>> {
+        let mut summaries: HashMap<NodeId, NodeSummary> = HashMap::new();
+        let mut ordered = Vec::new();
+
+        for entry in post_order(roots) {
+            let mut parts = Vec::new();
+            if let Some(text) = entry.node.content.as_str() {
+                parts.push(text.trim().to_string());
+            }
+            parts.extend(
+                entry
+                    .children
+                    .iter()
+                    .filter_map(|child| summaries.get(&child.node.id))
+                    .map(|summary| summary.text.clone()),
+            );
+            parts.retain(|part| !part.is_empty());
+
+            let input_hash = self.input_hash(&parts);
+            let cached = cached_summary(&entry.node)
+                .filter(|cached| use_cache && cached.input_hash == input_hash);
+
+            let summary = if let Some(cached) = cached {
+                self.cached_nodes.fetch_add(1, Ordering::Relaxed);
+                NodeSummary {
+                    node_id: entry.node.id.clone(),
+                    text: cached.text,
+                    input_hash,
+                    generated: false,
+                }
+            } else {
+                let calls_before = self.model_calls();
+                let text = self.condense(parts).await?;
+                NodeSummary {
+                    node_id: entry.node.id.clone(),
+                    text,
+                    input_hash,
+                    generated: self.model_calls() > calls_before,
+                }
+            };
+
+            summaries.insert(summary.node_id.clone(), summary.clone());
+            ordered.push(summary);
+        }
+
+        Ok(ordered)
+    }
+
+    /// Reduce texts to at most `summary_max_tokens` tokens
+    ///
+    /// Texts that already fit together are joined verbatim without a model call.
+    pub async fn condense(&self, parts: Vec<String>) -> NodeSpaceResult<String> {
+        if self.total_tokens(&parts) <= self.summary_max_tokens {
+            return Ok(parts.join(CONTEXT_SEPARATOR));
+        }
+
+        let mut condensed = Vec::with_capacity(parts.len());
+        for part in parts {
+            if self.counter.count_tokens(&part) > self.summary_max_tokens {
+                condensed.push(self.summarize_text(&part).await?);
+            } else {
+                condensed.push(part);
+            }
+        }
+
+        if condensed.len() == 1 {
+            return Ok(condensed.remove(0));
+        }
+        self.merge(condensed).await
+    }
+
+    /// Summarize one text, in chunks when it exceeds a single request
+    async fn summarize_text(&self, text: &str) -> NodeSpaceResult<String> {
+        let chunks = split_to_budget(self.counter, text, self.input_budget);
+        if chunks.len() == 1 {
+            return self
+                .generate(names::SUMMARIZE_NODE, ("content", &chunks[0]))
+                .await;
+        }
+
+        let mut summaries = Vec::with_capacity(chunks.len());
+        for chunk in &chunks {
+            summaries.push(
+                self.generate(names::SUMMARIZE_NODE, ("content", chunk))
+                    .await?,
+            );
+        }
+        self.merge(summaries).await
+    }
+
+    /// Merge summaries of at most `summary_max_tokens` each into one
+    async fn merge(&self, mut summaries: Vec<String>) -> NodeSpaceResult<String> {
+        loop {
+            if self.total_tokens(&summaries) <= self.input_budget {
+                let joined = summaries.join(CONTEXT_SEPARATOR);
+                return self
+                    .generate(names::SUMMARIZE_MERGE, ("summaries", &joined))
+                    .await;
+            }
+
+            let mut merged = Vec::new();
+            for batch in self.batches(summaries) {
+                if batch.len() == 1 {
+                    merged.extend(batch);
+                } else {
+                    let joined = batch.join(CONTEXT_SEPARATOR);
+                    merged.push(
+                        self.generate(names::SUMMARIZE_MERGE, ("summaries", &joined))
+                            .await?,
+                    );
+                }
+            }
+            summaries = merged;
+        }
+    }
+
+    /// Consecutive groups of summaries that each fit one request
+    fn batches(&self, summaries: Vec<String>) -> Vec<Vec<String>> {
+        let separator_tokens = self.counter.count_tokens(CONTEXT_SEPARATOR);
+        let mut batches = Vec::new();
+        let mut current: Vec<String> = Vec::new();
+        let mut used = 0;
+
+        for summary in summaries {
+            let tokens = self.counter.count_tokens(&summary) + separator_tokens;
+            if !current.is_empty() && used + tokens > self.input_budget {
+                batches.push(std::mem::take(&mut current));
+                used = 0;
+            }
+            used += tokens;
+            current.push(summary);
+        }
+        if !current.is_empty() {
+            batches.push(current);
+        }
+        batches
+    }
+
+    async fn generate(&self, template: &str, input: (&str, &str)) -> NodeSpaceResult<String> {
+        let max_words = max_words(self.summary_max_tokens);
+        let prompt = self
+            .prompts
+            .render(template, &[input, ("max_words", &max_words)])?;
+        self.model_calls.fetch_add(1, Ordering::Relaxed);
+
+        let summary = self.generator.generate(&prompt.text).await?;
+        Ok(truncate_to_tokens(
+            self.counter,
+            summary.trim(),
+            self.summary_max_tokens,
+        ))
+    }
+
+    fn total_tokens(&self, parts: &[String]) -> usize {
+        let separator_tokens = self.counter.count_tokens(CONTEXT_SEPARATOR);
+        parts
+            .iter()
+            .map(|part| self.counter.count_tokens(part) + separator_tokens)
+            .sum()
+    }
+
+    fn input_hash(&self, parts: &[String]) -> String {
+        let mut hasher = DefaultHasher::new();
+        for name in [names::SUMMARIZE_NODE, names::SUMMARIZE_MERGE] {
+            if let Some(template) = self.prompts.get(name) {
+                template.version.hash(&mut hasher);
+            }
+        }
+        self.summary_max_tokens.hash(&mut hasher);
+        parts.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Summarize a node and all of its descendants
+    pub async fn summarize_subtree(
+        &self,
+        root_id: &NodeId,
+        options: &SummarizationOptions,
+    ) -> NodeSpaceResult<HierarchicalSummary> {
+        let mut nodes = self.get_subtree_with_depths(root_id).await?;
+        let (root, depth) = nodes.remove(0);
+        let children = self.build_hierarchical_tree_from_flat_list(
+            nodes.into_iter().map(|(node, _)| node).collect(),
+            root_id,
+            depth + 1,
+        )?;
+        let tree = [HierarchicalNode {
+            parent_id: root.parent_id.clone(),
+            node: root,
+            children,
+            depth,
+            sibling_index: 0,
+        }];
+
+        let generation = self.summary_generation(options)?;
+        let summarizer = self.summarizer(&generation)?;
+        let summaries = summarizer
+            .summarize_forest(&tree, options.cache_summaries)
+            .await?;
+        self.cache_node_summaries(&tree, &summaries, options).await;
+
+        let summary = summaries
+            .last()
+            .map(|summary| summary.text.clone())
+            .unwrap_or_default();
+        Ok(HierarchicalSummary {
+            summary,
+            node_count: summaries.len(),
+            model_calls: summarizer.model_calls(),
+            cached_nodes: summarizer.cached_nodes(),
+            generation,
+        })
+    }
+
+    /// Summarize the notes of every day from `start` to `end`, inclusive
+    pub async fn summarize_date_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+        options: &SummarizationOptions,
+    ) -> NodeSpaceResult<HierarchicalSummary> {
+        validate_date_range(start, end, constants::MAX_SUMMARY_RANGE_DAYS)?;
+        let days = self.date_trees(start, end).await?;
+        self.summarize_days(&days, options).await
+    }
+
+    /// Summarize each day's tree, then merge the day summaries in date order
+    pub(crate) async fn summarize_days(
+        &self,
+        days: &[(NaiveDate, HierarchicalNode)],
+        options: &SummarizationOptions,
+    ) -> NodeSpaceResult<HierarchicalSummary> {
+        let trees: Vec<HierarchicalNode> = days.iter().map(|(_, tree)| tree.clone()).collect();
+
+        let generation = self.summary_generation(options)?;
+        let summarizer = self.summarizer(&generation)?;
+        let summaries = summarizer
+            .summarize_forest(&trees, options.cache_summaries)
+            .await?;
+        self.cache_node_summaries(&trees, &summaries, options).await;
+
+        let by_id: HashMap<&NodeId, &NodeSummary> = summaries
+            .iter()
+            .map(|summary| (&summary.node_id, summary))
+            .collect();
+        let day_summaries: Vec<String> = days
+            .iter()
+            .filter_map(|(date, tree)| {
+                by_id
+                    .get(&tree.node.id)
+                    .filter(|summary| !summary.text.is_empty())
+                    .map(|summary| format!("{}:\n{}", date.format("%A, %B %-d, %Y"), summary.text))
+            })
+            .collect();
+        let summary = summarizer.condense(day_summaries).await?;
+
+        Ok(HierarchicalSummary {
+            summary,
+            node_count: summaries.len(),
+            model_calls: summarizer.model_calls(),
+            cached_nodes: summarizer.cached_nodes(),
+            generation,
+        })
+    }
+
+    /// Fit texts into a prompt with `available_tokens` of room for them
+    ///
+    /// Returns the texts joined with `separator` when they fit, otherwise a
+    /// map-reduce summary of them.
+    pub(crate) async fn condense_to_fit(
+        &self,
+        contents: Vec<String>,
+        separator: &str,
+        available_tokens: usize,
+    ) -> NodeSpaceResult<String> {
+        let joined = contents.join(separator);
+        if self.token_counter.count_tokens(&joined) <= available_tokens {
+            return Ok(joined);
+        }
+
+        let generation = self.summary_generation(&SummarizationOptions::default())?;
+        let summarizer = self.summarizer(&generation)?;
+        let condensed = summarizer.condense(contents).await?;
+        log::info!(
+            "📚 Condensed {} tokens of content with {} summarization calls",
+            self.token_counter.count_tokens(&joined),
+            summarizer.model_calls()
+        );
+        Ok(condensed)
+    }
+
+    fn summary_generation(
+        &self,
+        options: &SummarizationOptions,
+    ) -> NodeSpaceResult<ResolvedGeneration> {
+        generation_options::resolve_generation(
+            &GenerationOptions::default().with_max_tokens(options.summary_max_tokens),
+            &self.config.performance_config,
+            &self.model_limits,
+            options.summary_max_tokens,
+        )
+    }
+
+    fn summarizer<'s>(
+        &'s self,
+        generation: &ResolvedGeneration,
+    ) -> NodeSpaceResult<Summarizer<'s>> {
+        Summarizer::new(
+            &self.nlp_engine,
+            self.token_counter.as_ref(),
+            &self.prompts,
+            generation,
+        )
+    }
+
+    /// Store newly generated summaries in node metadata when caching is enabled
+    async fn cache_node_summaries(
+        &self,
+        trees: &[HierarchicalNode],
+        summaries: &[NodeSummary],
+        options: &SummarizationOptions,
+    ) {
+        if !options.cache_summaries {
+            return;
+        }
+
+        let nodes: HashMap<NodeId, Node> = trees
+            .iter()
+            .flat_map(|tree| {
+                std::iter::once(tree.node.clone()).chain(flatten_hierarchy(&tree.children))
+            })
+            .map(|node| (node.id.clone(), node))
+            .collect();
+        let generated_at = Utc::now().to_rfc3339();
+
+        for summary in summaries.iter().filter(|summary| summary.generated) {
+            let Some(mut node) = nodes.get(&summary.node_id).cloned() else {
+                continue;
+            };
+            let cached = CachedSummary {
+                text: summary.text.clone(),
+                input_hash: summary.input_hash.clone(),
+                generated_at: generated_at.clone(),
+            };
+            match with_cached_summary(node.metadata.take(), &cached) {
+                Some(metadata) => {
+                    node.metadata = Some(metadata);
+                    if let Err(e) = self.data_store.update_node(node).await {
+                        log::warn!(
+                            "⚠️ Failed to cache summary for node {}: {}",
+                            summary.node_id,
+                            e
+                        );
+                    }
+                }
+                None => log::warn!(
+                    "⚠️ Node {} metadata is not an object, summary not cached",
+                    summary.node_id
+                ),
+            }
+        }
+    }
+}
+
+/// Summary cached in a node's metadata, if any
+pub fn cached_summary(node: &Node) -> Option<CachedSummary> {
+    let cached = node.metadata.as_ref()?.get(SUMMARY_METADATA_KEY)?;
+    serde_json::from_value(cached.clone()).ok()
+}
+
+/// Node metadata with `summary` stored under `SUMMARY_METADATA_KEY`
+///
+/// Returns `None` when existing metadata is not a JSON object.
+pub fn with_cached_summary(
+    metadata: Option<serde_json::Value>,
+    summary: &CachedSummary,
+) -> Option<serde_json::Value> {
+    let mut metadata = metadata.unwrap_or_else(|| serde_json::json!({}));
+    let value = serde_json::to_value(summary).ok()?;
+    metadata
+        .as_object_mut()?
+        .insert(SUMMARY_METADATA_KEY.to_string(), value);
+    Some(metadata)
+}
+
+/// Nodes of the given trees with every child before its parent
+fn post_order(roots: &[HierarchicalNode]) -> Vec<&HierarchicalNode> {
+    let mut ordered = Vec::new();
+    let mut stack: Vec<(&HierarchicalNode, bool)> =
+        roots.iter().rev().map(|root| (root, false)).collect();
+
+    while let Some((entry, children_done)) = stack.pop() {
+        if children_done {
+            ordered.push(entry);
+        } else {
+            stack.push((entry, true));
+            stack.extend(entry.children.iter().rev().map(|child| (child, false)));
+        }
+    }
+    ordered
+}
+
+/// Split text into line-aligned chunks of at most `budget` tokens
+///
+/// Lines longer than the budget are split between words.
+fn split_to_budget(counter: &dyn TokenCounter, text: &str, budget: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    let pieces = text.lines().flat_map(|line| {
+        if counter.count_tokens(line) <= budget {
+            vec![line.to_string()]
+        } else {
+            let mut words = Vec::new();
+            let mut piece = String::new();
+            for word in line.split_whitespace() {
+                let candidate = if piece.is_empty() {
+                    word.to_string()
+                } else {
+                    format!("{} {}", piece, word)
+                };
+                if !piece.is_empty() && counter.count_tokens(&candidate) > budget {
+                    words.push(std::mem::replace(&mut piece, word.to_string()));
+                } else {
+                    piece = candidate;
+                }
+            }
+            words.push(piece);
+            words
+        }
+    });
+
+    for piece in pieces {
+        let candidate = if current.is_empty() {
+            piece.clone()
+        } else {
+            format!("{}\n{}", current, piece)
+        };
+        if !current.is_empty() && counter.count_tokens(&candidate) > budget {
+            chunks.push(std::mem::replace(&mut current, piece));
+        } else {
+            current = candidate;
+        }
+    }
+    if !current.trim().is_empty() || chunks.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Cut text after the last whole word that fits `max_tokens`
+fn truncate_to_tokens(counter: &dyn TokenCounter, text: &str, max_tokens: usize) -> String {
+    if counter.count_tokens(text) <= max_tokens {
+        return text.to_string();
+    }
+    log::debug!("Summary exceeded {} tokens, truncating", max_tokens);
+
+    let mut truncated = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if truncated.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", truncated, word)
+        };
+        if counter.count_tokens(&candidate) > max_tokens {
+            break;
+        }
+        truncated = candidate;
+    }
+    truncated
+}
+
+/// Word limit given to the model for a token limit
+fn max_words(max_tokens: usize) -> String {
+    (max_tokens * 3 / 4).max(1).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HeuristicTokenCounter;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Returns a fixed-length summary and records every prompt
+    #[derive(Default)]
+    struct RecordingGenerator {
+        prompts: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl TextGenerator for RecordingGenerator {
+        async fn generate(&self, prompt: &str) -> NodeSpaceResult<String> {
+            let mut prompts = self.prompts.lock().unwrap();
+            prompts.push(prompt.to_string());
+            Ok(format!("summary {} covering the notes", prompts.len()))
+        }
+    }
+
+    fn generation(context_window: usize, max_tokens: usize) -> ResolvedGeneration {
+        ResolvedGeneration {
+            temperature: 0.7,
+            max_tokens,
+            context_window,
+            adjustments: vec![],
+        }
+    }
+
+    fn hierarchical(content: &str, children: Vec<HierarchicalNode>) -> HierarchicalNode {
+        HierarchicalNode {
+            node: Node::new("text".to_string(), json!(content)),
+            children,
+            depth: 0,
+            sibling_index: 0,
+            parent_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_short_trees_are_joined_without_model_calls() {
+        let generator = RecordingGenerator::default();
+        let prompts = PromptRegistry::default();
+        let summarizer = Summarizer::new(
+            &generator,
+            &HeuristicTokenCounter,
+            &prompts,
+            &generation(4096, 200),
+        )
+        .unwrap();
+
+        let tree = [hierarchical(
+            "Launch",
+            vec![hierarchical("Ship on Friday", vec![])],
+        )];
+        let summaries = summarizer.summarize_forest(&tree, false).await.unwrap();
+
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].text, "Ship on Friday");
+        assert_eq!(summaries[1].text, "Launch\n\nShip on Friday");
+        assert!(summaries.iter().all(|summary| !summary.generated));
+        assert_eq!(summarizer.model_calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_large_trees_are_reduced_within_the_context_window() {
+        let generator = RecordingGenerator::default();
+        let prompts = PromptRegistry::default();
+        let context_window = 400;
+        let summarizer = Summarizer::new(
+            &generator,
+            &HeuristicTokenCounter,
+            &prompts,
+            &generation(context_window, 40),
+        )
+        .unwrap();
+
+        let paragraph = "The quarterly review covered hiring, budget and the roadmap. ".repeat(60);
+        let leaves = (0..12)
+            .map(|_| hierarchical(&paragraph, vec![]))
+            .collect::<Vec<_>>();
+        let tree = [hierarchical("Project Atlas", leaves)];
+
+        let summaries = summarizer.summarize_forest(&tree, false).await.unwrap();
+        let root = summaries.last().unwrap();
+
+        assert_eq!(root.node_id, tree[0].node.id);
+        assert!(root.generated);
+        assert!(HeuristicTokenCounter.count_tokens(&root.text) <= 40);
+        let prompts = generator.prompts.lock().unwrap();
+        assert_eq!(summarizer.model_calls(), prompts.len());
+        for prompt in prompts.iter() {
+            assert!(HeuristicTokenCounter.count_tokens(prompt) + 40 <= context_window);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_summaries_are_reused_until_inputs_change() {
+        let generator = RecordingGenerator::default();
+        let prompts = PromptRegistry::default();
+        let summarizer = Summarizer::new(
+            &generator,
+            &HeuristicTokenCounter,
+            &prompts,
+            &generation(400, 40),
+        )
+        .unwrap();
+
+        let mut tree = [hierarchical(&"Long meeting notes. ".repeat(40), vec![])];
+        let first = summarizer.summarize_forest(&tree, true).await.unwrap();
+        assert!(first[0].generated);
+
+        let cached = CachedSummary {
+            text: first[0].text.clone(),
+            input_hash: first[0].input_hash.clone(),
+            generated_at: Utc::now().to_rfc3339(),
+        };
+        tree[0].node.metadata = with_cached_summary(Some(json!({ "source": "import" })), &cached);
+        assert_eq!(cached_summary(&tree[0].node), Some(cached));
+
+        let calls = summarizer.model_calls();
+        let second = summarizer.summarize_forest(&tree, true).await.unwrap();
+        assert_eq!(second[0].text, first[0].text);
+        assert_eq!(summarizer.model_calls(), calls);
+        assert_eq!(summarizer.cached_nodes(), 1);
+
+        tree[0].node.content = json!("Short notes");
+        let third = summarizer.summarize_forest(&tree, true).await.unwrap();
+        assert_eq!(third[0].text, "Short notes");
+        assert_eq!(summarizer.cached_nodes(), 1);
+    }
+}
@@ -0,0 +1,1585 @@
+//! Rule-based parsing of temporal expressions in queries
+//!
+//! `TemporalParser` recognizes absolute dates ("June 15", "2023-06-15",
+//! "15/06"), relative expressions ("3 days ago", "next Monday", "last month",
+//! "Q3"), ranges ("between May and July", "since August") and fuzzy windows
+//! ("around June 15", "early June", "recently"). Everything is resolved
+//! against a fixed `today`, so results are deterministic. Dates without a
+//! year resolve to the most recent occurrence, since queries are about notes
+//! already taken.
+
+use crate::periods::{Period, PeriodKind};
+use crate::{TemporalReference, TemporalType};
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+use serde::Deserialize;
+
+/// Days either side of a date, or of a week, for "around June 15"
+const FUZZY_DAY_WINDOW: i64 = 3;
+/// Days either side of a month for "around June"
+const FUZZY_MONTH_WINDOW: i64 = 7;
+/// Days either side of a quarter or year for "around 2024"
+const FUZZY_YEAR_WINDOW: i64 = 14;
+/// Days covered by "recently" and "lately"
+const RECENT_DAYS: i64 = 14;
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("monday", Weekday::Mon),
+    ("tuesday", Weekday::Tue),
+    ("wednesday", Weekday::Wed),
+    ("thursday", Weekday::Thu),
+    ("friday", Weekday::Fri),
+    ("saturday", Weekday::Sat),
+    ("sunday", Weekday::Sun),
+];
+
+const NUMBER_WORDS: [&str; 13] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    "eleven", "twelve",
+];
+
+/// Words that may precede a bare year, or "may" and "march", which are also verbs
+const DATE_PREPOSITIONS: &[&str] = &[
+    "in", "during", "since", "from", "between", "and", "to", "until", "till", "through", "of",
+    "early", "mid", "late", "around", "circa",
+];
+
+/// Occasions recognized as event-anchored references
+const EVENT_WORDS: &[&str] = &[
+    "birthday",
+    "anniversary",
+    "wedding",
+    "vacation",
+    "holiday",
+    "conference",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+/// Resolved date span of one expression
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: NaiveDate,
+    end: NaiveDate,
+    precision: Unit,
+    /// Resolved against today rather than written out
+    relative: bool,
+    explicit_year: bool,
+}
+
+impl Span {
+    fn day(date: NaiveDate, relative: bool) -> Self {
+        Self {
+            start: date,
+            end: date,
+            precision: Unit::Day,
+            relative,
+            explicit_year: false,
+        }
+    }
+
+    fn of(unit: Unit, date: NaiveDate, relative: bool) -> Self {
+        let (start, end) = match unit {
+            Unit::Day => (date, date),
+            Unit::Week => period_bounds(PeriodKind::Week, date),
+            Unit::Month => period_bounds(PeriodKind::Month, date),
+            Unit::Quarter => {
+                quarter_bounds(date.year(), (date.month() - 1) / 3 + 1).unwrap_or((date, date))
+            }
+            Unit::Year => period_bounds(PeriodKind::Year, date),
+        };
+        Self {
+            start,
+            end,
+            precision: unit,
+            relative,
+            explicit_year: false,
+        }
+    }
+
+    fn range(start: NaiveDate, end: NaiveDate, relative: bool) -> Self {
+        Self {
+            start,
+            end,
+            precision: Unit::Day,
+            relative,
+            explicit_year: false,
+        }
+    }
+
+    fn with_explicit_year(mut self) -> Self {
+        self.explicit_year = true;
+        self
+    }
+
+    fn shifted_years(self, years: i32) -> Option<Self> {
+        Some(Self {
+            start: shift(self.start, Unit::Year, i64::from(years))?,
+            end: shift(self.end, Unit::Year, i64::from(years))?,
+            ..self
+        })
+    }
+}
+
+type DateRange = (NaiveDate, NaiveDate);
+
+/// Reference found at a token position, spanning `len` tokens
+struct Found {
+    len: usize,
+    temporal_type: TemporalType,
+    parsed_date: Option<NaiveDate>,
+    date_range: Option<(NaiveDate, NaiveDate)>,
+}
+
+impl Found {
+    fn from_span(len: usize, span: Span) -> Self {
+        let temporal_type = if span.relative {
+            TemporalType::Relative
+        } else {
+            TemporalType::Exact
+        };
+        Self::with_type(len, span, temporal_type)
+    }
+
+    fn with_type(len: usize, span: Span, temporal_type: TemporalType) -> Self {
+        let (parsed_date, date_range) = if span.start == span.end {
+            (Some(span.start), None)
+        } else {
+            (None, Some((span.start, span.end)))
+        };
+        Self {
+            len,
+            temporal_type,
+            parsed_date,
+            date_range,
+        }
+    }
+
+    fn fuzzy(
+        len: usize,
+        parsed_date: Option<NaiveDate>,
+        range: Option<(NaiveDate, NaiveDate)>,
+    ) -> Self {
+        Self {
+            len,
+            temporal_type: TemporalType::Fuzzy,
+            parsed_date,
+            date_range: range,
+        }
+    }
+}
+
+struct Token {
+    text: String,
+    start: usize,
+    end: usize,
+    /// The word ended in a possessive "'s", stripped from `text`
+    possessive: bool,
+}
+
+/// Deterministic parser resolving temporal expressions against a fixed date
+#[derive(Debug, Clone, Copy)]
+pub struct TemporalParser {
+    today: NaiveDate,
+}
+
+impl TemporalParser {
+    pub fn new(today: NaiveDate) -> Self {
+        Self { today }
+    }
+
+    /// Temporal references in `text`, in the order they appear
+    pub fn parse(&self, text: &str) -> Vec<TemporalReference> {
+        let tokens = tokenize(text);
+        let words: Vec<&str> = tokens.iter().map(|token| token.text.as_str()).collect();
+
+        let mut references = Vec::new();
+        let mut index = 0;
+        while index < words.len() {
+            let found = self
+                .reference_at(&words, index)
+                .or_else(|| self.owned_event(&tokens, &words, index));
+            match found {
+                Some(found) => {
+                    let last = &tokens[index + found.len - 1];
+                    references.push(TemporalReference {
+                        raw_text: text[tokens[index].start..last.end].to_string(),
+                        parsed_date: found.parsed_date,
+                        date_range: found.date_range,
+                        temporal_type: found.temporal_type,
+                        anchor_date: None,
+                    });
+                    index += found.len;
+                }
+                None => index += 1,
+            }
+        }
+        references
+    }
+
+    fn reference_at(&self, w: &[&str], i: usize) -> Option<Found> {
+        w.get(i)?;
+        self.fuzzy_phrase(w, i)
+            .or_else(|| {
+                let found = (w[i] == "the").then(|| self.reference_at(w, i + 1))??;
+                Some(Found {
+                    len: found.len + 1,
+                    ..found
+                })
+            })
+            .or_else(|| self.range(w, i))
+            .or_else(|| self.fuzzy(w, i))
+            .or_else(|| {
+                self.expr(w, i)
+                    .map(|(len, span)| Found::from_span(len, span))
+            })
+            .or_else(|| self.event(w, i))
+    }
+
+    /// "birthday", "conferences"
+    fn event(&self, w: &[&str], i: usize) -> Option<Found> {
+        is_event_word(w[i]).then_some(Found {
+            len: 1,
+            temporal_type: TemporalType::Event,
+            parsed_date: None,
+            date_range: None,
+        })
+    }
+
+    /// "Claire's birthday", with the owner kept for resolution
+    fn owned_event(&self, tokens: &[Token], w: &[&str], i: usize) -> Option<Found> {
+        let owned = tokens[i].possessive && w.get(i + 1).is_some_and(|word| is_event_word(word));
+        owned.then_some(Found {
+            len: 2,
+            temporal_type: TemporalType::Event,
+            parsed_date: None,
+            date_range: None,
+        })
+    }
+
+    /// Fixed vague phrases such as "recently" or "a few days ago"
+    fn fuzzy_phrase(&self, w: &[&str], i: usize) -> Option<Found> {
+        let today = self.today;
+        let days_ago = |days: i64| today - Duration::days(days);
+        let phrases: [(&[&str], Option<DateRange>); 8] = [
+            (&["recently"], Some((days_ago(RECENT_DAYS), today))),
+            (&["lately"], Some((days_ago(RECENT_DAYS), today))),
+            (&["the", "other", "day"], Some((days_ago(7), days_ago(1)))),
+            (&["a", "while", "ago"], Some((days_ago(90), days_ago(14)))),
+            (&["a", "while", "back"], Some((days_ago(90), days_ago(14)))),
+            (&["around", "that", "time"], None),
+            (&["at", "that", "time"], None),
+            (&["back", "then"], None),
+        ];
+        for (phrase, range) in phrases {
+            if starts_with(w, i, phrase) {
+                return Some(Found::fuzzy(phrase.len(), None, range));
+            }
+        }
+
+        // "a few days ago", "a couple of weeks ago", "several months ago"
+        let counts: [(&[&str], i64, i64); 5] = [
+            (&["a", "couple", "of"], 2, 3),
+            (&["a", "couple"], 2, 3),
+            (&["a", "few"], 2, 5),
+            (&["few"], 2, 5),
+            (&["several"], 3, 7),
+        ];
+        for (phrase, low, high) in counts {
+            if !starts_with(w, i, phrase) {
+                continue;
+            }
+            let unit_index = i + phrase.len();
+            let unit = unit(w.get(unit_index)?)?;
+            if w.get(unit_index + 1) != Some(&"ago") {
+                return None;
+            }
+            let start = Span::of(unit, shift(today, unit, -high)?, true).start;
+            let end = Span::of(unit, shift(today, unit, -low)?, true).end;
+            return Some(Found::fuzzy(phrase.len() + 2, None, Some((start, end))));
+        }
+        None
+    }
+
+    /// "between X and Y", "from X to Y" and "since X"
+    fn range(&self, w: &[&str], i: usize) -> Option<Found> {
+        match w[i] {
+            "between" | "from" => {
+                let (start_len, start) = self.expr(w, i + 1)?;
+                let connector = w.get(i + 1 + start_len)?;
+                let connects = match w[i] {
+                    "between" => *connector == "and",
+                    _ => ["to", "until", "till", "through", "thru"].contains(connector),
+                };
+                if !connects {
+                    return None;
+                }
+                let (end_len, end) = self.expr(w, i + 2 + start_len)?;
+                let span = combine_range(start, end)?;
+                Some(Found::from_span(start_len + end_len + 2, span))
+            }
+            "since" => {
+                let (len, start) = self.expr(w, i + 1)?;
+                if start.start > self.today {
+                    return None;
+                }
+                Some(Found::from_span(
+                    len + 1,
+                    Span::range(start.start, self.today, true),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// "around X", "sometime in X", "early X", "the end of X"
+    fn fuzzy(&self, w: &[&str], i: usize) -> Option<Found> {
+        if ["around", "roughly", "approximately", "circa", "sometime"].contains(&w[i]) {
+            let skip =
+                usize::from(w[i] == "sometime" && matches!(w.get(i + 1), Some(&"in" | &"around")));
+            let (len, span) = self.expr(w, i + 1 + skip)?;
+            let window = match span.precision {
+                Unit::Day | Unit::Week => FUZZY_DAY_WINDOW,
+                Unit::Month => FUZZY_MONTH_WINDOW,
+                Unit::Quarter | Unit::Year => FUZZY_YEAR_WINDOW,
+            };
+            let center =
+                (span.precision == Unit::Day && span.start == span.end).then_some(span.start);
+            let range = (
+                span.start - Duration::days(window),
+                span.end + Duration::days(window),
+            );
+            return Some(Found::fuzzy(len + 1 + skip, center, Some(range)));
+        }
+
+        let (part, prefix) = match (w[i], w.get(i + 1)) {
+            ("early", _) => (0, 1),
+            ("mid", _) => (1, 1),
+            ("late", _) => (2, 1),
+            ("beginning" | "start", Some(&"of")) => (0, 2),
+            ("middle", Some(&"of")) => (1, 2),
+            ("end", Some(&"of")) => (2, 2),
+            _ => return None,
+        };
+        let (len, span) = self.expr(w, i + prefix)?;
+        let (start, end) = part_of(span, part)?;
+        Some(Found::fuzzy(len + prefix, None, Some((start, end))))
+    }
+
+    /// Exact or relative date expression starting at token `i`
+    fn expr(&self, w: &[&str], i: usize) -> Option<(usize, Span)> {
+        let today = self.today;
+        let word = |offset: usize| w.get(i + offset).copied();
+        let first = word(0)?;
+
+        match first {
+            "today" | "tonight" => return Some((1, Span::day(today, true))),
+            "yesterday" => return Some((1, Span::day(today - Duration::days(1), true))),
+            "tomorrow" => return Some((1, Span::day(today + Duration::days(1), true))),
+            "day" => {
+                let date = match (word(1), word(2)) {
+                    (Some("before"), Some("yesterday")) => today - Duration::days(2),
+                    (Some("after"), Some("tomorrow")) => today + Duration::days(2),
+                    _ => return None,
+                };
+                return Some((3, Span::day(date, true)));
+            }
+            _ => {}
+        }
+
+        if let Some(span) = self.numeric_date(first) {
+            return Some((1, span));
+        }
+
+        // "3 days ago", "two weeks from now"
+        if let (Some(n), Some(unit)) = (count(first), word(1).and_then(unit)) {
+            let (len, n) = match (word(2), word(3)) {
+                (Some("ago"), _) => (3, -n),
+                (Some("from"), Some("now")) => (4, n),
+                _ => (0, 0),
+            };
+            if len > 0 {
+                return Some((len, Span::of(unit, shift(today, unit, n)?, true)));
+            }
+        }
+
+        // "in 3 days", "in a week"
+        if first == "in" {
+            if let (Some(n), Some(unit)) = (word(1).and_then(count), word(2).and_then(unit)) {
+                return Some((3, Span::of(unit, shift(today, unit, n)?, true)));
+            }
+        }
+
+        if let Some(found) = self.relative(w, i) {
+            return Some(found);
+        }
+
+        // "earlier this week", "later this month"
+        if let ("earlier" | "later", Some("this"), Some(unit)) =
+            (first, word(1), word(2).and_then(unit))
+        {
+            let period = Span::of(unit, today, true);
+            let span = if first == "earlier" {
+                Span::range(period.start, today, true)
+            } else {
+                Span::range(today, period.end, true)
+            };
+            return Some((3, span));
+        }
+
+        if let Some(found) = self.month_date(w, i) {
+            return Some(found);
+        }
+
+        if let Some(found) = self.quarter(w, i) {
+            return Some(found);
+        }
+
+        if let Some(weekday) = weekday(first) {
+            let back =
+                (today.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+            return Some((1, Span::day(today - Duration::days(i64::from(back)), true)));
+        }
+
+        if first == "weekend" {
+            // Most recent weekend that has started
+            let anchor = if today.weekday().num_days_from_monday() >= 5 {
+                today
+            } else {
+                today - Duration::days(7)
+            };
+            return Some((1, weekend(anchor, true)));
+        }
+
+        let preceded_by_preposition = i > 0 && DATE_PREPOSITIONS.contains(&w[i - 1]);
+        if let Some(year) = year(first).filter(|_| preceded_by_preposition) {
+            let start = NaiveDate::from_ymd_opt(year, 1, 1)?;
+            return Some((1, Span::of(Unit::Year, start, false).with_explicit_year()));
+        }
+
+        None
+    }
+
+    /// "last month", "next Monday", "past 3 days", "this weekend", "last June"
+    fn relative(&self, w: &[&str], i: usize) -> Option<(usize, Span)> {
+        let today = self.today;
+        let direction: i64 = match w[i] {
+            "last" | "previous" | "past" => -1,
+            "next" | "coming" => 1,
+            "this" | "current" => 0,
+            _ => return None,
+        };
+        let rolling = w[i] == "past";
+        let second = *w.get(i + 1)?;
+
+        // "last 3 days", "next two weeks"
+        if let (Some(n), Some(unit)) = (count(second), w.get(i + 2).and_then(|word| unit(word))) {
+            if direction == 0 {
+                return None;
+            }
+            let other = shift(today, unit, n * direction)?;
+            let span = Span::range(other.min(today), other.max(today), true);
+            return Some((3, span));
+        }
+
+        if let Some(unit) = unit(second) {
+            let span = match (rolling, direction) {
+                (true, _) => Span::range(shift(today, unit, -1)?, today, true),
+                (false, 0) => Span::of(unit, today, true),
+                (false, _) => Span::of(unit, shift(today, unit, direction)?, true),
+            };
+            return Some((2, span));
+        }
+
+        if let Some(weekday) = weekday(second) {
+            let current = i64::from(today.weekday().num_days_from_monday());
+            let target = i64::from(weekday.num_days_from_monday());
+            let offset = match direction {
+                0 => target - current,
+                1 => (target - current + 6).rem_euclid(7) + 1,
+                _ => -((current - target + 6).rem_euclid(7) + 1),
+            };
+            return Some((2, Span::day(today + Duration::days(offset), true)));
+        }
+
+        if second == "weekend" {
+            let anchor = today + Duration::days(7 * direction);
+            return Some((2, weekend(anchor, true)));
+        }
+
+        if let Some(month) = full_month(second) {
+            let current = today.month();
+            let year = match direction {
+                0 => today.year(),
+                1 if month > current => today.year(),
+                1 => today.year() + 1,
+                _ if month < current => today.year(),
+                _ => today.year() - 1,
+            };
+            let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+            return Some((2, Span::of(Unit::Month, start, true)));
+        }
+
+        None
+    }
+
+    /// "June 15", "June 15th, 2023", "15th of June", "June 2023", "June"
+    fn month_date(&self, w: &[&str], i: usize) -> Option<(usize, Span)> {
+        let word = |offset: usize| w.get(i + offset).copied();
+
+        if let Some(month) = month(w[i]) {
+            if let Some(day) = word(1).and_then(day_of_month) {
+                return match word(2).and_then(year) {
+                    Some(year) => {
+                        let date = NaiveDate::from_ymd_opt(year, month, day)?;
+                        Some((3, Span::day(date, false).with_explicit_year()))
+                    }
+                    None => Some((2, Span::day(self.most_recent(month, day)?, false))),
+                };
+            }
+            if let Some(year) = word(1).and_then(year) {
+                let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+                return Some((2, Span::of(Unit::Month, start, false).with_explicit_year()));
+            }
+            let preceded_by_preposition = i > 0 && DATE_PREPOSITIONS.contains(&w[i - 1]);
+            let ambiguous = matches!(w[i], "may" | "march");
+            if full_month(w[i]).is_some() && (!ambiguous || preceded_by_preposition) {
+                let year = if month <= self.today.month() {
+                    self.today.year()
+                } else {
+                    self.today.year() - 1
+                };
+                let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+                return Some((1, Span::of(Unit::Month, start, false)));
+            }
+            return None;
+        }
+
+        let day = day_of_month(w[i])?;
+        let of = usize::from(word(1) == Some("of"));
+        let month = month(word(1 + of)?)?;
+        match word(2 + of).and_then(year) {
+            Some(year) => {
+                let date = NaiveDate::from_ymd_opt(year, month, day)?;
+                Some((3 + of, Span::day(date, false).with_explicit_year()))
+            }
+            None => Some((2 + of, Span::day(self.most_recent(month, day)?, false))),
+        }
+    }
+
+    /// "Q3", "Q3 2023", "third quarter", "the second quarter of 2024"
+    fn quarter(&self, w: &[&str], i: usize) -> Option<(usize, Span)> {
+        let (quarter, mut len) = match w[i].strip_prefix('q').and_then(|q| q.parse::<u32>().ok()) {
+            Some(quarter) => (quarter, 1),
+            None => {
+                let quarter = match w[i] {
+                    "first" | "1st" => 1,
+                    "second" | "2nd" => 2,
+                    "third" | "3rd" => 3,
+                    "fourth" | "4th" => 4,
+                    _ => return None,
+                };
+                if w.get(i + 1) != Some(&"quarter") {
+                    return None;
+                }
+                (quarter, 2)
+            }
+        };
+        if w.get(i + len) == Some(&"of") && w.get(i + len + 1).and_then(|word| year(word)).is_some()
+        {
+            len += 1;
+        }
+
+        match w.get(i + len).and_then(|word| year(word)) {
+            Some(year) => {
+                let (start, end) = quarter_bounds(year, quarter)?;
+                let span = Span::range(start, end, false);
+                Some((
+                    len + 1,
+                    Span {
+                        precision: Unit::Quarter,
+                        ..span
+                    }
+                    .with_explicit_year(),
+                ))
+            }
+            None => {
+                let (start, end) = quarter_bounds(self.today.year(), quarter)?;
+                let (start, end) = if start > self.today {
+                    quarter_bounds(self.today.year() - 1, quarter)?
+                } else {
+                    (start, end)
+                };
+                let span = Span::range(start, end, false);
+                Some((
+                    len,
+                    Span {
+                        precision: Unit::Quarter,
+                        ..span
+                    },
+                ))
+            }
+        }
+    }
+
+    /// Day/month in numeric form: `2023-06-15`, `2023-06`, `15/06`, `15/06/2023`, `15.06.2023`
+    ///
+    /// Dotted dates need a four-digit year, since "3.10.24" reads as well as a
+    /// version number. Without a year, two single digits ("1/2", "3/4") are
+    /// taken as a fraction.
+    fn numeric_date(&self, word: &str) -> Option<Span> {
+        let separator = ['-', '/', '.']
+            .into_iter()
+            .find(|sep| word.contains(*sep))?;
+        let parts: Vec<&str> = word.split(separator).collect();
+        if parts
+            .iter()
+            .any(|part| part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()))
+        {
+            return None;
+        }
+        let number = |index: usize| parts[index].parse::<u32>().ok();
+
+        if parts[0].len() == 4 {
+            let year = parts[0].parse().ok()?;
+            return match parts.len() {
+                2 if separator == '-' => {
+                    let start = NaiveDate::from_ymd_opt(year, number(1)?, 1)?;
+                    Some(Span::of(Unit::Month, start, false).with_explicit_year())
+                }
+                3 => {
+                    let date = NaiveDate::from_ymd_opt(year, number(1)?, number(2)?)?;
+                    Some(Span::day(date, false).with_explicit_year())
+                }
+                _ => None,
+            };
+        }
+
+        if separator == '-' || !(2..=3).contains(&parts.len()) {
+            return None;
+        }
+        let (first, second) = (number(0)?, number(1)?);
+        let is_day_or_month = |value: u32| (1..=31).contains(&value);
+        match parts.get(2) {
+            Some(year) if separator == '.' && year.len() != 4 => return None,
+            None if separator == '.' => return None,
+            // "1/2" is a fraction and "40/50" a score
+            None if parts.iter().all(|part| part.len() == 1)
+                || !(is_day_or_month(first) && is_day_or_month(second)) =>
+            {
+                return None
+            }
+            _ => {}
+        }
+
+        // Day first unless only the other order is valid
+        let (day, month) = if second > 12 && first <= 12 {
+            (second, first)
+        } else {
+            (first, second)
+        };
+        match parts.get(2) {
+            Some(year) => {
+                let year = match year.len() {
+                    2 => 2000 + year.parse::<i32>().ok()?,
+                    4 => year.parse().ok()?,
+                    _ => return None,
+                };
+                let date = NaiveDate::from_ymd_opt(year, month, day)?;
+                Some(Span::day(date, false).with_explicit_year())
+            }
+            None => Some(Span::day(self.most_recent(month, day)?, false)),
+        }
+    }
+
+    /// Most recent occurrence of a month and day on or before today
+    fn most_recent(&self, month: u32, day: u32) -> Option<NaiveDate> {
+        match NaiveDate::from_ymd_opt(self.today.year(), month, day) {
+            Some(date) if date <= self.today => Some(date),
+            _ => NaiveDate::from_ymd_opt(self.today.year() - 1, month, day),
+        }
+    }
+}
+
+/// Range from the start of one span to the end of another
+fn combine_range(mut start: Span, mut end: Span) -> Option<Span> {
+    if start.explicit_year && !end.explicit_year {
+        end = end.shifted_years(start.start.year() - end.start.year())?;
+    }
+    if start.start > end.end && !start.explicit_year {
+        start = start.shifted_years(-1)?;
+    }
+    if start.start > end.end {
+        return None;
+    }
+    Some(Span::range(
+        start.start,
+        end.end,
+        start.relative || end.relative,
+    ))
+}
+
+/// Early, middle or late third of a span, in whole months where possible
+fn part_of(span: Span, part: u32) -> Option<(NaiveDate, NaiveDate)> {
+    match span.precision {
+        Unit::Month => {
+            let start = span.start + Duration::days(i64::from(part) * 10);
+            let end = if part == 2 {
+                span.end
+            } else {
+                start + Duration::days(9)
+            };
+            Some((start, end))
+        }
+        Unit::Quarter | Unit::Year => {
+            let months = (span.end.year() - span.start.year()) as u32 * 12 + span.end.month()
+                - span.start.month()
+                + 1;
+            let per_part = months / 3;
+            let start = span
+                .start
+                .checked_add_months(Months::new(part * per_part))?;
+            let end = if part == 2 {
+                span.end
+            } else {
+                start
+                    .checked_add_months(Months::new(per_part))?
+                    .pred_opt()?
+            };
+            Some((start, end))
+        }
+        Unit::Day | Unit::Week => None,
+    }
+}
+
+fn period_bounds(kind: PeriodKind, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let period = Period::containing(kind, date);
+    (period.start(), period.end())
+}
+
+fn quarter_bounds(year: i32, quarter: u32) -> Option<(NaiveDate, NaiveDate)> {
+    if !(1..=4).contains(&quarter) {
+        return None;
+    }
+    let start = NaiveDate::from_ymd_opt(year, (quarter - 1) * 3 + 1, 1)?;
+    let end = start.checked_add_months(Months::new(3))?.pred_opt()?;
+    Some((start, end))
+}
+
+/// Saturday and Sunday of the ISO week containing `date`
+fn weekend(date: NaiveDate, relative: bool) -> Span {
+    let (monday, _) = period_bounds(PeriodKind::Week, date);
+    Span::range(
+        monday + Duration::days(5),
+        monday + Duration::days(6),
+        relative,
+    )
+}
+
+fn shift(date: NaiveDate, unit: Unit, amount: i64) -> Option<NaiveDate> {
+    let months = match unit {
+        Unit::Day => return date.checked_add_signed(Duration::days(amount)),
+        Unit::Week => return date.checked_add_signed(Duration::weeks(amount)),
+        Unit::Month => amount,
+        Unit::Quarter => amount * 3,
+        Unit::Year => amount * 12,
+    };
+    let magnitude = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+    if months < 0 {
+        date.checked_sub_months(magnitude)
+    } else {
+        date.checked_add_months(magnitude)
+    }
+}
+
+fn starts_with(w: &[&str], i: usize, phrase: &[&str]) -> bool {
+    w.get(i..i + phrase.len()) == Some(phrase)
+}
+
+fn unit(word: &str) -> Option<Unit> {
+    match word.strip_suffix('s').unwrap_or(word) {
+        "day" => Some(Unit::Day),
+        "week" => Some(Unit::Week),
+        "month" => Some(Unit::Month),
+        "quarter" => Some(Unit::Quarter),
+        "year" => Some(Unit::Year),
+        _ => None,
+    }
+}
+
+fn count(word: &str) -> Option<i64> {
+    if word == "a" || word == "an" {
+        return Some(1);
+    }
+    if let Some(n) = NUMBER_WORDS.iter().position(|number| *number == word) {
+        return Some(n as i64);
+    }
+    word.parse::<i64>().ok().filter(|n| (0..=1000).contains(n))
+}
+
+fn full_month(word: &str) -> Option<u32> {
+    MONTHS
+        .iter()
+        .position(|month| *month == word)
+        .map(|index| index as u32 + 1)
+}
+
+/// Full month name or its abbreviation ("jun", "sept")
+fn month(word: &str) -> Option<u32> {
+    full_month(word).or_else(|| {
+        let word = if word == "sept" { "sep" } else { word };
+        (word.len() == 3)
+            .then(|| MONTHS.iter().position(|month| month.starts_with(word)))?
+            .map(|index| index as u32 + 1)
+    })
+}
+
+fn weekday(word: &str) -> Option<Weekday> {
+    let word = word.strip_suffix('s').unwrap_or(word);
+    WEEKDAYS
+        .iter()
+        .find(|(name, _)| *name == word)
+        .map(|(_, weekday)| *weekday)
+}
+
+/// Day of month, with or without an ordinal suffix
+fn day_of_month(word: &str) -> Option<u32> {
+    let digits = ["st", "nd", "rd", "th"]
+        .iter()
+        .find_map(|suffix| word.strip_suffix(suffix))
+        .unwrap_or(word);
+    if digits.is_empty() || digits.len() > 2 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+fn year(word: &str) -> Option<i32> {
+    if word.len() != 4 || !word.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    word.parse()
+        .ok()
+        .filter(|year| (1900..=2100).contains(year))
+}
+
+/// Temporal reference in the shape returned by the extraction prompt
+#[derive(Debug, Deserialize)]
+pub(crate) struct ModelTemporalReference {
+    pub(crate) text: String,
+    #[serde(rename = "type")]
+    temporal_type: TemporalType,
+    #[serde(default)]
+    start: Option<NaiveDate>,
+    #[serde(default)]
+    end: Option<NaiveDate>,
+}
+
+impl ModelTemporalReference {
+    /// A single day becomes `parsed_date`, a longer span `date_range`
+    pub(crate) fn into_reference(self) -> TemporalReference {
+        let (parsed_date, date_range) = match (self.start, self.end) {
+            (Some(start), Some(end)) if end > start => (None, Some((start, end))),
+            (Some(start), Some(end)) if end == start => (Some(start), None),
+            (Some(start), None) => (Some(start), None),
+            _ => (None, None),
+        };
+        TemporalReference {
+            raw_text: self.text,
+            parsed_date,
+            date_range,
+            temporal_type: self.temporal_type,
+            anchor_date: None,
+        }
+    }
+}
+
+/// Whether a query has time words worth asking the model about
+pub(crate) fn mentions_time(query: &str) -> bool {
+    let cues = [
+        "ago", "before", "after", "during", "since", "until", "when", "day", "days", "week",
+        "weeks", "weekend", "month", "months", "year", "years", "season", "spring", "summer",
+        "autumn", "fall", "winter", "holiday", "holidays", "morning", "evening", "night",
+        "recently", "earlier", "later",
+    ];
+    let has_cue = query
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| cues.contains(&word.to_lowercase().as_str()));
+    has_cue
+        || query
+            .split_whitespace()
+            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+            .any(is_date_or_time)
+}
+
+/// "2023", "15th", "Q2", "3pm", "10:30", "15/06" or "2023-06-15"
+fn is_date_or_time(word: &str) -> bool {
+    let word = word.to_lowercase();
+    let all_digits = |text: &str| !text.is_empty() && text.chars().all(|c| c.is_ascii_digit());
+    let number_before = |suffixes: &[&str]| {
+        suffixes.iter().any(|suffix| {
+            word.strip_suffix(suffix)
+                .is_some_and(|number| all_digits(number) && number.len() <= 2)
+        })
+    };
+
+    if all_digits(&word) {
+        return word.len() == 4 && (1900..=2099).contains(&word.parse::<u32>().unwrap_or(0));
+    }
+    if number_before(&["st", "nd", "rd", "th", "am", "pm"]) {
+        return true;
+    }
+    if matches!(word.as_str(), "q1" | "q2" | "q3" | "q4") {
+        return true;
+    }
+    if let Some((hours, minutes)) = word.split_once(':') {
+        let minutes = minutes.trim_end_matches("am").trim_end_matches("pm");
+        return all_digits(hours) && hours.len() <= 2 && all_digits(minutes) && minutes.len() == 2;
+    }
+    // Day, month and year joined by a separator; dots need all three
+    ['/', '-', '.'].iter().any(|&separator| {
+        let parts: Vec<&str> = word.split(separator).collect();
+        let needed = if separator == '.' { 3..=3 } else { 2..=3 };
+        needed.contains(&parts.len())
+            && parts
+                .iter()
+                .all(|part| all_digits(part) && (part.len() <= 2 || part.len() == 4))
+    })
+}
+
+/// Event word, singular or plural
+fn is_event_word(word: &str) -> bool {
+    let word = word.strip_suffix('s').unwrap_or(word);
+    EVENT_WORDS.contains(&word)
+}
+
+/// Lowercase words with their byte offsets; hyphenated words like "mid-june"
+/// are split and possessive "'s" is stripped
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut push = |start: usize, piece: &str| {
+        let trimmed = piece.trim_start_matches(|c: char| !c.is_alphanumeric());
+        let start = start + piece.len() - trimmed.len();
+        let trimmed = trimmed.trim_end_matches(|c: char| !c.is_alphanumeric());
+        if trimmed.is_empty() {
+            return;
+        }
+        let lower = trimmed.to_lowercase().replace('\u{2019}', "'");
+        let (text, possessive) = match lower.strip_suffix("'s") {
+            Some(owner) if !owner.is_empty() => (owner.to_string(), true),
+            _ => (lower, false),
+        };
+        tokens.push(Token {
+            text,
+            start,
+            end: start + trimmed.len(),
+            possessive,
+        });
+    };
+
+    let mut offset = 0;
+    for piece in text.split_whitespace() {
+        let start = offset + text[offset..].find(piece).unwrap_or(0);
+        offset = start + piece.len();
+        let split_hyphen = piece.contains('-')
+            && piece
+                .split('-')
+                .all(|part| !part.is_empty() && part.chars().all(char::is_alphabetic));
+        if split_hyphen {
+            let mut part_start = start;
+            for part in piece.split('-') {
+                push(part_start, part);
+                part_start += part.len() + 1;
+            }
+        } else {
+            push(start, piece);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sunday
+    fn today() -> NaiveDate {
+        date("2026-10-18")
+    }
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    /// Each case: query, raw text, type, parsed date, date range
+    type Case = (
+        &'static str,
+        &'static str,
+        TemporalType,
+        Option<&'static str>,
+        Option<(&'static str, &'static str)>,
+    );
+
+    fn check(cases: &[Case]) {
+        let parser = TemporalParser::new(today());
+        for (query, raw, temporal_type, parsed_date, date_range) in cases {
+            let references = parser.parse(query);
+            assert_eq!(references.len(), 1, "{query}: {references:?}");
+            let reference = &references[0];
+            assert_eq!(reference.raw_text, *raw, "{query}");
+            assert_eq!(reference.temporal_type, *temporal_type, "{query}");
+            assert_eq!(reference.parsed_date, parsed_date.map(date), "{query}");
+            assert_eq!(
+                reference.date_range,
+                date_range.map(|(start, end)| (date(start), date(end))),
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_absolute_dates() {
+        use TemporalType::Exact;
+        check(&[
+            (
+                "notes from 2023-06-15",
+                "2023-06-15",
+                Exact,
+                Some("2023-06-15"),
+                None,
+            ),
+            (
+                "what happened on June 15?",
+                "June 15",
+                Exact,
+                Some("2026-06-15"),
+                None,
+            ),
+            (
+                "June 15th, 2023 meeting",
+                "June 15th, 2023",
+                Exact,
+                Some("2023-06-15"),
+                None,
+            ),
+            (
+                "the 15th of June",
+                "the 15th of June",
+                Exact,
+                Some("2026-06-15"),
+                None,
+            ),
+            (
+                "on 3 Sept 2024",
+                "3 Sept 2024",
+                Exact,
+                Some("2024-09-03"),
+                None,
+            ),
+            (
+                "Christmas is December 25",
+                "December 25",
+                Exact,
+                Some("2025-12-25"),
+                None,
+            ),
+            (
+                "photos from 15/06",
+                "15/06",
+                Exact,
+                Some("2026-06-15"),
+                None,
+            ),
+            (
+                "15/06/2023 notes",
+                "15/06/2023",
+                Exact,
+                Some("2023-06-15"),
+                None,
+            ),
+            (
+                "06/15/23 notes",
+                "06/15/23",
+                Exact,
+                Some("2023-06-15"),
+                None,
+            ),
+            (
+                "due 15.06.2023",
+                "15.06.2023",
+                Exact,
+                Some("2023-06-15"),
+                None,
+            ),
+            ("photos from 3/15", "3/15", Exact, Some("2026-03-15"), None),
+            (
+                "in June 2023",
+                "June 2023",
+                Exact,
+                None,
+                Some(("2023-06-01", "2023-06-30")),
+            ),
+            (
+                "budget for 2024-02",
+                "2024-02",
+                Exact,
+                None,
+                Some(("2024-02-01", "2024-02-29")),
+            ),
+            (
+                "what did I write in November",
+                "November",
+                Exact,
+                None,
+                Some(("2025-11-01", "2025-11-30")),
+            ),
+            (
+                "trips in 2023",
+                "2023",
+                Exact,
+                None,
+                Some(("2023-01-01", "2023-12-31")),
+            ),
+            (
+                "Q3 results",
+                "Q3",
+                Exact,
+                None,
+                Some(("2026-07-01", "2026-09-30")),
+            ),
+            (
+                "Q1 2025 planning",
+                "Q1 2025",
+                Exact,
+                None,
+                Some(("2025-01-01", "2025-03-31")),
+            ),
+            (
+                "the fourth quarter of 2024",
+                "the fourth quarter of 2024",
+                Exact,
+                None,
+                Some(("2024-10-01", "2024-12-31")),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_relative_expressions() {
+        use TemporalType::Relative;
+        check(&[
+            (
+                "what did I do yesterday",
+                "yesterday",
+                Relative,
+                Some("2026-10-17"),
+                None,
+            ),
+            (
+                "the day before yesterday",
+                "the day before yesterday",
+                Relative,
+                Some("2026-10-16"),
+                None,
+            ),
+            (
+                "3 days ago",
+                "3 days ago",
+                Relative,
+                Some("2026-10-15"),
+                None,
+            ),
+            (
+                "two weeks ago",
+                "two weeks ago",
+                Relative,
+                None,
+                Some(("2026-09-28", "2026-10-04")),
+            ),
+            (
+                "a month ago",
+                "a month ago",
+                Relative,
+                None,
+                Some(("2026-09-01", "2026-09-30")),
+            ),
+            (
+                "remind me in 3 days",
+                "in 3 days",
+                Relative,
+                Some("2026-10-21"),
+                None,
+            ),
+            (
+                "next Monday",
+                "next Monday",
+                Relative,
+                Some("2026-10-19"),
+                None,
+            ),
+            (
+                "last Monday",
+                "last Monday",
+                Relative,
+                Some("2026-10-12"),
+                None,
+            ),
+            (
+                "this Wednesday",
+                "this Wednesday",
+                Relative,
+                Some("2026-10-14"),
+                None,
+            ),
+            ("on Friday", "Friday", Relative, Some("2026-10-16"), None),
+            (
+                "last week",
+                "last week",
+                Relative,
+                None,
+                Some(("2026-10-05", "2026-10-11")),
+            ),
+            (
+                "this week",
+                "this week",
+                Relative,
+                None,
+                Some(("2026-10-12", "2026-10-18")),
+            ),
+            (
+                "yesterday's notes",
+                "yesterday's",
+                Relative,
+                Some("2026-10-17"),
+                None,
+            ),
+            (
+                "today’s tasks",
+                "today’s",
+                Relative,
+                Some("2026-10-18"),
+                None,
+            ),
+            (
+                "last week's meeting",
+                "last week's",
+                Relative,
+                None,
+                Some(("2026-10-05", "2026-10-11")),
+            ),
+            (
+                "past week",
+                "past week",
+                Relative,
+                None,
+                Some(("2026-10-11", "2026-10-18")),
+            ),
+            (
+                "last month",
+                "last month",
+                Relative,
+                None,
+                Some(("2026-09-01", "2026-09-30")),
+            ),
+            (
+                "next year",
+                "next year",
+                Relative,
+                None,
+                Some(("2027-01-01", "2027-12-31")),
+            ),
+            (
+                "last quarter",
+                "last quarter",
+                Relative,
+                None,
+                Some(("2026-07-01", "2026-09-30")),
+            ),
+            (
+                "the last 3 days",
+                "the last 3 days",
+                Relative,
+                None,
+                Some(("2026-10-15", "2026-10-18")),
+            ),
+            (
+                "past two weeks",
+                "past two weeks",
+                Relative,
+                None,
+                Some(("2026-10-04", "2026-10-18")),
+            ),
+            (
+                "last June",
+                "last June",
+                Relative,
+                None,
+                Some(("2026-06-01", "2026-06-30")),
+            ),
+            (
+                "next October",
+                "next October",
+                Relative,
+                None,
+                Some(("2027-10-01", "2027-10-31")),
+            ),
+            (
+                "last weekend",
+                "last weekend",
+                Relative,
+                None,
+                Some(("2026-10-10", "2026-10-11")),
+            ),
+            (
+                "over the weekend",
+                "the weekend",
+                Relative,
+                None,
+                Some(("2026-10-17", "2026-10-18")),
+            ),
+            (
+                "earlier this month",
+                "earlier this month",
+                Relative,
+                None,
+                Some(("2026-10-01", "2026-10-18")),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_ranges() {
+        use TemporalType::{Exact, Relative};
+        check(&[
+            (
+                "between May and July",
+                "between May and July",
+                Exact,
+                None,
+                Some(("2026-05-01", "2026-07-31")),
+            ),
+            (
+                "between November and February",
+                "between November and February",
+                Exact,
+                None,
+                Some(("2025-11-01", "2026-02-28")),
+            ),
+            (
+                "from June 1 to June 15",
+                "from June 1 to June 15",
+                Exact,
+                None,
+                Some(("2026-06-01", "2026-06-15")),
+            ),
+            (
+                "from March 2024 until May",
+                "from March 2024 until May",
+                Exact,
+                None,
+                Some(("2024-03-01", "2024-05-31")),
+            ),
+            (
+                "between 2022 and 2023",
+                "between 2022 and 2023",
+                Exact,
+                None,
+                Some(("2022-01-01", "2023-12-31")),
+            ),
+            (
+                "since August",
+                "since August",
+                Relative,
+                None,
+                Some(("2026-08-01", "2026-10-18")),
+            ),
+            (
+                "from last Monday to yesterday",
+                "from last Monday to yesterday",
+                Relative,
+                None,
+                Some(("2026-10-12", "2026-10-17")),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_fuzzy_windows_and_events() {
+        use TemporalType::{Event, Fuzzy};
+        check(&[
+            (
+                "around June 15",
+                "around June 15",
+                Fuzzy,
+                Some("2026-06-15"),
+                Some(("2026-06-12", "2026-06-18")),
+            ),
+            (
+                "sometime in March",
+                "sometime in March",
+                Fuzzy,
+                None,
+                Some(("2026-02-22", "2026-04-07")),
+            ),
+            (
+                "early June",
+                "early June",
+                Fuzzy,
+                None,
+                Some(("2026-06-01", "2026-06-10")),
+            ),
+            (
+                "mid-June 2024",
+                "mid-June 2024",
+                Fuzzy,
+                None,
+                Some(("2024-06-11", "2024-06-20")),
+            ),
+            (
+                "late 2025",
+                "late 2025",
+                Fuzzy,
+                None,
+                Some(("2025-09-01", "2025-12-31")),
+            ),
+            (
+                "the end of last month",
+                "the end of last month",
+                Fuzzy,
+                None,
+                Some(("2026-09-21", "2026-09-30")),
+            ),
+            (
+                "what did I note recently",
+                "recently",
+                Fuzzy,
+                None,
+                Some(("2026-10-04", "2026-10-18")),
+            ),
+            (
+                "a few days ago",
+                "a few days ago",
+                Fuzzy,
+                None,
+                Some(("2026-10-13", "2026-10-16")),
+            ),
+            (
+                "the other day",
+                "the other day",
+                Fuzzy,
+                None,
+                Some(("2026-10-11", "2026-10-17")),
+            ),
+            ("around that time", "around that time", Fuzzy, None, None),
+            (
+                "during Claire's birthday",
+                "Claire's birthday",
+                Event,
+                None,
+                None,
+            ),
+            ("after the conference", "the conference", Event, None, None),
+        ]);
+    }
+
+    #[test]
+    fn test_non_temporal_text_and_multiple_references() {
+        let parser = TemporalParser::new(today());
+        for query in [
+            "may I see my notes",
+            "the 3 tasks",
+            "version 1.2 notes",
+            "march forward on the roadmap",
+            "release 3.10.24 is out",
+            "add 1/2 cup of flour",
+            "scored 40/50 on the quiz",
+            "ratio of 0/12",
+            "",
+        ] {
+            assert!(parser.parse(query).is_empty(), "{query}");
+        }
+
+        let references = parser.parse("compare last week with Q2 2025");
+        let raw: Vec<_> = references.iter().map(|r| r.raw_text.as_str()).collect();
+        assert_eq!(raw, vec!["last week", "Q2 2025"]);
+    }
+
+    fn model_reference(start: Option<&str>, end: Option<&str>) -> TemporalReference {
+        ModelTemporalReference {
+            text: "then".to_string(),
+            temporal_type: TemporalType::Fuzzy,
+            start: start.map(date),
+            end: end.map(date),
+        }
+        .into_reference()
+    }
+
+    #[test]
+    fn test_model_ranges_have_no_parsed_date() {
+        let range = model_reference(Some("2026-06-01"), Some("2026-06-30"));
+        assert_eq!(range.parsed_date, None);
+        assert_eq!(
+            range.date_range,
+            Some((date("2026-06-01"), date("2026-06-30")))
+        );
+
+        for day in [
+            model_reference(Some("2026-06-15"), Some("2026-06-15")),
+            model_reference(Some("2026-06-15"), None),
+        ] {
+            assert_eq!(day.parsed_date, Some(date("2026-06-15")));
+            assert_eq!(day.date_range, None);
+        }
+
+        let reversed = model_reference(Some("2026-06-30"), Some("2026-06-01"));
+        assert_eq!((reversed.parsed_date, reversed.date_range), (None, None));
+    }
+
+    #[test]
+    fn test_only_date_and_time_patterns_count_as_time() {
+        for query in [
+            "notes from 2023",
+            "the 15th",
+            "Q2 planning",
+            "call at 3pm",
+            "standup at 10:30",
+            "photos from 15/06",
+            "2023-06-15 log",
+            "trip on 03.09.2024",
+            "what happened last week",
+        ] {
+            assert!(mentions_time(query), "{query}");
+        }
+        for query in [
+            "3 bugs left",
+            "v2 of the spec",
+            "version 1.2 notes",
+            "top 10 ideas",
+            "room 101",
+            "call Claire",
+        ] {
+            assert!(!mentions_time(query), "{query}");
+        }
+    }
+}
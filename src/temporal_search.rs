@@ -0,0 +1,224 @@
+//! Temporal search over date-node membership
+//!
+//! Content belongs to a date through its date-node root, so a temporal
+//! reference is resolved to the dates it covers and each date's tree is
+//! fetched with one indexed `get_nodes_by_root` lookup, several dates at a
+//! time. Fuzzy references score each date by its distance from the
+//! reference's center.
+
+use crate::date_range::lookup_days;
+use crate::scoped_query::flatten_hierarchy;
+use crate::{
+    constants, DataStore, NLPEngine, NodeSpaceService, SearchResult, TemporalReference,
+    TemporalType,
+};
+use chrono::{Duration, NaiveDate};
+use futures::TryStreamExt;
+use nodespace_core_types::NodeSpaceResult;
+use std::pin::pin;
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Nodes under the date nodes a temporal reference covers, best first
+    pub(crate) async fn search_by_temporal_ref(
+        &self,
+        temporal_ref: &TemporalReference,
+    ) -> NodeSpaceResult<Vec<SearchResult>> {
+        if temporal_ref.temporal_type == TemporalType::Event && temporal_ref.date_range.is_none() {
+            // Unanchored events fall back to searching for their mentions
+            return self.search_by_entity(&temporal_ref.raw_text).await;
+        }
+
+        let limit = constants::DEFAULT_MAX_RESULTS_PER_STRATEGY;
+        let days = lookup_days(
+            scored_dates(temporal_ref),
+            |(date, date_score)| async move {
+                let Some((date_node, tree_nodes)) = self.date_tree_nodes(date).await? else {
+                    return Ok(vec![]);
+                };
+                let hierarchy =
+                    self.build_hierarchical_tree_from_flat_list(tree_nodes, &date_node.id, 0)?;
+                Ok(flatten_hierarchy(&hierarchy)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, node)| SearchResult {
+                        node_id: node.id.clone(),
+                        node,
+                        score: node_score(date_score, index),
+                    })
+                    .collect::<Vec<_>>())
+            },
+        );
+        let mut days = pin!(days);
+
+        let mut results = Vec::new();
+        while let Some(day_results) = days.try_next().await? {
+            results.extend(day_results);
+            // Dates come best first; stop once a page of results is collected
+            if results.len() >= limit {
+                break;
+            }
+        }
+
+        results.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        results.truncate(limit);
+        Ok(results)
+    }
+}
+
+/// Dates to search for a reference with the base score of their nodes, best first
+///
+/// Exact and relative ranges are searched newest first and capped at
+/// `MAX_DATE_RANGE_DAYS`. Fuzzy and anchored event references decay with
+/// distance from the parsed date, or from the middle of the range.
+pub(crate) fn scored_dates(temporal_ref: &TemporalReference) -> Vec<(NaiveDate, f32)> {
+    let (start, end) = match (temporal_ref.parsed_date, temporal_ref.date_range) {
+        (_, Some((start, end))) => (start, end),
+        (Some(date), None) => (date, date),
+        (None, None) => return vec![],
+    };
+    if end < start {
+        return vec![];
+    }
+    let start = start.max(end - Duration::days(constants::MAX_DATE_RANGE_DAYS - 1));
+    let newest_first = end.iter_days().rev().take_while(|date| *date >= start);
+
+    match temporal_ref.temporal_type {
+        TemporalType::Exact | TemporalType::Relative => newest_first
+            .map(|date| (date, constants::TEMPORAL_MATCH_SCORE))
+            .collect(),
+        TemporalType::Fuzzy | TemporalType::Event => {
+            let center = temporal_ref
+                .parsed_date
+                .unwrap_or(start + (end - start) / 2);
+            let mut dates: Vec<_> = newest_first
+                .map(|date| {
+                    let distance = (date - center).num_days().abs() as f32;
+                    let decay = 0.5_f32.powf(distance / constants::TEMPORAL_DECAY_HALF_LIFE_DAYS);
+                    (date, constants::TEMPORAL_MATCH_SCORE * decay)
+                })
+                .collect();
+            // Stable, so equally distant dates stay newest first
+            dates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+            dates
+        }
+    }
+}
+
+/// Score of the `index`-th node under a date, which stays at zero for busy days
+fn node_score(date_score: f32, index: usize) -> f32 {
+    (date_score - index as f32 * constants::SCORE_DECAY_FACTOR * 0.5).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn reference(
+        temporal_type: TemporalType,
+        parsed_date: Option<NaiveDate>,
+        date_range: Option<(NaiveDate, NaiveDate)>,
+    ) -> TemporalReference {
+        TemporalReference {
+            raw_text: String::new(),
+            parsed_date,
+            date_range,
+            temporal_type,
+            anchor_date: None,
+        }
+    }
+
+    #[test]
+    fn test_exact_dates_and_ranges_are_searched_newest_first() {
+        let single = scored_dates(&reference(
+            TemporalType::Exact,
+            Some(date(2026, 6, 15)),
+            None,
+        ));
+        assert_eq!(
+            single,
+            vec![(date(2026, 6, 15), constants::TEMPORAL_MATCH_SCORE)]
+        );
+
+        let week = scored_dates(&reference(
+            TemporalType::Relative,
+            None,
+            Some((date(2026, 10, 5), date(2026, 10, 11))),
+        ));
+        assert_eq!(week.len(), 7);
+        assert_eq!(week[0].0, date(2026, 10, 11));
+        assert_eq!(week[6].0, date(2026, 10, 5));
+        assert!(week
+            .iter()
+            .all(|(_, score)| *score == constants::TEMPORAL_MATCH_SCORE));
+    }
+
+    #[test]
+    fn test_fuzzy_references_decay_with_distance_from_center() {
+        let dates = scored_dates(&reference(
+            TemporalType::Fuzzy,
+            Some(date(2026, 6, 15)),
+            Some((date(2026, 6, 12), date(2026, 6, 18))),
+        ));
+
+        assert_eq!(dates.len(), 7);
+        assert_eq!(
+            dates[0],
+            (date(2026, 6, 15), constants::TEMPORAL_MATCH_SCORE)
+        );
+        // One day either side ties; the newer date comes first
+        assert_eq!(dates[1].0, date(2026, 6, 16));
+        assert_eq!(dates[2].0, date(2026, 6, 14));
+        assert!(dates[1].1 < dates[0].1 && dates[1].1 == dates[2].1);
+        assert!(dates[6].1 < dates[2].1);
+
+        // Without a parsed date the middle of the range is the center
+        let early_june = scored_dates(&reference(
+            TemporalType::Fuzzy,
+            None,
+            Some((date(2026, 6, 1), date(2026, 6, 9))),
+        ));
+        assert_eq!(early_june[0].0, date(2026, 6, 5));
+    }
+
+    #[test]
+    fn test_long_and_unresolved_references_are_bounded() {
+        let years = scored_dates(&reference(
+            TemporalType::Exact,
+            None,
+            Some((date(2022, 1, 1), date(2023, 12, 31))),
+        ));
+        assert_eq!(years.len(), constants::MAX_DATE_RANGE_DAYS as usize);
+        assert_eq!(years[0].0, date(2023, 12, 31));
+
+        assert!(scored_dates(&reference(TemporalType::Fuzzy, None, None)).is_empty());
+        assert!(scored_dates(&reference(TemporalType::Event, None, None)).is_empty());
+
+        // Anchored events decay around the anchor like fuzzy references
+        let anchored = scored_dates(&reference(
+            TemporalType::Event,
+            Some(date(2026, 3, 14)),
+            Some((date(2026, 3, 11), date(2026, 3, 17))),
+        ));
+        assert_eq!(
+            anchored[0],
+            (date(2026, 3, 14), constants::TEMPORAL_MATCH_SCORE)
+        );
+        assert_eq!(anchored.len(), 7);
+    }
+
+    #[test]
+    fn test_node_scores_decay_down_to_zero() {
+        let score = constants::TEMPORAL_MATCH_SCORE;
+        assert_eq!(node_score(score, 0), score);
+        assert!(node_score(score, 1) < score);
+        assert_eq!(node_score(score, 1_000), 0.0);
+    }
+}
@@ -0,0 +1,45 @@
+//! Builders and generator doubles for the pipeline tests
+
+use crate::generation::TextGenerator;
+use crate::SearchResult;
+use async_trait::async_trait;
+use nodespace_core_types::{Node, NodeSpaceError, NodeSpaceResult};
+use serde_json::json;
+
+/// Search result for a new text node
+pub(crate) fn result(content: &str, score: f32) -> SearchResult {
+    let node = Node::new("text".to_string(), json!(content));
+    SearchResult {
+        node_id: node.id.clone(),
+        node,
+        score,
+    }
+}
+
+/// Generator double answering every prompt the same way
+pub(crate) struct StubGenerator(Option<&'static str>);
+
+impl StubGenerator {
+    /// Always answers `response`
+    pub(crate) fn replying(response: &'static str) -> Self {
+        Self(Some(response))
+    }
+
+    /// Fails every call, like a model that is not loaded
+    pub(crate) fn offline() -> Self {
+        Self(None)
+    }
+}
+
+#[async_trait]
+impl TextGenerator for StubGenerator {
+    async fn generate(&self, _prompt: &str) -> NodeSpaceResult<String> {
+        match self.0 {
+            Some(response) => Ok(response.to_string()),
+            None => Err(NodeSpaceError::InternalError {
+                message: "model offline".to_string(),
+                service: "test".to_string(),
+            }),
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! User timezone for resolving "today" and placing content on date nodes
+//!
+//! Date nodes are keyed by `NaiveDate`, so the instant a note is taken has to
+//! be converted to the user's local calendar date before picking its date
+//! node. Zones are IANA names such as `Europe/Rome`, resolved with the tz
+//! database bundled by `chrono-tz`, which also carries the legacy rule zones
+//! such as `EST5EDT` and `CET`. An unknown zone in the configuration falls back
+//! to UTC with a warning, so it never stops the configuration from loading.
+
+use crate::{CoreLogic, DataStore, NLPEngine, NodeSpaceService};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Offset, Utc};
+use chrono_tz::Tz;
+use nodespace_core_types::{NodeId, NodeSpaceError, NodeSpaceResult, ValidationError};
+use nodespace_data_store::NodeType;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Timezone used for the user's calendar dates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct UserTimezone {
+    tz: Tz,
+}
+
+impl UserTimezone {
+    pub fn utc() -> Self {
+        Self { tz: Tz::UTC }
+    }
+
+    /// Parse an IANA name such as `Europe/Berlin`, ignoring case
+    pub fn parse(name: &str) -> NodeSpaceResult<Self> {
+        let name = name.trim();
+        let tz = iana_zone(name).ok_or_else(|| {
+            NodeSpaceError::Validation(ValidationError::InvalidFormat {
+                field: "timezone".to_string(),
+                expected: "an IANA timezone name".to_string(),
+                actual: name.to_string(),
+                examples: vec![
+                    "Europe/Berlin".to_string(),
+                    "America/New_York".to_string(),
+                    "EST5EDT".to_string(),
+                ],
+            })
+        })?;
+        Ok(Self { tz })
+    }
+
+    /// Canonical IANA name of the zone
+    pub fn name(&self) -> &'static str {
+        self.tz.name()
+    }
+
+    /// UTC offset in effect at `instant`
+    pub fn offset_at(&self, instant: DateTime<Utc>) -> FixedOffset {
+        instant.with_timezone(&self.tz).offset().fix()
+    }
+
+    /// Local date and time at `instant`
+    pub fn local_datetime(&self, instant: DateTime<Utc>) -> NaiveDateTime {
+        instant.with_timezone(&self.tz).naive_local()
+    }
+
+    /// Local calendar date at `instant`, which decides its date node
+    pub fn date_at(&self, instant: DateTime<Utc>) -> NaiveDate {
+        self.local_datetime(instant).date()
+    }
+
+    /// Current local date
+    pub fn today(&self) -> NaiveDate {
+        self.date_at(Utc::now())
+    }
+}
+
+/// IANA zone by name, ignoring case
+fn iana_zone(name: &str) -> Option<Tz> {
+    name.parse().ok().or_else(|| {
+        chrono_tz::TZ_VARIANTS
+            .iter()
+            .find(|tz| tz.name().eq_ignore_ascii_case(name))
+            .copied()
+    })
+}
+
+impl Default for UserTimezone {
+    fn default() -> Self {
+        Self::utc()
+    }
+}
+
+impl fmt::Display for UserTimezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Configured zones that cannot be resolved fall back to UTC with a warning
+impl From<String> for UserTimezone {
+    fn from(name: String) -> Self {
+        Self::parse(&name).unwrap_or_else(|_| {
+            log::warn!("⚠️ Unknown timezone '{}', using UTC instead", name);
+            Self::utc()
+        })
+    }
+}
+
+impl From<UserTimezone> for String {
+    fn from(timezone: UserTimezone) -> Self {
+        timezone.name().to_string()
+    }
+}
+
+impl<D: DataStore + Send + Sync, N: NLPEngine + Send + Sync> NodeSpaceService<D, N> {
+    /// Configured user timezone
+    pub fn timezone(&self) -> &UserTimezone {
+        &self.config.timezone
+    }
+
+    /// Current date in the user's timezone
+    pub fn today(&self) -> NaiveDate {
+        self.config.timezone.today()
+    }
+
+    /// Date node an instant belongs to in the user's timezone
+    pub fn local_date_at(&self, instant: DateTime<Utc>) -> NaiveDate {
+        self.config.timezone.date_at(instant)
+    }
+
+    /// Create a node under the date node of the local date at `instant`
+    pub async fn create_node_at(
+        &self,
+        instant: DateTime<Utc>,
+        content: &str,
+        node_type: NodeType,
+        metadata: Option<serde_json::Value>,
+    ) -> NodeSpaceResult<NodeId> {
+        let date = self.local_date_at(instant);
+        self.create_node_for_date(date, content, node_type, metadata)
+            .await
+    }
+
+    /// Create a node under today's date node in the user's timezone
+    pub async fn create_node_for_today(
+        &self,
+        content: &str,
+        node_type: NodeType,
+        metadata: Option<serde_json::Value>,
+    ) -> NodeSpaceResult<NodeId> {
+        self.create_node_at(Utc::now(), content, node_type, metadata)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_late_evening_notes_land_on_the_local_date() {
+        let new_york = UserTimezone::parse("America/New_York").unwrap();
+        let tokyo = UserTimezone::parse("Asia/Tokyo").unwrap();
+        let kolkata = UserTimezone::parse("Asia/Kolkata").unwrap();
+
+        // 11pm in New York on June 3rd is already June 4th in UTC
+        let instant = utc(2026, 6, 4, 3, 0);
+        assert_eq!(new_york.date_at(instant), date(2026, 6, 3));
+        assert_eq!(UserTimezone::utc().date_at(instant), date(2026, 6, 4));
+
+        assert_eq!(tokyo.date_at(utc(2026, 6, 3, 15, 30)), date(2026, 6, 4));
+        assert_eq!(
+            kolkata.offset_at(instant).local_minus_utc(),
+            5 * 3600 + 1800
+        );
+        assert_eq!(UserTimezone::default().name(), "UTC");
+    }
+
+    #[test]
+    fn test_dst_transitions_in_both_hemispheres() {
+        let new_york = UserTimezone::parse("America/New_York").unwrap();
+        let offset = |tz: &UserTimezone, instant| tz.offset_at(instant).local_minus_utc() / 3600;
+
+        // Spring forward 2026-03-08 at 02:00 EST (07:00 UTC)
+        assert_eq!(offset(&new_york, utc(2026, 3, 8, 6, 59)), -5);
+        assert_eq!(offset(&new_york, utc(2026, 3, 8, 7, 0)), -4);
+        // Fall back 2026-11-01 at 02:00 EDT (06:00 UTC)
+        assert_eq!(offset(&new_york, utc(2026, 11, 1, 5, 59)), -4);
+        assert_eq!(offset(&new_york, utc(2026, 11, 1, 6, 0)), -5);
+        // 23:30 local on the night of the change stays on the local date
+        assert_eq!(new_york.date_at(utc(2026, 11, 2, 4, 30)), date(2026, 11, 1));
+
+        // London changes on the last Sunday of March at 01:00 UTC
+        let london = UserTimezone::parse("Europe/London").unwrap();
+        assert_eq!(offset(&london, utc(2026, 3, 29, 0, 59)), 0);
+        assert_eq!(offset(&london, utc(2026, 3, 29, 1, 0)), 1);
+        assert_eq!(london.date_at(utc(2026, 7, 1, 23, 30)), date(2026, 7, 2));
+
+        // Sydney is on daylight time over the new year
+        let sydney = UserTimezone::parse("Australia/Sydney").unwrap();
+        assert_eq!(offset(&sydney, utc(2026, 1, 15, 0, 0)), 11);
+        assert_eq!(offset(&sydney, utc(2026, 6, 15, 0, 0)), 10);
+        // Daylight time ends 2026-04-05 at 03:00 AEDT (16:00 UTC on the 4th)
+        assert_eq!(offset(&sydney, utc(2026, 4, 4, 15, 59)), 11);
+        assert_eq!(offset(&sydney, utc(2026, 4, 4, 16, 0)), 10);
+    }
+
+    #[test]
+    fn test_legacy_rule_zones_resolve_and_invalid_zones_are_rejected() {
+        let eastern = UserTimezone::parse("EST5EDT").unwrap();
+        let new_york = UserTimezone::parse("America/New_York").unwrap();
+        let cet = UserTimezone::parse("CET").unwrap();
+        let berlin = UserTimezone::parse("Europe/Berlin").unwrap();
+        for instant in [
+            utc(2026, 1, 15, 12, 0),
+            utc(2026, 3, 8, 7, 0),
+            utc(2026, 3, 29, 0, 59),
+            utc(2026, 3, 29, 1, 0),
+            utc(2026, 7, 1, 12, 0),
+            utc(2026, 10, 25, 1, 0),
+        ] {
+            assert_eq!(eastern.offset_at(instant), new_york.offset_at(instant));
+            assert_eq!(cet.offset_at(instant), berlin.offset_at(instant));
+        }
+        assert_eq!(
+            cet.offset_at(utc(2026, 7, 1, 12, 0)).local_minus_utc(),
+            2 * 3600
+        );
+
+        assert!(UserTimezone::parse("Mars/Olympus_Mons").is_err());
+        assert!(UserTimezone::parse("EST5EDT,M3.2.0,M11.1.0").is_err());
+
+        let json = serde_json::to_string(&UserTimezone::parse("europe/berlin").unwrap()).unwrap();
+        assert_eq!(json, "\"Europe/Berlin\"");
+    }
+
+    #[test]
+    fn test_any_iana_zone_is_accepted_and_unknown_config_falls_back_to_utc() {
+        let rome = UserTimezone::parse("Europe/Rome").unwrap();
+        assert_eq!(rome.date_at(utc(2026, 7, 1, 22, 30)), date(2026, 7, 2));
+        let dubai = UserTimezone::parse("asia/dubai").unwrap();
+        assert_eq!(
+            dubai.offset_at(utc(2026, 1, 1, 0, 0)).local_minus_utc(),
+            4 * 3600
+        );
+        let kathmandu: UserTimezone = serde_json::from_str("\"Asia/Kathmandu\"").unwrap();
+        assert_eq!(
+            kathmandu.offset_at(utc(2026, 1, 1, 0, 0)).local_minus_utc(),
+            5 * 3600 + 45 * 60
+        );
+
+        let fallback: UserTimezone = serde_json::from_str("\"nowhere\"").unwrap();
+        assert_eq!(fallback, UserTimezone::utc());
+
+        let mut config = serde_json::to_value(crate::NodeSpaceConfig::default()).unwrap();
+        config["timezone"] = serde_json::json!("Mars/Olympus_Mons");
+        let config: crate::NodeSpaceConfig = serde_json::from_value(config).unwrap();
+        assert_eq!(config.timezone.name(), "UTC");
+    }
+}